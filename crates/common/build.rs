@@ -0,0 +1,58 @@
+//! Generate `$OUT_DIR/wasm.rs` from the DNA sources so the embedded DNA can't drift from them.
+//!
+//! Set `SNAPMAIL_DNA_PATH` to a `.dna` bundle, or `SNAPMAIL_ZOMES_PATH` to a folder of zome wasms
+//! named `<zome>.wasm` (integrity zomes are those listed in `SNAPMAIL_INTEGRITY_ZOMES`, comma separated).
+//! Without them, the `src/wasm.rs` written by the `rustify-wasm` tool is copied as is.
+
+use rustify_wasm::*;
+use std::path::PathBuf;
+
+const DNA_PATH_ENV_VAR: &str = "SNAPMAIL_DNA_PATH";
+const ZOMES_PATH_ENV_VAR: &str = "SNAPMAIL_ZOMES_PATH";
+const INTEGRITY_ZOMES_ENV_VAR: &str = "SNAPMAIL_INTEGRITY_ZOMES";
+const DEFAULT_INTEGRITY_ZOMES: &str = "snapmail_model";
+
+fn main() -> anyhow::Result<()> {
+   println!("cargo:rerun-if-env-changed={}", DNA_PATH_ENV_VAR);
+   println!("cargo:rerun-if-env-changed={}", ZOMES_PATH_ENV_VAR);
+   println!("cargo:rerun-if-env-changed={}", INTEGRITY_ZOMES_ENV_VAR);
+   println!("cargo:rerun-if-env-changed={}", SOURCE_VERSION_ENV_VAR);
+   let output = PathBuf::from(std::env::var("OUT_DIR")?).join("wasm.rs");
+   let source = if let Ok(dna_path) = std::env::var(DNA_PATH_ENV_VAR) {
+      println!("cargo:rerun-if-changed={}", dna_path);
+      DnaSource::Bundle(PathBuf::from(dna_path))
+   } else if let Ok(zomes_path) = std::env::var(ZOMES_PATH_ENV_VAR) {
+      let integrity_names = std::env::var(INTEGRITY_ZOMES_ENV_VAR).unwrap_or(DEFAULT_INTEGRITY_ZOMES.to_string());
+      let integrity_names: Vec<&str> = integrity_names.split(',').map(|name| name.trim()).collect();
+      let mut integrity = Vec::new();
+      let mut coordinator = Vec::new();
+      for entry in std::fs::read_dir(&zomes_path)? {
+         let path = entry?.path();
+         if path.extension().map(|ext| ext != "wasm").unwrap_or(true) {
+            continue;
+         }
+         println!("cargo:rerun-if-changed={}", path.display());
+         let name = path.file_stem().unwrap().to_string_lossy().to_string();
+         if integrity_names.contains(&name.as_str()) {
+            integrity.push((name, path));
+         } else {
+            coordinator.push((name, path));
+         }
+      }
+      DnaSource::Zomes { dna_name: "snapmail".to_string(), integrity, coordinator }
+   } else {
+      let generated = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR")?).join("src").join("wasm.rs");
+      println!("cargo:rerun-if-changed={}", generated.display());
+      if !generated.exists() {
+         anyhow::bail!(
+            "No embedded DNA: set {} or {}, or run `rustify-wasm` to generate {:?}",
+            DNA_PATH_ENV_VAR, ZOMES_PATH_ENV_VAR, generated,
+         );
+      }
+      std::fs::copy(&generated, &output)?;
+      return Ok(());
+   };
+   let info = rustify_blocking(&source, &output, None)?;
+   println!("cargo:warning=Embedded DNA {} ({}) regenerated", info.dna_hash, info.source_version);
+   Ok(())
+}
@@ -0,0 +1,56 @@
+//! The snapmail zome API, independent of how it is reached.
+//!
+//! [`SnapmailCell`](crate::cell::SnapmailCell) calls the zome of a running conductor.
+//! `MockSnapmail`, with the `test-support` feature, keeps everything in memory,
+//! so command and rendering logic can be exercised without a conductor.
+
+use holochain_types::dna::*;
+use holochain_zome_types::*;
+use snapmail::{
+   api_error::*,
+   file::*,
+   handle::*,
+   mail::*,
+   mail::entries::*,
+};
+use std::collections::HashMap;
+
+
+/// Calls of the snapmail zome for one agent
+pub trait SnapmailApi: Send + Sync {
+   /// Agent making the calls
+   fn my_agent_pubkey(&self) -> AgentPubKey;
+   /// Number of peers known on the network
+   fn peer_count(&self) -> anyhow::Result<usize>;
+
+   // -- Handles
+
+   fn get_my_handle(&self) -> SnapmailApiResult<String>;
+   fn set_handle(&self, handle: String) -> SnapmailApiResult<EntryHash>;
+   fn get_all_handles(&self) -> SnapmailApiResult<Vec<HandleItem>>;
+   fn find_agent(&self, handle: String) -> SnapmailApiResult<Vec<AgentPubKey>>;
+   fn ping_agent(&self, agent: AgentPubKey) -> SnapmailApiResult<bool>;
+   fn get_enc_key(&self, agent: AgentPubKey) -> SnapmailApiResult<X25519PubKey>;
+
+   // -- Mails
+
+   fn send_mail(&self, input: SendMailInput) -> SnapmailApiResult<ActionHash>;
+   fn get_all_mails(&self) -> SnapmailApiResult<Vec<MailItem>>;
+   fn get_mail(&self, ah: ActionHash) -> SnapmailApiResult<GetMailOutput>;
+   fn delete_mail(&self, ah: ActionHash) -> SnapmailApiResult<DeleteMailOutput>;
+   fn acknowledge_mail(&self, ah: ActionHash) -> SnapmailApiResult<EntryHash>;
+   fn check_mail_inbox(&self) -> SnapmailApiResult<Vec<ActionHash>>;
+   fn check_ack_inbox(&self) -> SnapmailApiResult<Vec<EntryHash>>;
+   fn get_outmail_state(&self, ah: ActionHash) -> SnapmailApiResult<OutMailState>;
+   fn get_outmail_delivery_state(&self, ah: ActionHash) -> SnapmailApiResult<HashMap<AgentPubKey, DeliveryState>>;
+   fn resend_outmails(&self) -> SnapmailApiResult<Vec<ActionHash>>;
+   fn resend_outacks(&self) -> SnapmailApiResult<Vec<ActionHash>>;
+   fn request_acks(&self) -> SnapmailApiResult<u32>;
+
+   // -- Files
+
+   fn write_chunk(&self, input: FileChunk) -> SnapmailApiResult<EntryHash>;
+   fn write_manifest(&self, input: WriteManifestInput) -> SnapmailApiResult<ActionHash>;
+   fn get_manifest(&self, address: AnyDhtHash) -> SnapmailApiResult<FileManifest>;
+   fn get_chunk(&self, eh: EntryHash) -> SnapmailApiResult<String>;
+}
@@ -1,18 +1,20 @@
-use holochain::conductor::ConductorHandle;
+use crate::api::SnapmailApi;
 use snapmail::file::*;
+use snapmail::mail::entries::AttachmentInfo;
 // use snapmail_model::FileChunk
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use holochain_types::dna::*;
 use holochain_zome_types::*;
 use std::io::prelude::*;
 use std::io::Result;
+use crate::metrics::METRICS;
 
 // FIXME
 pub const FILE_MAX_SIZE: usize = 1000000;
 pub const CHUNK_MAX_SIZE: usize = 16000;
 
 ///
-pub fn write_attachment(conductor: ConductorHandle, filepath: PathBuf) -> Result<ActionHash> {
+pub fn write_attachment(cell: &dyn SnapmailApi, filepath: PathBuf) -> Result<ActionHash> {
    /// Load file
    let maybe_filename = filepath.file_name();
    let filename = if let Some(filename) = maybe_filename {
@@ -60,7 +62,7 @@ pub fn write_attachment(conductor: ConductorHandle, filepath: PathBuf) -> Result
          chunk_index: i,
          chunk: chunk_b64,
       };
-      let hh = snapmail_write_chunk(conductor.clone(), chunk_input)
+      let hh = cell.write_chunk(chunk_input)
          .map_err(|_err| std::io::Error::from(std::io::ErrorKind::Other))?;
       chunk_hh_list.push(hh);
       i += 1;
@@ -74,14 +76,18 @@ pub fn write_attachment(conductor: ConductorHandle, filepath: PathBuf) -> Result
       orig_filesize: file_meta.len() as usize,
       chunks: chunk_hh_list,
    };
-   let res = snapmail_write_manifest(conductor, input)
+   let res = cell.write_manifest(input)
       .map_err(|_err| std::io::Error::from(std::io::ErrorKind::Other))?;
+   METRICS.attachment_bytes_uploaded.inc_by(file_meta.len());
    Ok(res)
 }
 
-///
-pub fn get_attachment(conductor: ConductorHandle, eh: EntryHash, path: PathBuf) -> std::io::Result<PathBuf> {
-   let manifest = snapmail_get_manifest(conductor.clone(), AnyDhtHash::from(eh))
+/// Download an attachment into a folder.
+/// Fails if the data does not match the manifest's hash.
+/// Never overwrites a different file: a number is appended to the filename instead.
+/// If the same file is already there, its path is returned.
+pub fn get_attachment(cell: &dyn SnapmailApi, eh: EntryHash, path: PathBuf) -> std::io::Result<PathBuf> {
+   let manifest = cell.get_manifest(AnyDhtHash::from(eh))
       .map_err(|_err| std::io::Error::from(std::io::ErrorKind::Other))?;
 
    // /// Print
@@ -92,15 +98,78 @@ pub fn get_attachment(conductor: ConductorHandle, eh: EntryHash, path: PathBuf)
    let mut data: Vec<u8> = Vec::new();
 
    for chunk_eh in manifest.chunks {
-      let chunk_b64 = snapmail_get_chunk(conductor.clone(), chunk_eh).unwrap();
-      let chunk = base64::decode_config(chunk_b64.clone(), base64::URL_SAFE_NO_PAD).unwrap();
+      let chunk_b64 = cell.get_chunk(chunk_eh)
+         .map_err(|_err| std::io::Error::from(std::io::ErrorKind::Other))?;
+      let chunk = base64::decode_config(chunk_b64.clone(), base64::URL_SAFE_NO_PAD)
+         .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
       // msg!(" chunk size: {} KiB ({} KiB)", chunk.len() / 1024, chunk_b64.len() / 1024);
       data.extend(&chunk);
    }
 
+   /// Integrity check
+   let data_hash = holo_hash::encode::blake2b_256(data.as_slice());
+   if String::from_utf8_lossy(&data_hash) != manifest.data_hash {
+      let msg = format!("Attachment \"{}\" does not match its hash", manifest.filename);
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, msg));
+   }
+
    /// Write file to local dir
-   let filepath = path.join(manifest.filename);
+   let filepath = match download_target(&path, &manifest.filename, &data) {
+      DownloadTarget::Existing(filepath) => return Ok(filepath),
+      DownloadTarget::Free(filepath) => filepath,
+   };
    let mut file = std::fs::File::create(filepath.clone())?;
    file.write_all(&data)?;
+   METRICS.attachment_bytes_downloaded.inc_by(data.len() as u64);
    Ok(filepath)
 }
+
+/// Filename without any folder, so a manifest can not write outside the download folder
+fn safe_filename(filename: &str) -> String {
+   Path::new(filename).file_name()
+      .map(|name| name.to_string_lossy().to_string())
+      .unwrap_or_else(|| "__no_filename_found_".to_string())
+}
+
+/// Where to download a file
+enum DownloadTarget {
+   /// A file with the same content is already there
+   Existing(PathBuf),
+   /// Path not taken yet
+   Free(PathBuf),
+}
+
+/// Path in `folder` to write a file to, ex: "photo (2).jpg" if "photo.jpg" is taken,
+/// or the path of a file with the same content already there.
+fn download_target(folder: &Path, filename: &str, data: &[u8]) -> DownloadTarget {
+   let filename = safe_filename(filename);
+   let first = folder.join(&filename);
+   let stem = first.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+   let extension = first.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+   let mut candidate = first;
+   let mut index = 2;
+   loop {
+      if !candidate.exists() {
+         return DownloadTarget::Free(candidate);
+      }
+      if std::fs::read(&candidate).map(|existing| existing == data).unwrap_or(false) {
+         return DownloadTarget::Existing(candidate);
+      }
+      candidate = folder.join(format!("{} ({}){}", stem, index, extension));
+      index += 1;
+   }
+}
+
+
+/// Download all attachments of a mail into a folder, created if needed.
+/// Returns the path or the error of each attachment, in order.
+pub fn get_mail_attachments(
+   cell: &dyn SnapmailApi,
+   attachments: &[AttachmentInfo],
+   folder: &Path,
+) -> std::io::Result<Vec<std::io::Result<PathBuf>>> {
+   std::fs::create_dir_all(folder)?;
+   Ok(attachments.iter()
+      .map(|attachment| get_attachment(cell, attachment.manifest_eh.clone(), folder.to_path_buf()))
+      .collect())
+}
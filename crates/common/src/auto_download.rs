@@ -0,0 +1,97 @@
+//! Automatic download of the attachments of received mails.
+//!
+//! When enabled in the settings, the attachments of each new mail are written in a folder
+//! per sender and day inside the download folder, ex: `<downloads>/alex/2024-05-17/`.
+//! Mails from blocked senders, spam and trashed mails are skipped.
+
+use crate::{
+   api::SnapmailApi,
+   attachment::get_mail_attachments,
+   blocklist::*,
+   settings::*,
+   trash::Trash,
+};
+use chrono::{Local, TimeZone};
+use holochain_types::dna::*;
+use snapmail::mail::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+
+/// Folder name safe on every platform
+fn safe_folder_name(name: &str) -> String {
+   let name: String = name.trim().chars()
+      .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+      .collect();
+   if name.is_empty() || name.chars().all(|c| c == '.') {
+      return "_unknown_".to_string();
+   }
+   name
+}
+
+/// Folder of a mail's attachments: the download folder joined with the template,
+/// where `{sender}` is the sender's handle and `{date}` the day the mail was received
+pub fn mail_folder(download_folder: &Path, template: &str, sender: &str, item: &MailItem) -> PathBuf {
+   let date = Local.timestamp_opt(item.date, 0).single()
+      .map(|date| date.format("%Y-%m-%d").to_string())
+      .unwrap_or_else(|| "unknown-date".to_string());
+   let mut folder = download_folder.to_path_buf();
+   for part in template.split('/').filter(|part| !part.is_empty()) {
+      let part = part.replace("{sender}", sender).replace("{date}", &date);
+      folder.push(safe_folder_name(&part));
+   }
+   folder
+}
+
+
+/// Download the attachments of new received mails, if enabled. Returns the files written.
+pub fn auto_download(sid: &str, api: &dyn SnapmailApi, new_mails: &[ActionHash]) -> anyhow::Result<Vec<PathBuf>> {
+   let settings = SessionSettings::load(sid)?;
+   if !settings.auto_download.enabled || new_mails.is_empty() {
+      return Ok(Vec::new());
+   }
+   let handle_map: HashMap<AgentPubKey, String> = api.get_all_handles()?.into_iter()
+      .map(|item| (item.agentId, item.name))
+      .collect();
+   let classes = classify_network_mails(sid, api)?;
+   let trash = Trash::load(sid)?;
+   let download_folder = settings.download_folder();
+   let mut paths = Vec::new();
+   for item in api.get_all_mails()?.iter().filter(|item| new_mails.contains(&item.address)) {
+      if !matches!(item.state, MailState::In(_))
+         || item.mail.attachments.is_empty()
+         || trash.contains(&item.address)
+         || classes.get(&item.address).copied().unwrap_or(MailClass::Normal) != MailClass::Normal {
+         continue;
+      }
+      let attachments: Vec<_> = item.mail.attachments.iter()
+         .filter(|attachment| settings.auto_download.max_size.map(|max| attachment.orig_filesize <= max).unwrap_or(true))
+         .cloned()
+         .collect();
+      let sender = handle_map.get(&item.author).cloned().unwrap_or_else(|| item.author.to_string());
+      let folder = mail_folder(&download_folder, &settings.auto_download.folder_template, &sender, item);
+      for (attachment, res) in attachments.iter().zip(get_mail_attachments(api, &attachments, &folder)?) {
+         match res {
+            Ok(path) => paths.push(path),
+            Err(e) => err_msg!("Failed to download attachment \"{}\" of mail {}: {}", attachment.filename, item.address, e),
+         }
+      }
+   }
+   Ok(paths)
+}
+
+/// Download the attachments of new received mails, if enabled, and log the files written
+pub fn run_auto_download(sid: &str, api: &dyn SnapmailApi, new_mails: &[ActionHash]) -> Vec<PathBuf> {
+   match auto_download(sid, api, new_mails) {
+      Ok(paths) => {
+         for path in paths.iter() {
+            msg!("Attachment downloaded: {:?}", path);
+         }
+         paths
+      },
+      Err(e) => {
+         err_msg!("Attachment auto-download failed: {}", e);
+         Vec::new()
+      },
+   }
+}
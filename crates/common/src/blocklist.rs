@@ -0,0 +1,207 @@
+//! Sender blocklist and spam filtering.
+//!
+//! The zome accepts mail from any agent of the network, so filtering is done locally:
+//! mails from blocked senders are hidden, or deleted with the `Delete` block action, and never acknowledged.
+//! Mails of senders we never wrote to who send too many mails in a short time are flagged as spam.
+
+use crate::{
+   api::SnapmailApi,
+   globals::*,
+   session::session_path,
+   settings::*,
+   trash::is_purged,
+};
+use holochain_types::dna::*;
+use serde::{Serialize, Deserialize};
+use snapmail::mail::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
+
+
+/// How a received mail is filed
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum MailClass {
+   Normal,
+   /// Sender is blocked
+   Blocked,
+   /// Unknown sender sending too many mails
+   Spam,
+}
+
+
+/// Blocked senders of a session
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Blocklist {
+   /// Blocked agent key -> handle when blocked
+   pub agents: BTreeMap<String, String>,
+   /// Handle patterns to block. `*` matches any characters. Case insensitive.
+   pub patterns: Vec<String>,
+   /// Agent keys never flagged as spam
+   pub allowed: BTreeSet<String>,
+}
+
+impl Blocklist {
+   ///
+   pub fn filepath(sid: &str) -> PathBuf {
+      session_path(sid).join(BLOCKLIST_FILENAME)
+   }
+
+   /// Blocklist of a session. Empty if there is no blocklist file.
+   pub fn load(sid: &str) -> anyhow::Result<Self> {
+      let filepath = Self::filepath(sid);
+      match std::fs::read_to_string(&filepath) {
+         Ok(yaml) => Ok(serde_yaml::from_str::<Blocklist>(&yaml)?),
+         Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Blocklist::default()),
+         Err(e) => anyhow::bail!("Failed to read blocklist file {:?}: {}", filepath, e),
+      }
+   }
+
+   ///
+   pub fn save(&self, sid: &str) -> anyhow::Result<()> {
+      let yaml = serde_yaml::to_string(self)?;
+      std::fs::write(Self::filepath(sid), yaml)?;
+      Ok(())
+   }
+
+   /// Block an agent. Returns false if it already was.
+   pub fn block(&mut self, agent: &AgentPubKey, handle: &str) -> bool {
+      self.allowed.remove(&agent.to_string());
+      self.agents.insert(agent.to_string(), handle.to_string()).is_none()
+   }
+
+   /// Unblock an agent and never flag it as spam. Returns false if it was not blocked.
+   pub fn unblock(&mut self, agent: &AgentPubKey) -> bool {
+      self.allowed.insert(agent.to_string());
+      self.agents.remove(&agent.to_string()).is_some()
+   }
+
+   /// Returns false if the pattern was already there
+   pub fn add_pattern(&mut self, pattern: &str) -> anyhow::Result<bool> {
+      let pattern = pattern.trim().to_lowercase();
+      if pattern.is_empty() || pattern.chars().all(|c| c == '*') {
+         anyhow::bail!("Pattern \"{}\" would block everyone", pattern);
+      }
+      if self.patterns.contains(&pattern) {
+         return Ok(false);
+      }
+      self.patterns.push(pattern);
+      Ok(true)
+   }
+
+   /// Returns false if there was no such pattern
+   pub fn remove_pattern(&mut self, pattern: &str) -> bool {
+      let pattern = pattern.trim().to_lowercase();
+      let len = self.patterns.len();
+      self.patterns.retain(|current| *current != pattern);
+      self.patterns.len() != len
+   }
+
+   /// True if the agent, or its handle, is blocked
+   pub fn is_blocked(&self, agent: &AgentPubKey, maybe_handle: Option<&str>) -> bool {
+      if self.agents.contains_key(&agent.to_string()) {
+         return true;
+      }
+      let Some(handle) = maybe_handle else { return false };
+      let handle = handle.to_lowercase();
+      self.patterns.iter().any(|pattern| matches_pattern(pattern, &handle))
+   }
+}
+
+
+/// Glob match where `*` matches any characters
+pub(crate) fn matches_pattern(pattern: &str, text: &str) -> bool {
+   let parts: Vec<&str> = pattern.split('*').collect();
+   if parts.len() == 1 {
+      return pattern == text;
+   }
+   let first = parts[0];
+   let last = parts[parts.len() - 1];
+   if !text.starts_with(first) || !text[first.len()..].ends_with(last) {
+      return false;
+   }
+   let mut rest = &text[first.len()..text.len() - last.len()];
+   for part in parts[1..parts.len() - 1].iter() {
+      match rest.find(part) {
+         Some(index) => rest = &rest[index + part.len()..],
+         None => return false,
+      }
+   }
+   true
+}
+
+
+/// Class of every received mail.
+/// Known senders are the agents we sent mail to, and the allowed ones.
+pub fn classify_mails(
+   items: &[MailItem],
+   handle_map: &HashMap<AgentPubKey, String>,
+   blocklist: &Blocklist,
+   filter: &SpamFilter,
+) -> HashMap<ActionHash, MailClass> {
+   let mut known: HashSet<AgentPubKey> = HashSet::new();
+   for item in items.iter().filter(|item| matches!(item.state, MailState::Out(_))) {
+      known.extend(item.mail.to.iter().chain(item.mail.cc.iter()).chain(item.bcc.iter()).cloned());
+   }
+   /// Dates of received mails by sender
+   let mut dates_by_sender: HashMap<AgentPubKey, Vec<i64>> = HashMap::new();
+   for item in items.iter().filter(|item| matches!(item.state, MailState::In(_))) {
+      dates_by_sender.entry(item.author.clone()).or_default().push(item.date);
+   }
+   /// Only the mails of a burst are spam, not the sender's earlier or later ones
+   let flood_dates: HashMap<AgentPubKey, HashSet<i64>> = dates_by_sender.into_iter()
+      .filter(|(sender, _)| !known.contains(sender) && !blocklist.allowed.contains(&sender.to_string()))
+      .map(|(sender, dates)| (sender, filter.flood_dates(&dates)))
+      .collect();
+
+   let mut classes = HashMap::new();
+   for item in items.iter().filter(|item| matches!(item.state, MailState::In(_))) {
+      let maybe_handle = handle_map.get(&item.author).map(|handle| handle.as_str());
+      let class = if blocklist.is_blocked(&item.author, maybe_handle) {
+         MailClass::Blocked
+      } else if flood_dates.get(&item.author).map(|dates| dates.contains(&item.date)).unwrap_or(false) {
+         MailClass::Spam
+      } else {
+         MailClass::Normal
+      };
+      classes.insert(item.address.clone(), class);
+   }
+   classes
+}
+
+
+/// Class of the received mails of a network
+pub fn classify_network_mails(sid: &str, api: &dyn SnapmailApi) -> anyhow::Result<HashMap<ActionHash, MailClass>> {
+   let settings = SessionSettings::load(sid)?;
+   let blocklist = Blocklist::load(sid)?;
+   let handle_map = api.get_all_handles()?.into_iter()
+      .map(|item| (item.agentId, item.name))
+      .collect();
+   Ok(classify_mails(&api.get_all_mails()?, &handle_map, &blocklist, &settings.spam_filter))
+}
+
+/// True if a received mail may be acknowledged: its sender is not blocked
+pub fn may_acknowledge(sid: &str, api: &dyn SnapmailApi, ah: &ActionHash) -> anyhow::Result<bool> {
+   let classes = classify_network_mails(sid, api)?;
+   Ok(classes.get(ah) != Some(&MailClass::Blocked))
+}
+
+/// With the `Delete` block action, delete the mails of blocked senders. Returns the deleted mails.
+pub fn apply_block_action(sid: &str, api: &dyn SnapmailApi) -> anyhow::Result<Vec<ActionHash>> {
+   if SessionSettings::load(sid)?.block_action != BlockAction::Delete {
+      return Ok(Vec::new());
+   }
+   let items = api.get_all_mails()?;
+   let classes = classify_network_mails(sid, api)?;
+   let mut deleted = Vec::new();
+   for item in items.iter().filter(|item| !is_purged(item)) {
+      if classes.get(&item.address) != Some(&MailClass::Blocked) {
+         continue;
+      }
+      match api.delete_mail(item.address.clone()) {
+         Ok(_) => deleted.push(item.address.clone()),
+         Err(e) => err_msg!("Failed to delete blocked mail {}: {:?}", item.address, e),
+      }
+   }
+   Ok(deleted)
+}
@@ -0,0 +1,162 @@
+//! Zome calls addressed to one snapmail cell of a conductor.
+//!
+//! The `snapmail_*` wrappers of the zome crate always call the conductor's first cell.
+//! A [`SnapmailCell`] calls the zome of a specific cell, so a session can take part in several networks.
+//! Its zome calls are the [`SnapmailApi`] implementation backed by a conductor.
+
+use crate::{
+   globals::*,
+   api::SnapmailApi,
+   conductor::dump_cell_state,
+   metrics::timed_call,
+};
+use holochain::conductor::ConductorHandle;
+use holochain_types::dna::*;
+use holochain_zome_types::*;
+use serde::{de::DeserializeOwned, Serialize};
+use snapmail::{
+   SNAPMAIL_DEFAULT_COORDINATOR_ZOME_NAME,
+   api_error::*,
+   file::*,
+   handle::*,
+   mail::*,
+   mail::entries::*,
+};
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+
+/// A snapmail cell of a conductor, reached through its network alias
+#[derive(Clone)]
+pub struct SnapmailCell {
+   pub alias: String,
+   pub conductor: ConductorHandle,
+   pub cell_id: CellId,
+}
+
+impl SnapmailCell {
+   ///
+   pub fn new(alias: &str, conductor: ConductorHandle, cell_id: CellId) -> Self {
+      Self { alias: alias.to_string(), conductor, cell_id }
+   }
+
+   ///
+   pub fn agent_pubkey(&self) -> &AgentPubKey {
+      self.cell_id.agent_pubkey()
+   }
+
+   ///
+   pub fn dna_hash(&self) -> &DnaHash {
+      self.cell_id.dna_hash()
+   }
+
+   /// Call a function of the snapmail zome of this cell
+   pub fn call<I, O>(&self, fn_name: &str, payload: I) -> SnapmailApiResult<O>
+      where
+         I: Serialize + Debug,
+         O: DeserializeOwned + Debug,
+   {
+      timed_call(fn_name, || {
+         let result = holochain_util::tokio_helper::block_on(async {
+            self.conductor.easy_call_zome(
+               self.cell_id.agent_pubkey(),
+               None,
+               self.cell_id.clone(),
+               SNAPMAIL_DEFAULT_COORDINATOR_ZOME_NAME,
+               fn_name,
+               payload,
+            ).await
+         }, *DEFAULT_TIMEOUT);
+         match result {
+            Err(_elapsed) => Err(SnapmailApiError::Unique(format!("Zome call \"{}\" timed out", fn_name))),
+            Ok(Err(e)) => Err(SnapmailApiError::Unique(format!("Zome call \"{}\" failed: {:?}", fn_name, e))),
+            Ok(Ok(output)) => Ok(output),
+         }
+      })
+   }
+}
+
+
+impl SnapmailApi for SnapmailCell {
+   fn my_agent_pubkey(&self) -> AgentPubKey {
+      self.cell_id.agent_pubkey().clone()
+   }
+   fn peer_count(&self) -> anyhow::Result<usize> {
+      dump_cell_state(self.conductor.clone(), &self.cell_id)
+   }
+
+   // -- Handles
+
+   fn get_my_handle(&self) -> SnapmailApiResult<String> {
+      self.call("get_my_handle", ())
+   }
+   fn set_handle(&self, handle: String) -> SnapmailApiResult<EntryHash> {
+      self.call("set_handle", handle)
+   }
+   fn get_all_handles(&self) -> SnapmailApiResult<Vec<HandleItem>> {
+      self.call("get_all_handles", ())
+   }
+   fn find_agent(&self, handle: String) -> SnapmailApiResult<Vec<AgentPubKey>> {
+      self.call("find_agent", handle)
+   }
+   fn ping_agent(&self, agent: AgentPubKey) -> SnapmailApiResult<bool> {
+      self.call("ping_agent", agent)
+   }
+   fn get_enc_key(&self, agent: AgentPubKey) -> SnapmailApiResult<X25519PubKey> {
+      self.call("get_enc_key", agent)
+   }
+
+   // -- Mails
+
+   fn send_mail(&self, input: SendMailInput) -> SnapmailApiResult<ActionHash> {
+      self.call("send_mail", input)
+   }
+   fn get_all_mails(&self) -> SnapmailApiResult<Vec<MailItem>> {
+      self.call("get_all_mails", ())
+   }
+   fn get_mail(&self, ah: ActionHash) -> SnapmailApiResult<GetMailOutput> {
+      self.call("get_mail", ah)
+   }
+   fn delete_mail(&self, ah: ActionHash) -> SnapmailApiResult<DeleteMailOutput> {
+      self.call("delete_mail", ah)
+   }
+   fn acknowledge_mail(&self, ah: ActionHash) -> SnapmailApiResult<EntryHash> {
+      self.call("acknowledge_mail", ah)
+   }
+   fn check_mail_inbox(&self) -> SnapmailApiResult<Vec<ActionHash>> {
+      self.call("check_mail_inbox", ())
+   }
+   fn check_ack_inbox(&self) -> SnapmailApiResult<Vec<EntryHash>> {
+      self.call("check_ack_inbox", ())
+   }
+   fn get_outmail_state(&self, ah: ActionHash) -> SnapmailApiResult<OutMailState> {
+      self.call("get_outmail_state", ah)
+   }
+   fn get_outmail_delivery_state(&self, ah: ActionHash) -> SnapmailApiResult<HashMap<AgentPubKey, DeliveryState>> {
+      self.call("get_outmail_delivery_state", ah)
+   }
+   fn resend_outmails(&self) -> SnapmailApiResult<Vec<ActionHash>> {
+      self.call("resend_outmails", ())
+   }
+   fn resend_outacks(&self) -> SnapmailApiResult<Vec<ActionHash>> {
+      self.call("resend_outacks", ())
+   }
+   fn request_acks(&self) -> SnapmailApiResult<u32> {
+      self.call("request_acks", ())
+   }
+
+   // -- Files
+
+   fn write_chunk(&self, input: FileChunk) -> SnapmailApiResult<EntryHash> {
+      self.call("write_chunk", input)
+   }
+   fn write_manifest(&self, input: WriteManifestInput) -> SnapmailApiResult<ActionHash> {
+      self.call("write_manifest", input)
+   }
+   fn get_manifest(&self, address: AnyDhtHash) -> SnapmailApiResult<FileManifest> {
+      self.call("get_manifest", address)
+   }
+   fn get_chunk(&self, eh: EntryHash) -> SnapmailApiResult<String> {
+      self.call("get_chunk", eh)
+   }
+}
@@ -0,0 +1,377 @@
+//! High-level async API for embedding snapmail in another Rust service.
+//!
+//! A [`SnapmailClient`] owns the conductor of one session and talks to one of its networks.
+//! Zome calls run on tokio's blocking pool, so the client can be used from any async task.
+//! Failures are returned as [`ClientError`] instead of aborting the process.
+//!
+//! ```ignore
+//! let client = SnapmailClient::open("alex", None).await?;
+//! let mut events = client.subscribe();
+//! let ah = client.send(MailDraft::new("Hello", "How are you?").to("billy")).await?;
+//! while let Ok(event) = events.recv().await { /* ... */ }
+//! let report = client.shutdown(Duration::from_secs(30)).await?;
+//! ```
+
+use crate::{
+   globals::*,
+   api::SnapmailApi,
+   attachment::*,
+   cell::SnapmailCell,
+   holochain::try_conductor_from_config,
+   lock::*,
+   networks::network_cell,
+   session::{session_path, setup_session, touch_last_used},
+   shutdown::*,
+};
+use holochain::conductor::ConductorHandle;
+use holochain::conductor::config::ConductorConfig;
+use holochain_types::dna::*;
+use holochain_types::signal::Signal;
+use snapmail::{
+   api_error::*,
+   file::*,
+   mail::*,
+   mail::entries::*,
+   signal_protocol::SignalProtocol,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_stream::StreamExt;
+
+/// Events kept for subscribers that lag behind
+const EVENT_CAPACITY: usize = 256;
+
+pub type ClientResult<T> = Result<T, ClientError>;
+
+/// Failures of a [`SnapmailClient`]
+#[derive(Error, Debug)]
+pub enum ClientError {
+   #[error("session \"{0}\" not found or not set up")]
+   SessionNotFound(String),
+   #[error("session \"{sid}\" is in use: {reason}")]
+   SessionLocked { sid: String, reason: String },
+   #[error("conductor failed: {0}")]
+   Conductor(String),
+   #[error("network unavailable: {0}")]
+   Network(String),
+   #[error("no agent found with handle \"{0}\"")]
+   UnknownHandle(String),
+   #[error("no mail found at {0}")]
+   MailNotFound(ActionHash),
+   #[error("mail has no recipient")]
+   NoRecipient,
+   #[error("attachment {path:?} failed: {source}")]
+   Attachment { path: PathBuf, source: std::io::Error },
+   #[error("zome call failed: {0}")]
+   Zome(#[from] SnapmailApiError),
+   #[error("client task failed: {0}")]
+   Task(#[from] tokio::task::JoinError),
+}
+
+
+/// Mail to send. Recipients are given by handle.
+#[derive(Debug, Clone, Default)]
+pub struct MailDraft {
+   pub subject: String,
+   pub body: String,
+   pub to: Vec<String>,
+   pub cc: Vec<String>,
+   pub bcc: Vec<String>,
+   /// Files to attach
+   pub attachments: Vec<PathBuf>,
+}
+
+impl MailDraft {
+   ///
+   pub fn new(subject: &str, body: &str) -> Self {
+      Self {
+         subject: subject.to_string(),
+         body: body.to_string(),
+         ..Default::default()
+      }
+   }
+
+   ///
+   pub fn to(mut self, handle: &str) -> Self {
+      self.to.push(handle.to_string());
+      self
+   }
+
+   ///
+   pub fn cc(mut self, handle: &str) -> Self {
+      self.cc.push(handle.to_string());
+      self
+   }
+
+   ///
+   pub fn bcc(mut self, handle: &str) -> Self {
+      self.bcc.push(handle.to_string());
+      self
+   }
+
+   ///
+   pub fn attach(mut self, path: PathBuf) -> Self {
+      self.attachments.push(path);
+      self
+   }
+}
+
+
+/// A mail of the mailbox, as returned by [`SnapmailClient::get`]
+#[derive(Debug, Clone)]
+pub enum MailContent {
+   Received(InMail),
+   Sent(OutMail),
+}
+
+/// Something that happened on the client's network
+#[derive(Debug, Clone)]
+pub enum SnapmailEvent {
+   MailReceived(MailItem),
+   AckReceived { from: AgentPubKey, for_mail: ActionHash },
+   FileReceived(FileManifest),
+}
+
+impl From<SignalProtocol> for SnapmailEvent {
+   fn from(signal: SignalProtocol) -> Self {
+      match signal {
+         SignalProtocol::ReceivedMail(item) => SnapmailEvent::MailReceived(item),
+         SignalProtocol::ReceivedAck(ack) => SnapmailEvent::AckReceived { from: ack.from, for_mail: ack.for_mail },
+         SignalProtocol::ReceivedFile(manifest) => SnapmailEvent::FileReceived(manifest),
+      }
+   }
+}
+
+
+/// Snapmail session running in this process
+pub struct SnapmailClient {
+   sid: String,
+   cell: SnapmailCell,
+   events: broadcast::Sender<SnapmailEvent>,
+   listener: JoinHandle<()>,
+   is_shutdown: bool,
+}
+
+impl SnapmailClient {
+   /// Start the conductor of an existing session and use one of its networks.
+   /// Uses the session's default network if no alias is given.
+   pub async fn open(sid: &str, maybe_network: Option<&str>) -> ClientResult<Self> {
+      let config_path = session_path(sid).join(CONDUCTOR_CONFIG_FILENAME);
+      let config = ConductorConfig::load_yaml(config_path.as_ref())
+         .map_err(|_| ClientError::SessionNotFound(sid.to_string()))?;
+      acquire_session_lock(sid)
+         .map_err(|e| ClientError::SessionLocked { sid: sid.to_string(), reason: e.to_string() })?;
+      let conductor = match try_conductor_from_config(config).await {
+         Ok(conductor) => conductor,
+         Err(e) => {
+            release_session_lock(sid);
+            return Err(ClientError::Conductor(e.to_string()));
+         },
+      };
+      snapshot_pending_work(conductor.clone(), sid).await;
+      touch_last_used(sid);
+      Self::from_conductor(sid, conductor, maybe_network)
+   }
+
+   /// Create a new session on `network_seed` and use it. Its handle is the session ID.
+   pub async fn create(sid: &str, network_seed: String) -> ClientResult<Self> {
+      let (conductor, _dna_hash) = setup_session(sid, network_seed, None, None).await
+         .map_err(|e| ClientError::Conductor(e.to_string()))?;
+      Self::from_conductor(sid, conductor, None)
+   }
+
+   ///
+   fn from_conductor(sid: &str, conductor: ConductorHandle, maybe_network: Option<&str>) -> ClientResult<Self> {
+      let cell = match network_cell(conductor.clone(), sid, maybe_network) {
+         Ok(cell) => cell,
+         Err(e) => {
+            conductor.shutdown();
+            release_session_lock(sid);
+            return Err(ClientError::Network(e.to_string()));
+         },
+      };
+      let (events, _) = broadcast::channel(EVENT_CAPACITY);
+      let listener = tokio::spawn(forward_signals(cell.clone(), events.clone()));
+      Ok(Self {
+         sid: sid.to_string(),
+         cell,
+         events,
+         listener,
+         is_shutdown: false,
+      })
+   }
+
+   /// Run a zome call on the blocking pool
+   async fn call<T, F>(&self, f: F) -> ClientResult<T>
+      where
+         T: Send + 'static,
+         F: FnOnce(&SnapmailCell) -> SnapmailApiResult<T> + Send + 'static,
+   {
+      let cell = self.cell.clone();
+      Ok(tokio::task::spawn_blocking(move || f(&cell)).await??)
+   }
+
+   ///
+   pub fn sid(&self) -> &str {
+      &self.sid
+   }
+
+   /// Alias of the network used
+   pub fn network(&self) -> &str {
+      &self.cell.alias
+   }
+
+   ///
+   pub fn agent_pubkey(&self) -> &AgentPubKey {
+      self.cell.agent_pubkey()
+   }
+
+   /// The underlying zome API, for calls not covered by the client
+   pub fn api(&self) -> &dyn SnapmailApi {
+      &self.cell
+   }
+
+   /// Events of the client's network from now on
+   pub fn subscribe(&self) -> broadcast::Receiver<SnapmailEvent> {
+      self.events.subscribe()
+   }
+
+   /// Agent publishing this handle
+   pub async fn resolve_handle(&self, handle: &str) -> ClientResult<AgentPubKey> {
+      let name = handle.to_string();
+      let agents = self.call(move |cell| cell.find_agent(name)).await?;
+      agents.into_iter().next().ok_or(ClientError::UnknownHandle(handle.to_string()))
+   }
+
+   /// Handle published by an agent, if any
+   pub async fn handle_of(&self, agent: &AgentPubKey) -> ClientResult<Option<String>> {
+      let handles = self.call(|cell| cell.get_all_handles()).await?;
+      Ok(handles.into_iter().find(|item| item.agentId == *agent).map(|item| item.name))
+   }
+
+   /// Send a mail. Attachments are written to the source chain first.
+   pub async fn send(&self, draft: MailDraft) -> ClientResult<ActionHash> {
+      if draft.to.is_empty() && draft.cc.is_empty() && draft.bcc.is_empty() {
+         return Err(ClientError::NoRecipient);
+      }
+      let mut recipients = [Vec::new(), Vec::new(), Vec::new()];
+      for (list, handles) in recipients.iter_mut().zip([&draft.to, &draft.cc, &draft.bcc]) {
+         for handle in handles.iter() {
+            list.push(self.resolve_handle(handle).await?);
+         }
+      }
+      let [to, cc, bcc] = recipients;
+      let mut manifest_address_list = Vec::new();
+      for path in draft.attachments {
+         let cell = self.cell.clone();
+         let path_c = path.clone();
+         let res = tokio::task::spawn_blocking(move || write_attachment(&cell, path_c)).await?;
+         manifest_address_list.push(res.map_err(|source| ClientError::Attachment { path, source })?);
+      }
+      let input = SendMailInput {
+         subject: draft.subject,
+         payload: draft.body,
+         to,
+         cc,
+         bcc,
+         manifest_address_list,
+      };
+      self.call(move |cell| cell.send_mail(input)).await
+   }
+
+   /// All mails of the mailbox, after fetching pending mails and acks
+   pub async fn list(&self) -> ClientResult<Vec<MailItem>> {
+      self.call(|cell| {
+         let _ = cell.check_ack_inbox()?;
+         let _ = cell.check_mail_inbox()?;
+         cell.get_all_mails()
+      }).await
+   }
+
+   ///
+   pub async fn get(&self, ah: &ActionHash) -> ClientResult<MailContent> {
+      let ah_c = ah.clone();
+      let output = self.call(move |cell| cell.get_mail(ah_c)).await?;
+      match output.0 {
+         Some(Ok(inmail)) => Ok(MailContent::Received(inmail)),
+         Some(Err(outmail)) => Ok(MailContent::Sent(outmail)),
+         None => Err(ClientError::MailNotFound(ah.clone())),
+      }
+   }
+
+   /// Acknowledge a received mail
+   pub async fn ack(&self, ah: &ActionHash) -> ClientResult<EntryHash> {
+      let ah = ah.clone();
+      self.call(move |cell| cell.acknowledge_mail(ah)).await
+   }
+
+   ///
+   pub async fn delete(&self, ah: &ActionHash) -> ClientResult<()> {
+      let ah_c = ah.clone();
+      let output = self.call(move |cell| cell.delete_mail(ah_c)).await?;
+      output.0.map(|_| ()).ok_or(ClientError::MailNotFound(ah.clone()))
+   }
+
+   /// Delivery state of a sent mail
+   pub async fn outmail_state(&self, ah: &ActionHash) -> ClientResult<OutMailState> {
+      let ah = ah.clone();
+      self.call(move |cell| cell.get_outmail_state(ah)).await
+   }
+
+   /// Write an attachment in `folder`. Returns the path of the written file.
+   pub async fn download(&self, manifest_eh: &EntryHash, folder: PathBuf) -> ClientResult<PathBuf> {
+      let cell = self.cell.clone();
+      let eh = manifest_eh.clone();
+      let folder_c = folder.clone();
+      tokio::task::spawn_blocking(move || get_attachment(&cell, eh, folder_c)).await?
+         .map_err(|source| ClientError::Attachment { path: folder, source })
+   }
+
+   /// Stop listening, wait for pending publishes, mails and acks (at most `max_wait`),
+   /// then shut the conductor down and release the session.
+   /// The report tells what was still pending if `max_wait` was reached.
+   pub async fn shutdown(mut self, max_wait: Duration) -> ClientResult<ShutdownReport> {
+      self.listener.abort();
+      let report = graceful_shutdown(self.cell.conductor.clone(), &self.sid, max_wait).await;
+      self.is_shutdown = true;
+      release_session_lock(&self.sid);
+      Ok(report)
+   }
+
+   /// Shut down without waiting for pending work
+   fn close(&mut self) {
+      if self.is_shutdown {
+         return;
+      }
+      self.is_shutdown = true;
+      self.listener.abort();
+      self.cell.conductor.shutdown();
+      release_session_lock(&self.sid);
+   }
+}
+
+impl Drop for SnapmailClient {
+   fn drop(&mut self) {
+      self.close();
+   }
+}
+
+
+/// Broadcast the snapmail signals of a cell as events
+async fn forward_signals(cell: SnapmailCell, events: broadcast::Sender<SnapmailEvent>) {
+   let stream = cell.conductor.signal_broadcaster().await.subscribe_merged();
+   tokio::pin!(stream);
+   while let Some(signal) = stream.next().await {
+      let Signal::App(cell_id, app_signal) = signal else { continue };
+      if cell_id != cell.cell_id {
+         continue;
+      }
+      if let Ok(snapmail_signal) = app_signal.into_inner().decode::<SignalProtocol>() {
+         /// No subscriber is not an error
+         let _ = events.send(snapmail_signal.into());
+      }
+   }
+}
@@ -3,10 +3,14 @@ use crate::{
    //config::*,
 };
 use crate::holochain::*;
+use crate::metrics::METRICS;
+use crate::session::touch_last_used;
+use crate::lock::{acquire_session_lock, release_session_lock};
+use crate::networks::SessionNetworks;
+use crate::shutdown::snapshot_pending_work;
 use snapmail::SNAPMAIL_DEFAULT_COORDINATOR_ZOME_NAME;
 use holochain::conductor::ConductorHandle;
 use holochain_types::dna::*;
-use holochain_types::dna::wasm::DnaWasm;
 use holochain_types::app::*;
 use holochain_zome_types::*;
 use holochain_p2p::*;
@@ -22,48 +26,75 @@ use holochain::conductor::config::ConductorConfig;
 ///
 pub async fn start_conductor_or_abort(sid: String) -> (ConductorHandle, DnaHash) {
    /// Make sure config exists
-   let config_path = Path::new(&*CONFIG_PATH).join(sid.clone()).join(CONDUCTOR_CONFIG_FILENAME);
-   if let Err(_e) = ConductorConfig::load_yaml(config_path.as_ref()) {
+   let conductor_config_path = config_path().join(sid.clone()).join(CONDUCTOR_CONFIG_FILENAME);
+   if let Err(_e) = ConductorConfig::load_yaml(conductor_config_path.as_ref()) {
       err_msg!("Failed to load config for session \"{}\"", sid);
       err_msg!("Make sure it has been setup with snapmail-cli");
+      err_msg!("Run \"snapmail-cli {} doctor\" for a full diagnostic", sid);
+      std::process::abort();
+   }
+   /// Make sure no other process is using this session
+   if let Err(e) = acquire_session_lock(&sid) {
+      err_msg!("{}", e);
+      err_msg!("Close the other instance first");
       std::process::abort();
    }
    /// Load conductor from config file
-   let conductor = conductor_handle_from_config_path(Some(config_path)).await;
+   let conductor = conductor_handle_from_config_path(Some(conductor_config_path)).await;
    /// Make sure it has the correct DNA
    /// - Get UID
-   let path = CONFIG_PATH.as_path().join(sid.clone());
+   let path = config_path().join(sid.clone());
    let app_filepath = path.join(APP_CONFIG_FILENAME);
    let uid = std::fs::read_to_string(app_filepath).expect("Should have config folder");
    let expected_dna = load_dna_from_rs(uid).await;
    let expected_wasm = expected_dna.get_wasm_for_zome(&ZomeName::from("snapmail")).unwrap();
    let expected_wasm_hash = holo_hash::WasmHash::with_data(expected_wasm).await;
 
-   /// - Get Installed DNAs
+   /// - Get Installed DNAs (one per joined network). Archived networks keep their older wasm.
    let dnas = conductor.list_dnas();
+   let archived_dnas = match SessionNetworks::load(&sid) {
+      Ok(networks) => networks.archived_dnas(),
+      Err(e) => {
+         err_msg!("{}", e);
+         err_msg!("Run \"snapmail-cli {} doctor\" for a full diagnostic", sid);
+         std::process::abort();
+      },
+   };
    /// - Check
-   if dnas.len() != 1 {
-      err_msg!("No installed DNA found ({})", dnas.len());
-      err_msg!("Make sure it has been setup with snapmail-cli");
-      std::process::abort();
-   }
-   let dna = conductor.get_dna(&dnas[0]).unwrap();
-   let expected_hash = dna.dna_hash().clone();
-   let maybe_wasm = dna.get_wasm_for_zome(&ZomeName::from("snapmail"));
-   if maybe_wasm.is_err() {
-      err_msg!("Installed DNA Mismatch:");
-      err_msg!(" - \"snapmail\" zome not found");
+   if dnas.is_empty() {
+      err_msg!("No installed DNA found");
       err_msg!("Make sure it has been setup with snapmail-cli");
+      err_msg!("Run \"snapmail-cli {} doctor\" for a full diagnostic", sid);
       std::process::abort();
    }
-   let wasm_hash = holo_hash::WasmHash::with_data(maybe_wasm.unwrap()).await;
-   if wasm_hash != expected_wasm_hash {
-      err_msg!("Installed DNA Mismatch:");
-      err_msg!(" - Installed Wasm: {}\n  - Expected Wasm: {}", wasm_hash, expected_wasm_hash);
-      err_msg!("Make sure it has been setup with snapmail-cli");
-      std::process::abort();
+   for dna_hash in dnas.iter() {
+      if archived_dnas.contains(&dna_hash.to_string()) {
+         continue;
+      }
+      let maybe_wasm_hash = installed_wasm_hash(&conductor, dna_hash).await;
+      if maybe_wasm_hash.is_none() {
+         err_msg!("Installed DNA Mismatch ({}):", dna_hash);
+         err_msg!(" - \"snapmail\" zome not found");
+         err_msg!("Make sure it has been setup with snapmail-cli");
+         err_msg!("Run \"snapmail-cli {} doctor\" for a full diagnostic", sid);
+         std::process::abort();
+      }
+      let wasm_hash = maybe_wasm_hash.unwrap();
+      if wasm_hash != expected_wasm_hash {
+         err_msg!("Installed DNA Mismatch ({}):", dna_hash);
+         err_msg!(" - Installed Wasm: {}\n  - Expected Wasm: {}", wasm_hash, expected_wasm_hash);
+         err_msg!("Run \"snapmail-cli {} migrate\" to move its mails to the new DNA", sid);
+         std::process::abort();
+      }
    }
+   /// - DNA of the default network
+   let maybe_default_dna = SessionNetworks::load(&sid).ok()
+      .and_then(|networks| networks.networks[0].dna_hash.clone())
+      .and_then(|default_dna| dnas.iter().find(|dna_hash| dna_hash.to_string() == default_dna).cloned());
+   let expected_hash = maybe_default_dna.unwrap_or(dnas[0].clone());
    /// Done
+   snapshot_pending_work(conductor.clone(), &sid).await;
+   touch_last_used(&sid);
    return (conductor, expected_hash);
 }
 
@@ -72,9 +103,15 @@ pub async fn start_conductor_or_abort(sid: String) -> (ConductorHandle, DnaHash)
 ///
 pub async fn start_conductor(sid: String) -> ConductorHandle {
    msg!("** start_conductor: {:?}", sid);
+   /// Make sure no other process is using this session
+   if let Err(e) = acquire_session_lock(&sid) {
+      err_msg!("{}", e);
+      err_msg!("Close the other instance first");
+      std::process::abort();
+   }
    /// Load conductor from config file
-   let config_path = Path::new(&*CONFIG_PATH).join(sid.clone()).join(CONDUCTOR_CONFIG_FILENAME);
-   let conductor = conductor_handle_from_config_path(Some(config_path)).await;
+   let conductor_config_path = config_path().join(sid.clone()).join(CONDUCTOR_CONFIG_FILENAME);
+   let conductor = conductor_handle_from_config_path(Some(conductor_config_path)).await;
    /// Check state
    //let _ = conductor.print_setup();
    let dnas = conductor.list_dnas();
@@ -84,11 +121,20 @@ pub async fn start_conductor(sid: String) -> ConductorHandle {
    //let _interfaces = conductor.list_app_interfaces().await.unwrap();
    //msg!("App Interfaces: {:?}", interfaces);
    let cell_ids = conductor.list_cell_ids(None);
-   println!("Cell IDs: {:?}", cell_ids);
+   msg!("Cell IDs: {:?}", cell_ids);
    /// Done
+   snapshot_pending_work(conductor.clone(), &sid).await;
+   touch_last_used(&sid);
    return conductor;
 }
 
+/// Hash of the "snapmail" zome wasm of an installed DNA. `None` if the zome is missing.
+pub async fn installed_wasm_hash(conductor: &ConductorHandle, dna_hash: &DnaHash) -> Option<holo_hash::WasmHash> {
+   let dna = conductor.get_dna(dna_hash)?;
+   let wasm = dna.get_wasm_for_zome(&ZomeName::from("snapmail")).ok()?;
+   Some(holo_hash::WasmHash::with_data(wasm).await)
+}
+
 /// Create a DnaFile from a path to a *.dna bundle
 async fn load_dna_from_path(ns: NetworkSeed, path: &Path) -> holochain_types::dna::error::DnaResult<DnaFile> {
    let mut dna = DnaBundle::read_from_file(path)
@@ -101,58 +147,91 @@ async fn load_dna_from_path(ns: NetworkSeed, path: &Path) -> holochain_types::dn
 }
 
 
-// ///
-// async fn load_dna_from_rs(ns: NetworkSeed) -> DnaFile {
-//    let compressed = base64::decode_config(crate::wasm::DNA_WASM_B64, base64::URL_SAFE_NO_PAD).unwrap();
-//    let (decompressed, _checksum) = yazi::decompress(&compressed, yazi::Format::Zlib).unwrap();
-//
-//    let dna_wasm = DnaWasm::from(decompressed);
-//    let (_, wasm_hash) = holochain_types::dna::wasm::DnaWasmHashed::from_content(dna_wasm.clone())
-//    .await
-//    .into_inner();
-//    let zome_def: ZomeDef = ZomeDef::from_hash(wasm_hash.clone());
-//    let zome = (ZOME_NAME.into(), zome_def).into();
-//    //let name = SNAPMAIL_APP_ID.to_string();
-//    let name =format!("{}-{}", SNAPMAIL_APP_ID, ns);
-//    println!(" - name: {}", name);
-//    let dna_file = DnaFile::new(DnaDef {
-//    name,
-//    modifiers: {
-//       network_seed: ns,
-//       properties: SerializedBytes::try_from(()).unwrap(),
-//       origin_time: Timestamp::HOLOCHAIN_EPOCH,
-//       quantum_time: Duration::from_secs(5 * 60),
-//    }
-//    integrity_zomes: vec![zome].into(),
-//    coordinator_zomes: vec![zome].into(),
-//    },
-//    vec![dna_wasm].into_iter(),
-//    ).await.expect("Dna file load failed");
-//    dna_file
-// }
-
-/// Install Snapmail DNA from dna file
-/// FIXME: hardcoded DNA file path
-#[allow(deprecated)]
-pub async fn install_app(sid: String, ns: NetworkSeed, maybe_path: Option<PathBuf>) -> ConductorResult<DnaHash> {
+/// Hash of the embedded DNA on the network with this seed
+pub async fn embedded_dna_hash(ns: NetworkSeed) -> DnaHash {
+   load_dna_from_rs(ns).await.dna_hash().clone()
+}
+
+/// Create a DnaFile from the DNA bundle embedded in wasm.rs (see rustify-wasm)
+async fn load_dna_from_rs(ns: NetworkSeed) -> DnaFile {
+   let compressed = base64::decode_config(crate::wasm::DNA_BUNDLE_B64, base64::URL_SAFE_NO_PAD)
+      .expect("Embedded DNA should be valid base64");
+   let (bundle_bytes, _checksum) = yazi::decompress(&compressed, yazi::Format::Zlib)
+      .expect("Embedded DNA should be zlib compressed");
+   let dna = DnaBundle::decode(&bundle_bytes)
+      .expect("Embedded DNA should be a DNA bundle")
+      .into_dna_file(None, None)
+      .await
+      .expect("Embedded DNA should produce a DnaFile")
+      .0;
+   dna.with_network_seed(ns).await.expect("Setting network seed should not fail")
+}
+
+/// Install Snapmail DNA from dna file as the session's default network.
+/// Returns the conductor it started, so the session can be used without starting another one.
+/// Never exits the process: on failure the conductor is shut down and the session lock released.
+pub async fn install_app(sid: String, ns: NetworkSeed, maybe_path: Option<PathBuf>) -> ConductorResult<(ConductorHandle, DnaHash)> {
    /// Load conductor from config file
-   let config_path = Path::new(&*CONFIG_PATH).join(sid.clone());
-   let conductor_path = config_path.join(CONDUCTOR_CONFIG_FILENAME);
-   let app_filepath = config_path.join(APP_CONFIG_FILENAME);
+   let session_path = config_path().join(sid.clone());
+   let conductor_path = session_path.join(CONDUCTOR_CONFIG_FILENAME);
+   let app_filepath = session_path.join(APP_CONFIG_FILENAME);
    std::fs::write(app_filepath, ns.as_bytes())?;
-   let conductor = conductor_handle_from_config_path(Some(conductor_path)).await;
+   let config = ConductorConfig::load_yaml(conductor_path.as_ref())
+      .map_err(|e| ConductorError::Other(e.into()))?;
+   acquire_session_lock(&sid)
+      .map_err(|e| ConductorError::Other(e.into()))?;
+   let conductor = match try_conductor_from_config(config).await {
+      Ok(conductor) => conductor,
+      Err(e) => {
+         release_session_lock(&sid);
+         return Err(ConductorError::Other(e.into()));
+      },
+   };
+   match install_default_app(&sid, conductor.clone(), ns, maybe_path).await {
+      Ok(dna_hash) => Ok((conductor, dna_hash)),
+      Err(e) => {
+         conductor.shutdown();
+         release_session_lock(&sid);
+         Err(e)
+      },
+   }
+}
+
+/// Install the DNA for a new agent as the session's default network
+async fn install_default_app(
+   sid: &str,
+   conductor: ConductorHandle,
+   ns: NetworkSeed,
+   maybe_path: Option<PathBuf>,
+) -> ConductorResult<DnaHash> {
    /// Generate keys
    let agent_key = conductor
       .keystore()
       .new_sign_keypair_random()
       .await?;
+   /// Install
+   let dna_hash = install_dna_app(conductor, SNAPMAIL_APP_ID.to_string(), agent_key, ns, maybe_path).await?;
+   SessionNetworks::set_default_dna(sid, &dna_hash)
+      .map_err(|e| ConductorError::Other(e.into()))?;
+   Ok(dna_hash)
+}
 
+/// Install and enable the Snapmail DNA with the given network seed as a new app for an agent
+#[allow(deprecated)]
+pub async fn install_dna_app(
+   conductor: ConductorHandle,
+   app_id: String,
+   agent_key: AgentPubKey,
+   ns: NetworkSeed,
+   maybe_path: Option<PathBuf>,
+) -> ConductorResult<DnaHash> {
    /// Load DnaFile
    let dna_file = if let Some(path) = maybe_path {
-      println!("Loading DNA from path: {}", path.to_string_lossy());
-      load_dna_from_path(ns, &path).await.unwrap()
+      msg!("Loading DNA from path: {}", path.to_string_lossy());
+      load_dna_from_path(ns, &path).await
+         .map_err(|e| ConductorError::Other(e.into()))?
    } else {
-      println!("Building DNA from wasm stored in Rust code.");
+      msg!("Building DNA from wasm stored in Rust code.");
       load_dna_from_rs(ns).await
    };
 
@@ -164,12 +243,12 @@ pub async fn install_app(sid: String, ns: NetworkSeed, maybe_path: Option<PathBu
    /// Call genesis
    conductor
        .clone()
-       .install_app(SNAPMAIL_APP_ID.to_string(), vec![cell_id_with_proof])
+       .install_app(app_id.clone(), vec![cell_id_with_proof])
        .await?;
    /// Activate app
    conductor
       .clone()
-      .enable_app(SNAPMAIL_APP_ID.into())
+      .enable_app(app_id.into())
       .await?;
    /// Done
    let dnas = conductor.list_dnas();
@@ -177,14 +256,23 @@ pub async fn install_app(sid: String, ns: NetworkSeed, maybe_path: Option<PathBu
    Ok(dna_file.dna_hash().clone())
 }
 
-///
-pub fn dump_state(conductor: ConductorHandle) -> usize {
+/// Number of peers known on all networks of the conductor
+pub fn dump_state(conductor: ConductorHandle) -> anyhow::Result<usize> {
+   let _timer = METRICS.dump_state_duration.start_timer();
+   let mut peer_count = 0;
+   for cell_id in conductor.list_cell_ids(None).iter() {
+      peer_count += dump_cell_state(conductor.clone(), cell_id)?;
+   }
+   METRICS.peers.set(peer_count as i64);
+   Ok(peer_count)
+}
+
+/// Number of peers known on the network of a cell
+pub fn dump_cell_state(conductor: ConductorHandle, cell_id: &CellId) -> anyhow::Result<usize> {
    let result = holochain_util::tokio_helper::block_on(async {
       //let p2p = conductor.holochain_p2p();
       //let broadcaster = conductor.signal_broadcaster();
 
-      let cell_id = &conductor.list_cell_ids(None)[0];
-
       // let cell = conductor.cell_by_id(cell_id).unwrap();
       // let arc = cell.env();
       // let source_chain = SourceChainBuf::new(arc.clone().into()).unwrap();
@@ -197,7 +285,7 @@ pub fn dump_state(conductor: ConductorHandle) -> usize {
       let peer_dump = p2p_agent_store::dump_state(
          p2p_env.into(),
          Some(cell_id.clone()),
-      ).await.expect("p2p_agent_store::dump_state() should not fail");
+      ).await.map_err(|e| anyhow::anyhow!("Failed to read the peer store: {:?}", e))?;
 
       //let state = conductor.dump_cell_state(&cell_ids[0]).await.unwrap();
       //msg!(" {}", state);
@@ -205,7 +293,7 @@ pub fn dump_state(conductor: ConductorHandle) -> usize {
       // msg!("Conductor state dump:");
       // msg!(" - peer dump: {}", peer_dump);
       // msg!(" - Peers: {}", peer_dump.peers.len());
-      peer_dump.peers.len()
+      Ok::<usize, anyhow::Error>(peer_dump.peers.len())
    }, std::time::Duration::from_secs(9));
-   result.expect("dump_state() should not fail")
+   result.map_err(|_| anyhow::anyhow!("Timed out reading the peer store"))?
 }
\ No newline at end of file
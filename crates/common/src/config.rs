@@ -5,6 +5,8 @@ use holochain_conductor_api::config::conductor::{ConductorConfig, KeystoreConfig
 use holochain_conductor_api::config::*;
 use crate::globals::*;
 use kitsune_p2p_types::config::KitsuneP2pConfig;
+use holochain_p2p::kitsune_p2p::{TransportConfig, NetworkType, ProxyConfig};
+use url2::Url2;
 
 
 /// Create a new default [`ConductorConfig`] with environment path
@@ -108,3 +110,213 @@ pub fn generate_directory(
     std::fs::create_dir(keystore_dir)?;
     Ok(dir)
 }
+
+
+/// Transport of a conductor's network
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetworkTransport {
+    /// QUIC with peer discovery through MDNS
+    Mdns,
+    /// QUIC with peer discovery through a bootstrap service
+    Quic,
+}
+
+impl std::str::FromStr for NetworkTransport {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mdns" => Ok(NetworkTransport::Mdns),
+            "quic" => Ok(NetworkTransport::Quic),
+            _ => Err(format!("Unknown transport \"{}\" (expected \"mdns\" or \"quic\")", s)),
+        }
+    }
+}
+
+
+/// Changes to apply to the network part of a [`ConductorConfig`].
+/// `None` fields are left untouched.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkChange {
+    pub maybe_transport: Option<NetworkTransport>,
+    pub maybe_proxy: Option<Url2>,
+    /// Stop using a proxy
+    pub remove_proxy: bool,
+    pub maybe_bootstrap: Option<Url2>,
+    pub maybe_bind_to: Option<Url2>,
+    pub maybe_override_host: Option<String>,
+    pub maybe_override_port: Option<u16>,
+}
+
+impl NetworkChange {
+    /// True if nothing would change
+    pub fn is_empty(&self) -> bool {
+        self.maybe_transport.is_none()
+           && self.maybe_proxy.is_none()
+           && !self.remove_proxy
+           && self.maybe_bootstrap.is_none()
+           && self.maybe_bind_to.is_none()
+           && self.maybe_override_host.is_none()
+           && self.maybe_override_port.is_none()
+    }
+
+    /// Check URL schemes before touching any config
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(proxy) = &self.maybe_proxy {
+            if proxy.scheme() != "kitsune-proxy" {
+                anyhow::bail!("Invalid proxy URL \"{}\": expecting a \"kitsune-proxy://\" URL", proxy);
+            }
+            if self.remove_proxy {
+                anyhow::bail!("Cannot set and remove the proxy at the same time");
+            }
+        }
+        if let Some(bootstrap) = &self.maybe_bootstrap {
+            if bootstrap.scheme() != "https" && bootstrap.scheme() != "http" {
+                anyhow::bail!("Invalid bootstrap URL \"{}\": expecting an \"http(s)://\" URL", bootstrap);
+            }
+        }
+        if let Some(bind_to) = &self.maybe_bind_to {
+            if bind_to.scheme() != "kitsune-quic" {
+                anyhow::bail!("Invalid bind address \"{}\": expecting a \"kitsune-quic://\" URL", bind_to);
+            }
+        }
+        Ok(())
+    }
+}
+
+
+/// Current proxy and bootstrap URLs of a [`ConductorConfig`]
+pub fn network_urls(config: &ConductorConfig) -> (Option<Url2>, Option<Url2>) {
+    let network = match &config.network {
+        None => return (None, None),
+        Some(network) => network,
+    };
+    let maybe_proxy = network.transport_pool.iter().find_map(|transport| {
+        match transport {
+            TransportConfig::Proxy { proxy_config: ProxyConfig::RemoteProxyClient { proxy_url }, .. } => Some(proxy_url.clone()),
+            _ => None,
+        }
+    });
+    (maybe_proxy, network.bootstrap_service.clone())
+}
+
+
+/// Apply a [`NetworkChange`] to a [`ConductorConfig`].
+/// A missing network section is created from defaults.
+pub fn apply_network_change(config: &mut ConductorConfig, change: &NetworkChange) -> anyhow::Result<()> {
+    change.validate()?;
+    let network = config.network.get_or_insert_with(KitsuneP2pConfig::default);
+
+    /// Extract current QUIC transport settings, wrapped in a proxy or not
+    let (mut bind_to, mut override_host, mut override_port, mut maybe_proxy) =
+        match network.transport_pool.first() {
+            Some(TransportConfig::Quic { bind_to, override_host, override_port }) => {
+                (bind_to.clone(), override_host.clone(), *override_port, None)
+            },
+            Some(TransportConfig::Proxy { sub_transport, proxy_config }) => {
+                let proxy_url = match proxy_config {
+                    ProxyConfig::RemoteProxyClient { proxy_url } => Some(proxy_url.clone()),
+                    _ => None,
+                };
+                match sub_transport.as_ref() {
+                    TransportConfig::Quic { bind_to, override_host, override_port } => {
+                        (bind_to.clone(), override_host.clone(), *override_port, proxy_url)
+                    },
+                    _ => (None, None, None, proxy_url),
+                }
+            },
+            _ => (None, None, None, None),
+        };
+
+    /// Apply changes
+    if let Some(transport) = change.maybe_transport {
+        network.network_type = match transport {
+            NetworkTransport::Mdns => NetworkType::QuicMdns,
+            NetworkTransport::Quic => NetworkType::QuicBootstrap,
+        };
+    }
+    if change.maybe_bind_to.is_some() {
+        bind_to = change.maybe_bind_to.clone();
+    }
+    if change.maybe_override_host.is_some() {
+        override_host = change.maybe_override_host.clone();
+    }
+    if change.maybe_override_port.is_some() {
+        override_port = change.maybe_override_port;
+    }
+    if change.remove_proxy {
+        maybe_proxy = None;
+    }
+    if change.maybe_proxy.is_some() {
+        maybe_proxy = change.maybe_proxy.clone();
+    }
+    if change.maybe_bootstrap.is_some() {
+        network.bootstrap_service = change.maybe_bootstrap.clone();
+    }
+
+    /// Rebuild transport pool
+    let quic = TransportConfig::Quic {
+        bind_to,
+        override_host,
+        override_port,
+    };
+    network.transport_pool = match maybe_proxy {
+        None => vec![quic],
+        Some(proxy_url) => vec![TransportConfig::Proxy {
+            sub_transport: Box::new(quic),
+            proxy_config: ProxyConfig::RemoteProxyClient { proxy_url },
+        }],
+    };
+    Ok(())
+}
+
+
+/// Apply a [`NetworkChange`] to the conductor config of a session directory.
+/// Returns the diff of the config file. Nothing is written if `dry_run` is set.
+pub fn change_network(path: PathBuf, change: &NetworkChange, dry_run: bool) -> anyhow::Result<Vec<String>> {
+    let mut config = read_config(path.clone())?
+       .ok_or_else(|| anyhow::anyhow!("No conductor config found in {:?}", path))?;
+    let before = serde_yaml::to_string(&config)?;
+    apply_network_change(&mut config, change)?;
+    let after = serde_yaml::to_string(&config)?;
+    let diff = diff_lines(&before, &after);
+    if !dry_run && !diff.is_empty() {
+        write_config(path, &config);
+    }
+    Ok(diff)
+}
+
+
+/// Line diff of two texts. Only changed lines are returned, prefixed by "- " or "+ ".
+pub fn diff_lines(before: &str, after: &str) -> Vec<String> {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+    /// Longest common subsequence table
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+            };
+        }
+    }
+    /// Walk the table
+    let mut res = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            res.push(format!("- {}", a[i]));
+            i += 1;
+        } else {
+            res.push(format!("+ {}", b[j]));
+            j += 1;
+        }
+    }
+    res.extend(a[i..].iter().map(|line| format!("- {}", line)));
+    res.extend(b[j..].iter().map(|line| format!("+ {}", line)));
+    res
+}
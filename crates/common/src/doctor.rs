@@ -0,0 +1,275 @@
+//! Session diagnostics.
+//!
+//! Each check reports a [`Finding`] instead of aborting,
+//! so a broken session can be inspected part by part.
+
+use crate::{
+   globals::*,
+   holochain::*,
+   conductor::installed_wasm_hash,
+   networks::*,
+   api::SnapmailApi,
+   cell::SnapmailCell,
+   lock::*,
+   wasm::SNAPMAIL_WASM_HASH,
+};
+use holochain::conductor::ConductorHandle;
+use holochain::conductor::config::ConductorConfig;
+use holochain_types::dna::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FindingStatus {
+   Ok,
+   Warning,
+   Error,
+   /// Could not be checked because a previous check failed
+   Skipped,
+}
+
+/// Result of one diagnostic check
+#[derive(Debug, Clone)]
+pub struct Finding {
+   pub check: &'static str,
+   pub status: FindingStatus,
+   pub detail: String,
+   pub maybe_fix: Option<String>,
+}
+
+impl Finding {
+   fn ok(check: &'static str, detail: String) -> Self {
+      Self { check, status: FindingStatus::Ok, detail, maybe_fix: None }
+   }
+   fn warning(check: &'static str, detail: String, fix: String) -> Self {
+      Self { check, status: FindingStatus::Warning, detail, maybe_fix: Some(fix) }
+   }
+   fn error(check: &'static str, detail: String, fix: String) -> Self {
+      Self { check, status: FindingStatus::Error, detail, maybe_fix: Some(fix) }
+   }
+   fn skipped(check: &'static str) -> Self {
+      Self { check, status: FindingStatus::Skipped, detail: "previous check failed".to_string(), maybe_fix: None }
+   }
+}
+
+
+/// Check every part of a session, from its files up to network connectivity
+pub async fn diagnose(sid: &str) -> Vec<Finding> {
+   let mut findings = Vec::new();
+   let session_path = config_path().join(sid);
+   let setup_fix = format!("Run: snapmail-cli {} clear && snapmail-cli {} setup <uid>", sid, sid);
+
+   /// Session folder
+   if !session_path.is_dir() {
+      findings.push(Finding::error("session folder", format!("{:?} not found", session_path), setup_fix));
+      return findings;
+   }
+   findings.push(Finding::ok("session folder", format!("{:?}", session_path)));
+
+   /// Conductor config
+   let config_path = session_path.join(CONDUCTOR_CONFIG_FILENAME);
+   let config = match ConductorConfig::load_yaml(config_path.as_ref()) {
+      Ok(config) => {
+         findings.push(Finding::ok("conductor config", format!("{:?} parsed", config_path)));
+         Some(config)
+      },
+      Err(e) => {
+         findings.push(Finding::error(
+            "conductor config",
+            format!("{:?}: {}", config_path, e),
+            format!("Fix the YAML by hand or redo setup. {}", setup_fix),
+         ));
+         None
+      }
+   };
+
+   /// App config (network id)
+   let app_filepath = session_path.join(APP_CONFIG_FILENAME);
+   match std::fs::read_to_string(&app_filepath) {
+      Ok(uid) => findings.push(Finding::ok("app config", format!("network id: \"{}\"", uid))),
+      Err(e) => findings.push(Finding::error(
+         "app config",
+         format!("{:?}: {}", app_filepath, e),
+         format!("Run: snapmail-cli {} change --uid <uid>", sid),
+      )),
+   }
+
+   /// Session lock
+   let is_locked = match acquire_session_lock(sid) {
+      Ok(()) => {
+         findings.push(Finding::ok("session lock", "session not in use by another process".to_string()));
+         true
+      },
+      Err(e) => {
+         findings.push(Finding::error(
+            "session lock",
+            format!("{}", e),
+            "Close the other instance and run doctor again".to_string(),
+         ));
+         false
+      },
+   };
+   let config = if is_locked { config } else { None };
+
+   /// Keystore & conductor
+   let conductor = match config {
+      None => {
+         findings.push(Finding::skipped("keystore"));
+         None
+      },
+      Some(config) => match try_conductor_from_config(config).await {
+         Ok(conductor) => {
+            findings.push(Finding::ok("keystore", "unlocked and conductor started".to_string()));
+            Some(conductor)
+         },
+         Err(e) => {
+            findings.push(Finding::error(
+               "keystore",
+               format!("Conductor failed to start: {}", e),
+               "Check the keystore folder and passphrase of the session".to_string(),
+            ));
+            None
+         },
+      }
+   };
+   let conductor = match conductor {
+      Some(conductor) => conductor,
+      None => {
+         for check in ["installed DNA", "DNA wasm", "app enabled", "handle", "peers"] {
+            findings.push(Finding::skipped(check));
+         }
+         if is_locked {
+            release_session_lock(sid);
+         }
+         return findings;
+      }
+   };
+   findings.append(&mut diagnose_conductor(sid, conductor.clone(), &setup_fix).await);
+   conductor.shutdown();
+   release_session_lock(sid);
+   findings
+}
+
+
+/// Checks that need a running conductor
+async fn diagnose_conductor(sid: &str, conductor: ConductorHandle, setup_fix: &str) -> Vec<Finding> {
+   let mut findings = Vec::new();
+
+   /// Installed DNAs (one per joined network)
+   let dnas = conductor.list_dnas();
+   let networks = match SessionNetworks::load(sid) {
+      Ok(networks) => networks,
+      Err(e) => {
+         findings.push(Finding::error(
+            "networks",
+            format!("{}", e),
+            format!("Fix or remove {:?}", SessionNetworks::filepath(sid)),
+         ));
+         SessionNetworks::default()
+      },
+   };
+   let archived_dnas = networks.archived_dnas();
+   let migrate_fix = format!("Run: snapmail-cli {} migrate", sid);
+   if dnas.is_empty() {
+      findings.push(Finding::error(
+         "installed DNA",
+         "No installed DNA found".to_string(),
+         setup_fix.to_string(),
+      ));
+      findings.push(Finding::skipped("DNA wasm"));
+   }
+   for dna_hash in dnas.iter() {
+      findings.push(Finding::ok("installed DNA", format!("{}", dna_hash)));
+      /// Wasm hash
+      match installed_wasm_hash(&conductor, dna_hash).await {
+         None => findings.push(Finding::error(
+            "DNA wasm",
+            "\"snapmail\" zome not found in installed DNA".to_string(),
+            setup_fix.to_string(),
+         )),
+         Some(wasm_hash) => {
+            if wasm_hash.to_string() == SNAPMAIL_WASM_HASH {
+               findings.push(Finding::ok("DNA wasm", format!("{}", wasm_hash)));
+            } else if archived_dnas.contains(&dna_hash.to_string()) {
+               findings.push(Finding::ok("DNA wasm", format!("{} (archived network)", wasm_hash)));
+            } else {
+               findings.push(Finding::error(
+                  "DNA wasm",
+                  format!("Installed wasm {} does not match expected wasm {}", wasm_hash, SNAPMAIL_WASM_HASH),
+                  migrate_fix.clone(),
+               ));
+            }
+         }
+      }
+   }
+
+   /// App enabled
+   let default_app_id = networks.networks.first()
+      .map(|entry| entry.app_id.clone())
+      .unwrap_or(SNAPMAIL_APP_ID.to_string());
+   match conductor.list_running_apps().await {
+      Ok(apps) if apps.iter().any(|app_id| app_id == &default_app_id) => {
+         findings.push(Finding::ok("app enabled", default_app_id));
+      },
+      Ok(apps) => findings.push(Finding::error(
+         "app enabled",
+         format!("\"{}\" is not running. Running apps: {:?}", default_app_id, apps),
+         setup_fix.to_string(),
+      )),
+      Err(e) => findings.push(Finding::error("app enabled", format!("{}", e), setup_fix.to_string())),
+   }
+
+   /// Networks
+   let cells = match all_network_cells(conductor.clone(), sid) {
+      Ok(cells) => cells,
+      Err(e) => {
+         findings.push(Finding::error("networks", format!("{}", e), setup_fix.to_string()));
+         findings.push(Finding::skipped("handle"));
+         findings.push(Finding::skipped("peers"));
+         return findings;
+      },
+   };
+   for cell in cells.iter() {
+      findings.append(&mut diagnose_cell(sid, cell, setup_fix));
+   }
+   findings
+}
+
+
+/// Checks of one network's cell
+fn diagnose_cell(sid: &str, cell: &SnapmailCell, setup_fix: &str) -> Vec<Finding> {
+   let mut findings = Vec::new();
+   let network_flag = if cell.alias == DEFAULT_NETWORK_ALIAS { String::new() } else { format!(" --network {}", cell.alias) };
+
+   /// Handle
+   match cell.get_my_handle() {
+      Ok(handle) if !handle.is_empty() => findings.push(Finding::ok("handle", format!("[{}] \"{}\"", cell.alias, handle))),
+      Ok(_) => findings.push(Finding::warning(
+         "handle",
+         format!("[{}] No handle set", cell.alias),
+         format!("Run: snapmail-cli {}{} set-handle <name>", sid, network_flag),
+      )),
+      Err(e) => findings.push(Finding::error(
+         "handle",
+         format!("[{}] Zome call failed: {:?}", cell.alias, e),
+         setup_fix.to_string(),
+      )),
+   }
+
+   /// Peers
+   match cell.peer_count() {
+      Ok(peer_count) if peer_count > 1 => {
+         findings.push(Finding::ok("peers", format!("[{}] {} peers known", cell.alias, peer_count)));
+      },
+      Ok(peer_count) => findings.push(Finding::warning(
+         "peers",
+         format!("[{}] {} peer known: node looks isolated", cell.alias, peer_count),
+         format!("Check the bootstrap and proxy URLs with: snapmail-cli {} info", sid),
+      )),
+      Err(e) => findings.push(Finding::error(
+         "peers",
+         format!("[{}] {}", cell.alias, e),
+         setup_fix.to_string(),
+      )),
+   }
+   findings
+}
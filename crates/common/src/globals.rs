@@ -1,6 +1,7 @@
 use url2::Url2;
 use directories::ProjectDirs;
 use std::path::PathBuf;
+use std::sync::RwLock;
 
 pub const SNAPMAIL_VERSION: &'static str   = env!("CARGO_PKG_VERSION");
 pub const SNAPMAIL_APP_ID: &str            = "snapmail-app";
@@ -8,12 +9,45 @@ pub const SNAPMAIL_APP_NAME: &str          = "snapmail-cli";
 pub const LAIR_MAGIC_READY_STRING: &str    = "#lair-keystore-ready#";
 pub const CONDUCTOR_CONFIG_FILENAME: &str  = "conductor-config.yaml";
 pub const APP_CONFIG_FILENAME: &str        = "app-config.txt";
-pub const APP_DL_CONFIG_FILENAME: &str     = "app-dl-config.txt"; // FIXME
+pub const APP_DL_CONFIG_FILENAME: &str     = "app-dl-config.txt"; // Legacy, imported in settings
+pub const SETTINGS_FILENAME: &str          = "settings.yaml";
+pub const LOG_FOLDER_NAME: &str            = "logs";
+pub const LAST_USED_FILENAME: &str         = "last-used.txt";
+pub const DEFAULT_SESSION_FILENAME: &str   = "default-session.txt";
+pub const SESSION_ENV_VAR: &str            = "SNAPMAIL_SESSION";
+pub const HOME_ENV_VAR: &str               = "SNAPMAIL_HOME";
+pub const LOCK_FILENAME: &str              = "session.lock";
+pub const NETWORKS_FILENAME: &str          = "networks.yaml";
+pub const DEFAULT_NETWORK_ALIAS: &str      = "default";
+pub const ARCHIVES_FOLDER_NAME: &str       = "archives";
+pub const PRESENCE_FILENAME: &str          = "presence.yaml";
+pub const TRASH_FILENAME: &str             = "trash.yaml";
+pub const LABELS_FILENAME: &str            = "labels.yaml";
+pub const READ_FLAGS_FILENAME: &str        = "read.yaml";
+pub const BLOCKLIST_FILENAME: &str         = "blocklist.yaml";
 
 lazy_static! {
    pub static ref DEFAULT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(9);
-   pub static ref CONFIG_PATH: PathBuf = ProjectDirs::from("", "", SNAPMAIL_APP_NAME).unwrap().config_dir().to_owned();
-   //pub static ref CONDUCTOR_CONFIG_FILEPATH: PathBuf = std::path::Path::new(&*CONFIG_PATH).join(CONDUCTOR_CONFIG_FILENAME);
+   pub static ref DEFAULT_CONFIG_PATH: PathBuf = ProjectDirs::from("", "", SNAPMAIL_APP_NAME).unwrap().config_dir().to_owned();
+   static ref CONFIG_ROOT_OVERRIDE: RwLock<Option<PathBuf>> = RwLock::new(None);
    pub static ref DEFAULT_PROXY_URL: Url2 =  url2!("kitsune-proxy://VYgwCrh2ZCKL1lpnMM1VVUee7ks-9BkmW47C_ys4nqg/kitsune-quic/h/kitsune-proxy.harris-braun.com/p/4010/--");
    pub static ref DEFAULT_BOOTSTRAP_URL: Url2 =  url2!("https://bootstrap-staging.holo.host");
 }
+
+
+/// Root folder of all sessions.
+/// In order of precedence: [`set_config_root`], `SNAPMAIL_HOME`, the system's config dir.
+pub fn config_path() -> PathBuf {
+   if let Some(root) = CONFIG_ROOT_OVERRIDE.read().unwrap().as_ref() {
+      return root.clone();
+   }
+   match std::env::var(HOME_ENV_VAR) {
+      Ok(root) if !root.is_empty() => PathBuf::from(root),
+      _ => DEFAULT_CONFIG_PATH.clone(),
+   }
+}
+
+/// Override the root folder of all sessions (ex: from a `--root` flag)
+pub fn set_config_root(root: PathBuf) {
+   *CONFIG_ROOT_OVERRIDE.write().unwrap() = Some(root);
+}
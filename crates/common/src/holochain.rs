@@ -1,7 +1,7 @@
 use holochain::conductor::{Conductor, ConductorHandle};
 use std::path::PathBuf;
 use holochain::conductor::paths::ConfigFilePath;
-use holochain::conductor::config::ConductorConfig;
+use holochain::conductor::config::{ConductorConfig, KeystoreConfig};
 use holochain_conductor_api::conductor::ConductorConfigError;
 
 // -- Copied from holochain/main.rs -- //
@@ -156,3 +156,25 @@ file. Details:
       config_path, error
    )
 }
+
+
+/// Build a conductor from an already loaded config.
+/// Unlike `conductor_handle_from_config_path()` this never exits the process.
+pub async fn try_conductor_from_config(config: ConductorConfig) -> anyhow::Result<ConductorHandle> {
+   let passphrase = match &config.keystore {
+      KeystoreConfig::DangerTestKeystore => None,
+      KeystoreConfig::LairServer { .. } | KeystoreConfig::LairServerInProc { .. } => {
+         Some(holochain_util::pw::pw_get()?)
+      }
+   };
+   let env_path = PathBuf::from(config.environment_path.clone());
+   if !env_path.is_dir() {
+      anyhow::bail!("Database directory {} not found", env_path.display());
+   }
+   let conductor = Conductor::builder()
+      .config(config)
+      .passphrase(passphrase)
+      .build()
+      .await?;
+   Ok(conductor)
+}
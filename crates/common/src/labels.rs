@@ -0,0 +1,223 @@
+//! Local labels on mails, and saved folders defined as mail queries.
+//!
+//! Labels are per session and never leave the machine: they are stored in the session's
+//! labels file, keyed by mail action hash, next to the saved folders.
+
+use crate::{
+   globals::*,
+   session::session_path,
+};
+use chrono::{Local, NaiveDate, TimeZone};
+use holochain_types::dna::*;
+use serde::{Serialize, Deserialize};
+use snapmail::mail::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+
+/// Coarse mail state a query can filter on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StateFilter {
+   Incoming,
+   Outgoing,
+   Unacknowledged,
+   Acknowledged,
+}
+
+impl StateFilter {
+   ///
+   pub fn matches(&self, state: &MailState) -> bool {
+      match self {
+         Self::Incoming => matches!(state, MailState::In(_)),
+         Self::Outgoing => matches!(state, MailState::Out(_)),
+         Self::Unacknowledged => matches!(state, MailState::In(InMailState::Unacknowledged)),
+         Self::Acknowledged => matches!(state,
+            MailState::In(InMailState::AckUnsent)
+            | MailState::In(InMailState::AckPending)
+            | MailState::In(InMailState::AckDelivered)
+            | MailState::Out(OutMailState::AllAcknowledged)),
+      }
+   }
+}
+
+impl FromStr for StateFilter {
+   type Err = anyhow::Error;
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      match s.to_lowercase().as_str() {
+         "incoming" => Ok(Self::Incoming),
+         "outgoing" => Ok(Self::Outgoing),
+         "unacknowledged" => Ok(Self::Unacknowledged),
+         "acknowledged" => Ok(Self::Acknowledged),
+         _ => anyhow::bail!("Unknown state \"{}\". Expected incoming, outgoing, unacknowledged or acknowledged", s),
+      }
+   }
+}
+
+
+/// Criteria a mail must all meet. Unset criteria match any mail.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct MailQuery {
+   /// Handle of the author
+   pub from: Option<String>,
+   pub label: Option<String>,
+   pub state: Option<StateFilter>,
+   /// First day included
+   pub since: Option<NaiveDate>,
+   /// Last day included
+   pub until: Option<NaiveDate>,
+}
+
+impl MailQuery {
+   /// `sender` is the author's handle, if known
+   pub fn matches(&self, item: &MailItem, sender: Option<&str>, labels: Option<&BTreeSet<String>>) -> bool {
+      if let Some(from) = &self.from {
+         if sender != Some(from.as_str()) {
+            return false;
+         }
+      }
+      if let Some(label) = &self.label {
+         if !labels.map(|labels| labels.contains(label)).unwrap_or(false) {
+            return false;
+         }
+      }
+      if let Some(state) = &self.state {
+         if !state.matches(&item.state) {
+            return false;
+         }
+      }
+      if self.since.is_some() || self.until.is_some() {
+         let Some(date) = Local.timestamp_opt(item.date, 0).single().map(|date| date.naive_local().date()) else {
+            return false;
+         };
+         if self.since.map(|since| date < since).unwrap_or(false)
+            || self.until.map(|until| date > until).unwrap_or(false) {
+            return false;
+         }
+      }
+      true
+   }
+}
+
+impl std::fmt::Display for MailQuery {
+   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      let mut criteria = Vec::new();
+      if let Some(from) = &self.from { criteria.push(format!("from:{}", from)); }
+      if let Some(label) = &self.label { criteria.push(format!("label:{}", label)); }
+      if let Some(state) = &self.state { criteria.push(format!("state:{:?}", state).to_lowercase()); }
+      if let Some(since) = &self.since { criteria.push(format!("since:{}", since)); }
+      if let Some(until) = &self.until { criteria.push(format!("until:{}", until)); }
+      if criteria.is_empty() {
+         return write!(f, "<all mails>");
+      }
+      write!(f, "{}", criteria.join(" "))
+   }
+}
+
+
+/// A named query shown as a folder
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SavedFolder {
+   pub name: String,
+   pub query: MailQuery,
+}
+
+
+/// Labels and saved folders of a session
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct MailLabels {
+   /// Mail action hash -> labels
+   pub mails: BTreeMap<String, BTreeSet<String>>,
+   pub folders: Vec<SavedFolder>,
+}
+
+impl MailLabels {
+   ///
+   pub fn filepath(sid: &str) -> PathBuf {
+      session_path(sid).join(LABELS_FILENAME)
+   }
+
+   /// Labels of a session. Empty if there is no labels file.
+   pub fn load(sid: &str) -> anyhow::Result<Self> {
+      let filepath = Self::filepath(sid);
+      match std::fs::read_to_string(&filepath) {
+         Ok(yaml) => Ok(serde_yaml::from_str::<MailLabels>(&yaml)?),
+         Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(MailLabels::default()),
+         Err(e) => anyhow::bail!("Failed to read labels file {:?}: {}", filepath, e),
+      }
+   }
+
+   ///
+   pub fn save(&self, sid: &str) -> anyhow::Result<()> {
+      let yaml = serde_yaml::to_string(self)?;
+      std::fs::write(Self::filepath(sid), yaml)?;
+      Ok(())
+   }
+
+   ///
+   pub fn labels_of(&self, ah: &ActionHash) -> Option<&BTreeSet<String>> {
+      self.mails.get(&ah.to_string())
+   }
+
+   /// Returns false if the mail already had this label
+   pub fn add(&mut self, ah: &ActionHash, label: &str) -> anyhow::Result<bool> {
+      let label = label.trim();
+      if label.is_empty() {
+         anyhow::bail!("Label can not be empty");
+      }
+      Ok(self.mails.entry(ah.to_string()).or_default().insert(label.to_string()))
+   }
+
+   /// Returns false if the mail did not have this label
+   pub fn remove(&mut self, ah: &ActionHash, label: &str) -> bool {
+      let key = ah.to_string();
+      let Some(labels) = self.mails.get_mut(&key) else { return false };
+      let removed = labels.remove(label.trim());
+      if labels.is_empty() {
+         self.mails.remove(&key);
+      }
+      removed
+   }
+
+   /// Each label in use, with its number of mails
+   pub fn label_counts(&self) -> BTreeMap<String, usize> {
+      let mut counts = BTreeMap::new();
+      for label in self.mails.values().flatten() {
+         *counts.entry(label.clone()).or_insert(0) += 1;
+      }
+      counts
+   }
+
+   ///
+   pub fn folder(&self, name: &str) -> anyhow::Result<&SavedFolder> {
+      self.folders.iter()
+         .find(|folder| folder.name == name)
+         .ok_or_else(|| anyhow::anyhow!("Unknown folder \"{}\"", name))
+   }
+
+   /// Add a folder, or replace the query of the folder with the same name
+   pub fn save_folder(&mut self, name: &str, query: MailQuery) -> anyhow::Result<()> {
+      let name = name.trim();
+      if name.is_empty() {
+         anyhow::bail!("Folder name can not be empty");
+      }
+      if ["inbox", "sent", "trash", "junk", "spam", "all"].contains(&name.to_lowercase().as_str()) {
+         anyhow::bail!("\"{}\" is a built-in folder", name);
+      }
+      match self.folders.iter_mut().find(|folder| folder.name == name) {
+         Some(folder) => folder.query = query,
+         None => self.folders.push(SavedFolder { name: name.to_string(), query }),
+      }
+      Ok(())
+   }
+
+   /// Returns false if there is no folder with this name
+   pub fn remove_folder(&mut self, name: &str) -> bool {
+      let len = self.folders.len();
+      self.folders.retain(|folder| folder.name != name);
+      self.folders.len() != len
+   }
+}
@@ -9,13 +9,42 @@ extern crate lazy_static;
 #[macro_use]
 extern crate url2;
 
+pub use tracing;
+
 #[macro_use]
 pub mod utils;
 #[macro_use]
 pub mod attachment;
+pub mod auto_download;
+pub mod api;
+pub mod blocklist;
+pub mod cell;
+pub mod client;
 pub mod conductor;
 pub mod config;
+pub mod doctor;
 pub mod error;
 pub mod globals;
 pub mod holochain;
-pub mod wasm;
+pub mod labels;
+pub mod lock;
+pub mod logging;
+pub mod metrics;
+pub mod migrate;
+#[cfg(feature = "test-support")]
+pub mod mock;
+pub mod networks;
+pub mod peers;
+pub mod presence;
+pub mod read_flags;
+pub mod rules;
+pub mod session;
+pub mod settings;
+pub mod shutdown;
+pub mod trash;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+/// Embedded DNA, generated by the build script
+pub mod wasm {
+   include!(concat!(env!("OUT_DIR"), "/wasm.rs"));
+}
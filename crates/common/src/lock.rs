@@ -0,0 +1,122 @@
+//! Advisory lock on a session's folder.
+//!
+//! Only one process at a time may run a conductor on a session's databases and keystore.
+//! The lock file records who holds it. The OS releases the lock when its process dies,
+//! so a lock file left by a crashed process is detected as stale and taken over.
+
+use crate::globals::*;
+use chrono::{DateTime, Local};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+
+/// Content of a session's lock file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LockInfo {
+   pub pid: u32,
+   /// Command line of the process holding the lock (ex: "snapmail-tui alex")
+   pub mode: String,
+   pub since: DateTime<Local>,
+}
+
+impl std::fmt::Display for LockInfo {
+   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      write!(f, "\"{}\" (PID {}) since {}", self.mode, self.pid, self.since.format("%Y-%m-%d %H:%M:%S"))
+   }
+}
+
+
+/// A held lock. Released when dropped.
+/// The lock file is emptied instead of removed so another process never locks a deleted file.
+#[derive(Debug)]
+struct SessionLock {
+   file: File,
+}
+
+impl Drop for SessionLock {
+   fn drop(&mut self) {
+      let _ = self.file.set_len(0);
+      let _ = self.file.unlock();
+   }
+}
+
+
+lazy_static! {
+   /// Locks held by this process, by session ID
+   static ref HELD_LOCKS: Mutex<HashMap<String, SessionLock>> = Mutex::new(HashMap::new());
+}
+
+
+/// Path of a session's lock file
+pub fn lock_filepath(sid: &str) -> PathBuf {
+   config_path().join(sid).join(LOCK_FILENAME)
+}
+
+/// Lock a session for this process. Does nothing if this process already holds the lock.
+/// Fails if another running process holds it.
+pub fn acquire_session_lock(sid: &str) -> anyhow::Result<()> {
+   let mut held = HELD_LOCKS.lock().unwrap();
+   if held.contains_key(sid) {
+      return Ok(());
+   }
+   let mut file = OpenOptions::new()
+      .read(true)
+      .write(true)
+      .create(true)
+      .open(lock_filepath(sid))?;
+   if file.try_lock_exclusive().is_err() {
+      return match read_lock_info(&mut file) {
+         Some(info) => Err(anyhow::anyhow!("Session \"{}\" is already in use by {}", sid, info)),
+         None => Err(anyhow::anyhow!("Session \"{}\" is already in use by another process", sid)),
+      };
+   }
+   /// Lock acquired: any previous content was left by a process that did not exit cleanly
+   if let Some(stale) = read_lock_info(&mut file) {
+      if stale.pid != std::process::id() {
+         msg!("Removing stale lock of session \"{}\" left by {}", sid, stale);
+      }
+   }
+   let info = LockInfo {
+      pid: std::process::id(),
+      mode: std::env::args().collect::<Vec<String>>().join(" "),
+      since: Local::now(),
+   };
+   file.set_len(0)?;
+   file.seek(SeekFrom::Start(0))?;
+   file.write_all(serde_json::to_string(&info)?.as_bytes())?;
+   file.sync_all()?;
+   held.insert(sid.to_string(), SessionLock { file });
+   Ok(())
+}
+
+/// Release a session's lock held by this process, if any
+pub fn release_session_lock(sid: &str) {
+   HELD_LOCKS.lock().unwrap().remove(sid);
+}
+
+/// Info about the process currently holding a session's lock, if any.
+/// Returns `None` when the session is free or its lock is stale.
+pub fn session_lock_holder(sid: &str) -> Option<LockInfo> {
+   if HELD_LOCKS.lock().unwrap().contains_key(sid) {
+      return None;
+   }
+   let mut file = OpenOptions::new().read(true).write(true).open(lock_filepath(sid)).ok()?;
+   if file.try_lock_exclusive().is_ok() {
+      let _ = file.unlock();
+      return None;
+   }
+   read_lock_info(&mut file)
+}
+
+///
+fn read_lock_info(file: &mut File) -> Option<LockInfo> {
+   let mut content = String::new();
+   file.seek(SeekFrom::Start(0)).ok()?;
+   file.read_to_string(&mut content).ok()?;
+   serde_json::from_str(&content).ok()
+}
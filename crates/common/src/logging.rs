@@ -0,0 +1,216 @@
+//! Tracing based logging shared by the CLI and the TUI.
+//!
+//! The `msg!`, `dbg!` and `err_msg!` macros emit `tracing` events
+//! which are routed to stdout, a rotating log file and/or an in-memory [`LogBuffer`].
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{
+   fmt::MakeWriter,
+   layer::SubscriberExt,
+   util::SubscriberInitExt,
+   EnvFilter, Layer,
+};
+
+/// Max number of lines kept in a LogBuffer
+pub const LOG_BUFFER_MAX_LINES: usize = 1000;
+pub const LOG_FILE_PREFIX: &str = "snapmail.log";
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+   Pretty,
+   Json,
+}
+
+impl FromStr for LogFormat {
+   type Err = String;
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      match s.to_lowercase().as_str() {
+         "pretty" => Ok(LogFormat::Pretty),
+         "json" => Ok(LogFormat::Json),
+         _ => Err(format!("Unknown log format \"{}\" (expected \"pretty\" or \"json\")", s)),
+      }
+   }
+}
+
+
+/// How logs should be filtered and where they should go
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+   /// 0 = info, negative is quieter, positive is more verbose
+   pub verbosity: i8,
+   pub format: LogFormat,
+   /// Write daily rotated log files in this directory
+   pub maybe_log_dir: Option<PathBuf>,
+   /// Capture logs emitted by the zome WASM
+   pub wasm_log: bool,
+   /// Print logs to stdout
+   pub stdout: bool,
+}
+
+impl Default for LogConfig {
+   fn default() -> Self {
+      Self {
+         verbosity: 0,
+         format: LogFormat::Pretty,
+         maybe_log_dir: None,
+         wasm_log: false,
+         stdout: true,
+      }
+   }
+}
+
+impl LogConfig {
+   /// Filter directives for this verbosity. `RUST_LOG` takes precedence when set.
+   fn filter(&self) -> EnvFilter {
+      if let Ok(filter) = EnvFilter::try_from_default_env() {
+         return filter;
+      }
+      let (snapmail_level, other_level) = match self.verbosity {
+         i8::MIN..=-1 => ("error", "error"),
+         0 => ("info", "warn"),
+         1 => ("debug", "warn"),
+         2 => ("trace", "info"),
+         _ => ("trace", "debug"),
+      };
+      let mut directives = format!("{},snapmail={}", other_level, snapmail_level);
+      if self.wasm_log {
+         directives += ",wasm_trace=trace";
+      }
+      EnvFilter::new(directives)
+   }
+}
+
+
+/// In-memory ring buffer of formatted log lines, used by the TUI logs panel
+#[derive(Clone, Default)]
+pub struct LogBuffer {
+   lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl LogBuffer {
+   ///
+   pub fn new() -> Self {
+      Self::default()
+   }
+
+   ///
+   pub fn push(&self, line: String) {
+      let mut lines = self.lines.lock().unwrap();
+      if lines.len() >= LOG_BUFFER_MAX_LINES {
+         lines.pop_front();
+      }
+      lines.push_back(line);
+   }
+
+   /// Get a copy of the last `count` lines
+   pub fn last_lines(&self, count: usize) -> Vec<String> {
+      let lines = self.lines.lock().unwrap();
+      let skip = lines.len().saturating_sub(count);
+      lines.iter().skip(skip).cloned().collect()
+   }
+
+   ///
+   pub fn len(&self) -> usize {
+      self.lines.lock().unwrap().len()
+   }
+}
+
+/// io::Write adapter that stores each written line in a LogBuffer
+pub struct LogBufferWriter {
+   buffer: LogBuffer,
+}
+
+impl Write for LogBufferWriter {
+   fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      let text = String::from_utf8_lossy(buf);
+      for line in text.lines() {
+         if !line.is_empty() {
+            self.buffer.push(line.to_string());
+         }
+      }
+      Ok(buf.len())
+   }
+
+   fn flush(&mut self) -> std::io::Result<()> {
+      Ok(())
+   }
+}
+
+impl<'a> MakeWriter<'a> for LogBuffer {
+   type Writer = LogBufferWriter;
+   fn make_writer(&'a self) -> Self::Writer {
+      LogBufferWriter { buffer: self.clone() }
+   }
+}
+
+
+/// Set `WASM_LOG`, which holochain reads to filter zome logs, unless already set.
+/// Setting the environment is not thread safe: call it before starting the tokio runtime.
+pub fn set_wasm_log_env(wasm_log: bool) {
+   if std::env::var_os("WASM_LOG").is_none() {
+      std::env::set_var("WASM_LOG", if wasm_log { "debug" } else { "error" });
+   }
+}
+
+/// Setup the global tracing subscriber.
+/// The returned guard must be kept alive for the log file to be flushed.
+pub fn init_logging(config: &LogConfig, maybe_buffer: Option<LogBuffer>) -> anyhow::Result<Option<WorkerGuard>> {
+   let mut layers = Vec::new();
+
+   /// Stdout
+   if config.stdout {
+      let layer = match config.format {
+         LogFormat::Pretty => tracing_subscriber::fmt::layer()
+            .with_target(false)
+            .with_timer(tracing_subscriber::fmt::time::ChronoLocal::new("%H:%M:%S".to_string()))
+            .boxed(),
+         LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .boxed(),
+      };
+      layers.push(layer);
+   }
+
+   /// Rotating log file
+   let mut maybe_guard = None;
+   if let Some(log_dir) = &config.maybe_log_dir {
+      std::fs::create_dir_all(log_dir)?;
+      let file_appender = tracing_appender::rolling::daily(log_dir, LOG_FILE_PREFIX);
+      let (writer, guard) = tracing_appender::non_blocking(file_appender);
+      let layer = match config.format {
+         LogFormat::Pretty => tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(writer)
+            .boxed(),
+         LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_writer(writer)
+            .boxed(),
+      };
+      layers.push(layer);
+      maybe_guard = Some(guard);
+   }
+
+   /// In-memory buffer
+   if let Some(buffer) = maybe_buffer {
+      let layer = tracing_subscriber::fmt::layer()
+         .with_ansi(false)
+         .with_target(false)
+         .with_timer(tracing_subscriber::fmt::time::ChronoLocal::new("%H:%M:%S".to_string()))
+         .with_writer(buffer)
+         .boxed();
+      layers.push(layer);
+   }
+
+   tracing_subscriber::registry()
+      .with(config.filter())
+      .with(layers)
+      .try_init()?;
+   Ok(maybe_guard)
+}
@@ -0,0 +1,189 @@
+//! Prometheus metrics for "always on" nodes.
+//!
+//! Metrics are stored in the global [`METRICS`] registry and can be exposed
+//! over HTTP with [`serve_metrics`].
+
+use prometheus::{
+   Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec,
+   IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use snapmail::mail::entries::*;
+use std::net::SocketAddr;
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+lazy_static! {
+   pub static ref METRICS: SnapmailMetrics = SnapmailMetrics::new();
+}
+
+/// All the metrics a snapmail node can expose
+pub struct SnapmailMetrics {
+   pub registry: Registry,
+   /// Number of peers in the agent store
+   pub peers: IntGauge,
+   /// Number of mails per direction and state
+   pub mails: IntGaugeVec,
+   /// Number of inmails not acknowledged yet
+   pub inbox_unacknowledged: IntGauge,
+   /// Number of outmails not received by all recipients yet
+   pub outbox_pending: IntGauge,
+   /// Number of signals received per kind
+   pub signals: IntCounterVec,
+   /// Zome call latency per zome function
+   pub zome_call_duration: HistogramVec,
+   /// Failed zome calls per zome function
+   pub zome_call_failures: IntCounterVec,
+   /// Attachment bytes sent
+   pub attachment_bytes_uploaded: IntCounter,
+   /// Attachment bytes received
+   pub attachment_bytes_downloaded: IntCounter,
+   /// Duration of a full state dump
+   pub dump_state_duration: Histogram,
+}
+
+impl SnapmailMetrics {
+   ///
+   pub fn new() -> Self {
+      let registry = Registry::new_custom(Some("snapmail".to_string()), None)
+         .expect("Metrics registry prefix should be valid");
+
+      let peers = IntGauge::new("peers", "Number of peers known by the agent store").unwrap();
+      let mails = IntGaugeVec::new(
+         Opts::new("mails", "Number of mails by direction and state"),
+         &["direction", "state"],
+      ).unwrap();
+      let inbox_unacknowledged = IntGauge::new("inbox_unacknowledged", "Number of received mails not acknowledged").unwrap();
+      let outbox_pending = IntGauge::new("outbox_pending", "Number of sent mails not yet received by all recipients").unwrap();
+      let signals = IntCounterVec::new(
+         Opts::new("signals_received_total", "Number of signals received by kind"),
+         &["kind"],
+      ).unwrap();
+      let zome_call_duration = HistogramVec::new(
+         HistogramOpts::new("zome_call_duration_seconds", "Zome call latency"),
+         &["fn_name"],
+      ).unwrap();
+      let zome_call_failures = IntCounterVec::new(
+         Opts::new("zome_call_failures_total", "Number of failed zome calls"),
+         &["fn_name"],
+      ).unwrap();
+      let attachment_bytes_uploaded = IntCounter::new("attachment_bytes_uploaded_total", "Attachment bytes written to the DHT").unwrap();
+      let attachment_bytes_downloaded = IntCounter::new("attachment_bytes_downloaded_total", "Attachment bytes fetched from the DHT").unwrap();
+      let dump_state_duration = Histogram::with_opts(
+         HistogramOpts::new("dump_state_duration_seconds", "Duration of a conductor state dump"),
+      ).unwrap();
+
+      registry.register(Box::new(peers.clone())).unwrap();
+      registry.register(Box::new(mails.clone())).unwrap();
+      registry.register(Box::new(inbox_unacknowledged.clone())).unwrap();
+      registry.register(Box::new(outbox_pending.clone())).unwrap();
+      registry.register(Box::new(signals.clone())).unwrap();
+      registry.register(Box::new(zome_call_duration.clone())).unwrap();
+      registry.register(Box::new(zome_call_failures.clone())).unwrap();
+      registry.register(Box::new(attachment_bytes_uploaded.clone())).unwrap();
+      registry.register(Box::new(attachment_bytes_downloaded.clone())).unwrap();
+      registry.register(Box::new(dump_state_duration.clone())).unwrap();
+
+      SnapmailMetrics {
+         registry,
+         peers,
+         mails,
+         inbox_unacknowledged,
+         outbox_pending,
+         signals,
+         zome_call_duration,
+         zome_call_failures,
+         attachment_bytes_uploaded,
+         attachment_bytes_downloaded,
+         dump_state_duration,
+      }
+   }
+
+   /// Refresh the mail gauges from a full mail list
+   pub fn update_mails(&self, all_mail_list: &Vec<MailItem>) {
+      self.mails.reset();
+      let mut unacknowledged = 0;
+      let mut pending = 0;
+      for item in all_mail_list.iter() {
+         let (direction, state) = match &item.state {
+            MailState::In(in_state) => {
+               if let InMailState::Unacknowledged = in_state {
+                  unacknowledged += 1;
+               }
+               ("in", format!("{:?}", in_state))
+            },
+            MailState::Out(out_state) => {
+               match out_state {
+                  OutMailState::Unsent | OutMailState::AllSent => pending += 1,
+                  _ => {},
+               }
+               ("out", format!("{:?}", out_state))
+            },
+         };
+         self.mails.with_label_values(&[direction, &state]).inc();
+      }
+      self.inbox_unacknowledged.set(unacknowledged);
+      self.outbox_pending.set(pending);
+   }
+
+   /// Count a received signal
+   pub fn inc_signal(&self, kind: &str) {
+      self.signals.with_label_values(&[kind]).inc();
+   }
+
+   /// Encode all metrics in the Prometheus text format
+   pub fn encode(&self) -> String {
+      let mut buffer = Vec::new();
+      let encoder = TextEncoder::new();
+      encoder.encode(&self.registry.gather(), &mut buffer)
+         .expect("Encoding metrics should not fail");
+      String::from_utf8(buffer).unwrap_or_default()
+   }
+}
+
+
+/// Call a zome function wrapper and record its latency and failure
+pub fn timed_call<T, E, F>(fn_name: &str, call: F) -> Result<T, E>
+   where F: FnOnce() -> Result<T, E>
+{
+   let start = Instant::now();
+   let res = call();
+   METRICS.zome_call_duration
+      .with_label_values(&[fn_name])
+      .observe(start.elapsed().as_secs_f64());
+   if res.is_err() {
+      METRICS.zome_call_failures.with_label_values(&[fn_name]).inc();
+   }
+   res
+}
+
+
+/// Serve the `/metrics` endpoint forever at the given address
+pub async fn serve_metrics(addr: SocketAddr) -> std::io::Result<()> {
+   let listener = TcpListener::bind(addr).await?;
+   msg!("Metrics endpoint listening at http://{}/metrics", listener.local_addr()?);
+   loop {
+      let (mut socket, _) = listener.accept().await?;
+      tokio::spawn(async move {
+         /// Only the request line matters
+         let mut buf = [0u8; 1024];
+         let n = match socket.read(&mut buf).await {
+            Ok(n) => n,
+            Err(_) => return,
+         };
+         let request = String::from_utf8_lossy(&buf[..n]);
+         let is_metrics = request.starts_with("GET /metrics ");
+         let response = if is_metrics {
+            let body = METRICS.encode();
+            format!(
+               "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+               body.len(), body,
+            )
+         } else {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+         };
+         let _ = socket.write_all(response.as_bytes()).await;
+         let _ = socket.shutdown().await;
+      });
+   }
+}
@@ -0,0 +1,201 @@
+//! Migration of a network's mailbox to a new snapmail DNA.
+//!
+//! A new zome release changes the DNA hash, so its cell can not be upgraded in place.
+//! The new DNA is installed as a new cell next to the old one, with the same agent and network seed.
+//! Mails, handles and attachments of the old cell are archived in the session folder
+//! and can be re-sent to self on the new cell.
+//! The old cell stays installed as an archived network, so it remains readable with `--network`.
+
+use crate::{
+   globals::*,
+   utils::*,
+   attachment::*,
+   api::SnapmailApi,
+   cell::SnapmailCell,
+   conductor::{install_dna_app, installed_wasm_hash},
+   networks::*,
+   wasm::SNAPMAIL_WASM_HASH,
+};
+use chrono::{DateTime, Local, TimeZone};
+use holochain::conductor::ConductorHandle;
+use holochain_types::dna::*;
+use serde::Serialize;
+use snapmail::{
+   handle::*,
+   mail::*,
+};
+use std::path::PathBuf;
+
+
+/// Outcome of a network migration, also written in its archive folder
+#[derive(Serialize, Debug, Clone)]
+pub struct MigrationReport {
+   pub alias: String,
+   /// Alias under which the old cell stays readable
+   pub archive_alias: String,
+   pub old_dna_hash: String,
+   pub new_dna_hash: String,
+   pub archive_path: PathBuf,
+   pub mail_count: usize,
+   pub handle_count: usize,
+   pub attachment_count: usize,
+   /// Attachments that could not be fetched from the old cell
+   pub attachment_failures: usize,
+   /// Mails re-sent to self on the new cell
+   pub resent_count: usize,
+   pub date: DateTime<Local>,
+}
+
+
+/// Folder holding the archive of a network's old cell
+pub fn archive_path(sid: &str, alias: &str, dna_hash: &DnaHash) -> PathBuf {
+   config_path().join(sid).join(ARCHIVES_FOLDER_NAME).join(format!("{}-{}", alias, dna_hash))
+}
+
+/// True if a network's cell does not run the wasm embedded in this release
+pub async fn is_outdated(cell: &SnapmailCell) -> bool {
+   match installed_wasm_hash(&cell.conductor, cell.dna_hash()).await {
+      Some(wasm_hash) => wasm_hash.to_string() != SNAPMAIL_WASM_HASH,
+      None => true,
+   }
+}
+
+
+/// Move a network of a session to the current snapmail DNA (or the DNA at `maybe_dna_path`).
+/// Uses the default network if no alias is given.
+pub async fn migrate_network(
+   conductor: ConductorHandle,
+   sid: &str,
+   maybe_alias: Option<&str>,
+   maybe_dna_path: Option<PathBuf>,
+   resend: bool,
+) -> anyhow::Result<MigrationReport> {
+   let alias = maybe_alias.unwrap_or(DEFAULT_NETWORK_ALIAS);
+   let mut networks = SessionNetworks::load(sid)?;
+   let entry = networks.get(alias)?.clone();
+   if entry.archived {
+      anyhow::bail!("Network \"{}\" is an archive and can not be migrated", alias);
+   }
+   let old_cell = network_cell(conductor.clone(), sid, Some(alias))?;
+   if maybe_dna_path.is_none() && !is_outdated(&old_cell).await {
+      anyhow::bail!("Network \"{}\" already uses the current DNA", alias);
+   }
+   let old_dna_hash = old_cell.dna_hash().clone();
+
+   /// Archive old cell content
+   let archive_path = archive_path(sid, alias, &old_dna_hash);
+   msg!("Archiving network \"{}\" in {:?}", alias, archive_path);
+   std::fs::create_dir_all(&archive_path)?;
+   let my_handle = old_cell.get_my_handle().unwrap_or_default();
+   let handle_list = old_cell.get_all_handles()?;
+   std::fs::write(archive_path.join("handles.json"), serde_json::to_string_pretty(&handle_list)?)?;
+   let mail_list = old_cell.get_all_mails()?;
+   std::fs::write(archive_path.join("mails.json"), serde_json::to_string_pretty(&mail_list)?)?;
+   /// Attachments are stored in a folder per mail
+   let mut attachment_count = 0;
+   let mut attachment_failures = 0;
+   for item in mail_list.iter() {
+      if item.mail.attachments.is_empty() {
+         continue;
+      }
+      let mail_folder = archive_path.join("attachments").join(item.address.to_string());
+      std::fs::create_dir_all(&mail_folder)?;
+      for attachment in item.mail.attachments.iter() {
+         match get_attachment(&old_cell, attachment.manifest_eh.clone(), mail_folder.clone()) {
+            Ok(_) => attachment_count += 1,
+            Err(e) => {
+               err_msg!("Failed to archive attachment \"{}\" of mail {}: {}", attachment.filename, item.address, e);
+               attachment_failures += 1;
+            },
+         }
+      }
+   }
+
+   /// Install new DNA for the same agent and network seed
+   let app_id = format!("{}@{}", entry.app_id.split('@').next().unwrap(), Local::now().format("%Y%m%d%H%M%S"));
+   let agent_key = old_cell.agent_pubkey().clone();
+   let new_dna_hash = install_dna_app(conductor.clone(), app_id.clone(), agent_key, entry.network_seed.clone(), maybe_dna_path).await?;
+   msg!("Installed new DNA for network \"{}\": {}", alias, new_dna_hash);
+
+   /// Old cell becomes an archived network. The alias now addresses the new cell.
+   let mut archive_alias = format!("{}-archive", alias);
+   let mut index = 2;
+   while networks.get(&archive_alias).is_ok() {
+      archive_alias = format!("{}-archive{}", alias, index);
+      index += 1;
+   }
+   for network in networks.networks.iter_mut().filter(|network| network.alias == alias) {
+      network.alias = archive_alias.clone();
+      network.dna_hash = Some(old_dna_hash.to_string());
+      network.archived = true;
+   }
+   networks.networks.push(NetworkEntry {
+      alias: alias.to_string(),
+      network_seed: entry.network_seed.clone(),
+      app_id,
+      dna_hash: Some(new_dna_hash.to_string()),
+      archived: false,
+   });
+   networks.save(sid)?;
+
+   /// Publish same handle on the new cell
+   let new_cell = network_cell(conductor.clone(), sid, Some(alias))?;
+   if !my_handle.is_empty() {
+      new_cell.set_handle(my_handle)?;
+   }
+
+   /// Re-send mails to self
+   let mut resent_count = 0;
+   if resend {
+      for item in mail_list.iter() {
+         let mail_folder = archive_path.join("attachments").join(item.address.to_string());
+         match resend_to_self(&new_cell, &handle_list, item, mail_folder) {
+            Ok(_) => resent_count += 1,
+            Err(e) => err_msg!("Failed to re-send mail {}: {}", item.address, e),
+         }
+      }
+   }
+
+   /// Done
+   let report = MigrationReport {
+      alias: alias.to_string(),
+      archive_alias,
+      old_dna_hash: old_dna_hash.to_string(),
+      new_dna_hash: new_dna_hash.to_string(),
+      archive_path: archive_path.clone(),
+      mail_count: mail_list.len(),
+      handle_count: handle_list.len(),
+      attachment_count,
+      attachment_failures,
+      resent_count,
+      date: Local::now(),
+   };
+   std::fs::write(archive_path.join("report.json"), serde_json::to_string_pretty(&report)?)?;
+   Ok(report)
+}
+
+
+/// Send a copy of an archived mail to self on the new cell.
+/// The original author and date are prepended to the content.
+fn resend_to_self(cell: &dyn SnapmailApi, handle_list: &Vec<HandleItem>, item: &MailItem, mail_folder: PathBuf) -> anyhow::Result<ActionHash> {
+   let author = get_name(handle_list, &item.author).unwrap_or(format!("{}", item.author));
+   let date = Local.timestamp_opt(item.mail.date_sent as i64, 0).single()
+      .map(|date| date.to_string())
+      .unwrap_or_else(|| format!("unknown ({})", item.mail.date_sent));
+   let mut manifest_address_list = Vec::new();
+   for attachment in item.mail.attachments.iter() {
+      let path = mail_folder.join(&attachment.filename);
+      if path.exists() {
+         manifest_address_list.push(write_attachment(cell, path)?);
+      }
+   }
+   let mail = SendMailInput {
+      subject: format!("[archived] {}", item.mail.subject),
+      payload: format!("From: {}\nDate: {}\n\n{}", author, date, item.mail.payload),
+      to: vec![cell.my_agent_pubkey()],
+      cc: vec![],
+      bcc: vec![],
+      manifest_address_list,
+   };
+   Ok(cell.send_mail(mail)?)
+}
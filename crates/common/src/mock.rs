@@ -0,0 +1,575 @@
+//! In-memory [`SnapmailApi`] for tests and offline development.
+//! Requires the `test-support` feature.
+//!
+//! A [`MockNetwork`] holds the state shared by all its agents: handles, mails, acks and files.
+//! Each [`MockSnapmail`] calls the API as one agent of that network.
+//! Mails and acks are delivered immediately to online agents.
+//! For offline agents they stay pending until the recipient checks its inbox
+//! or the sender resends them while the recipient is online.
+//! An agent can hold its hand-offs, leaving its new mails `Unsent` and its acks `AckUnsent`.
+
+use crate::api::SnapmailApi;
+use holochain_types::dna::*;
+use holochain_zome_types::*;
+use snapmail::{
+   api_error::*,
+   file::*,
+   handle::*,
+   mail::*,
+   mail::entries::*,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+
+/// Mail sent by an agent of the mock network
+#[derive(Debug, Clone)]
+struct MockOutMail {
+   ah: ActionHash,
+   author: AgentPubKey,
+   mail: Mail,
+   bcc: Vec<AgentPubKey>,
+   deliveries: HashMap<AgentPubKey, DeliveryState>,
+   acks: HashSet<AgentPubKey>,
+   deleted: bool,
+   /// False until the sender hands it off to its recipients
+   handed_off: bool,
+}
+
+impl MockOutMail {
+   fn recipients(&self) -> Vec<AgentPubKey> {
+      self.mail.to.iter()
+         .chain(self.mail.cc.iter())
+         .chain(self.bcc.iter())
+         .cloned()
+         .collect()
+   }
+
+   fn state(&self) -> OutMailState {
+      let recipients = self.recipients();
+      if self.deleted {
+         return OutMailState::Deleted;
+      }
+      if !self.handed_off {
+         return OutMailState::Unsent;
+      }
+      if recipients.iter().all(|agent| self.acks.contains(agent)) {
+         return OutMailState::AllAcknowledged;
+      }
+      if self.deliveries.values().all(|state| *state == DeliveryState::Delivered) {
+         return OutMailState::AllReceived;
+      }
+      OutMailState::AllSent
+   }
+}
+
+/// Mail received by an agent of the mock network
+#[derive(Debug, Clone)]
+struct MockInMail {
+   ah: ActionHash,
+   outmail_ah: ActionHash,
+   from: AgentPubKey,
+   mail: Mail,
+   date_received: u64,
+   state: InMailState,
+}
+
+/// An agent of the mock network
+#[derive(Debug, Clone)]
+struct MockAgent {
+   key: AgentPubKey,
+   handle: String,
+   online: bool,
+   /// Hand-offs of new mails and acks are held, as when the conductor stops right after a commit
+   holds_hand_offs: bool,
+   inmails: Vec<MockInMail>,
+}
+
+/// State shared by all agents of a [`MockNetwork`]
+#[derive(Debug, Default)]
+struct MockState {
+   next_id: u32,
+   agents: Vec<MockAgent>,
+   outmails: Vec<MockOutMail>,
+   /// Acks not delivered yet, as (recipient of the mail, outmail)
+   pending_acks: Vec<(AgentPubKey, ActionHash)>,
+   manifests: HashMap<AnyDhtHash, (EntryHash, FileManifest)>,
+   chunks: HashMap<EntryHash, String>,
+}
+
+impl MockState {
+   /// Raw 36 bytes of a new unique hash
+   fn next_raw_36(&mut self) -> Vec<u8> {
+      self.next_id += 1;
+      let mut raw = vec![0xdb; 32];
+      raw.extend_from_slice(&self.next_id.to_be_bytes());
+      raw
+   }
+
+   fn new_action_hash(&mut self) -> ActionHash {
+      ActionHash::from_raw_36(self.next_raw_36())
+   }
+
+   fn new_entry_hash(&mut self) -> EntryHash {
+      EntryHash::from_raw_36(self.next_raw_36())
+   }
+
+   fn agent(&self, key: &AgentPubKey) -> SnapmailApiResult<&MockAgent> {
+      self.agents.iter()
+         .find(|agent| agent.key == *key)
+         .ok_or(SnapmailApiError::Unique(format!("Unknown agent: {}", key)))
+   }
+
+   fn agent_mut(&mut self, key: &AgentPubKey) -> SnapmailApiResult<&mut MockAgent> {
+      self.agents.iter_mut()
+         .find(|agent| agent.key == *key)
+         .ok_or(SnapmailApiError::Unique(format!("Unknown agent: {}", key)))
+   }
+
+   fn is_online(&self, key: &AgentPubKey) -> bool {
+      self.agent(key).map(|agent| agent.online).unwrap_or(false)
+   }
+
+   fn holds_hand_offs(&self, key: &AgentPubKey) -> bool {
+      self.agent(key).map(|agent| agent.holds_hand_offs).unwrap_or(false)
+   }
+
+   /// Deliver a handed off mail to its online recipients
+   fn hand_off(&mut self, outmail_index: usize) {
+      self.outmails[outmail_index].handed_off = true;
+      for recipient in self.outmails[outmail_index].recipients() {
+         if self.is_online(&recipient) {
+            self.deliver(outmail_index, &recipient);
+         }
+      }
+   }
+
+   /// Queue an ack and deliver it if the mail's sender is online
+   fn hand_off_ack(&mut self, from: &AgentPubKey, inmail_ah: &ActionHash) {
+      let Ok(agent) = self.agent_mut(from) else { return };
+      let Some(inmail) = agent.inmails.iter_mut().find(|inmail| inmail.ah == *inmail_ah) else { return };
+      inmail.state = InMailState::AckPending;
+      let (sender, outmail_ah) = (inmail.from.clone(), inmail.outmail_ah.clone());
+      self.pending_acks.push((from.clone(), outmail_ah.clone()));
+      if self.is_online(&sender) {
+         self.deliver_ack(from, &outmail_ah);
+      }
+   }
+
+   /// Store the mail in the recipient's inbox if it is still pending
+   fn deliver(&mut self, outmail_index: usize, recipient: &AgentPubKey) {
+      let outmail = &self.outmails[outmail_index];
+      if !outmail.handed_off || outmail.deliveries.get(recipient) != Some(&DeliveryState::Pending) {
+         return;
+      }
+      let ah = self.new_action_hash();
+      let outmail = &self.outmails[outmail_index];
+      let inmail = MockInMail {
+         ah,
+         outmail_ah: outmail.ah.clone(),
+         from: outmail.author.clone(),
+         mail: outmail.mail.clone(),
+         date_received: now(),
+         state: InMailState::Unacknowledged,
+      };
+      if let Ok(agent) = self.agent_mut(recipient) {
+         agent.inmails.push(inmail);
+         self.outmails[outmail_index].deliveries.insert(recipient.clone(), DeliveryState::Delivered);
+      }
+   }
+
+   /// Record the ack in the sender's outmail and mark it delivered in the recipient's inmail
+   fn deliver_ack(&mut self, from: &AgentPubKey, outmail_ah: &ActionHash) {
+      if let Some(outmail) = self.outmails.iter_mut().find(|outmail| outmail.ah == *outmail_ah) {
+         outmail.acks.insert(from.clone());
+      }
+      self.pending_acks.retain(|(agent, ah)| !(agent == from && ah == outmail_ah));
+      if let Ok(agent) = self.agent_mut(from) {
+         for inmail in agent.inmails.iter_mut().filter(|inmail| inmail.outmail_ah == *outmail_ah) {
+            if inmail.state == InMailState::AckPending {
+               inmail.state = InMailState::AckDelivered;
+            }
+         }
+      }
+   }
+
+   fn mail_items(&self, me: &AgentPubKey) -> Vec<MailItem> {
+      let mut items = Vec::new();
+      for outmail in self.outmails.iter().filter(|outmail| outmail.author == *me) {
+         items.push(MailItem {
+            address: outmail.ah.clone(),
+            author: me.clone(),
+            mail: outmail.mail.clone(),
+            state: MailState::Out(outmail.state()),
+            bcc: outmail.bcc.clone(),
+            date: outmail.mail.date_sent as i64,
+         });
+      }
+      if let Ok(agent) = self.agent(me) {
+         for inmail in agent.inmails.iter() {
+            items.push(MailItem {
+               address: inmail.ah.clone(),
+               author: inmail.from.clone(),
+               mail: inmail.mail.clone(),
+               state: MailState::In(inmail.state.clone()),
+               bcc: vec![],
+               date: inmail.date_received as i64,
+            });
+         }
+      }
+      items
+   }
+}
+
+/// Seconds since the epoch
+fn now() -> u64 {
+   chrono::Utc::now().timestamp() as u64
+}
+
+
+/// Network of in-memory snapmail agents
+#[derive(Debug, Clone, Default)]
+pub struct MockNetwork {
+   state: Arc<Mutex<MockState>>,
+}
+
+impl MockNetwork {
+   ///
+   pub fn new() -> Self {
+      Self::default()
+   }
+
+   /// Add an online agent publishing `handle`
+   pub fn add_agent(&self, handle: &str) -> MockSnapmail {
+      let mut state = self.lock();
+      let key = AgentPubKey::from_raw_36(state.next_raw_36());
+      state.agents.push(MockAgent {
+         key: key.clone(),
+         handle: handle.to_string(),
+         online: true,
+         holds_hand_offs: false,
+         inmails: Vec::new(),
+      });
+      MockSnapmail { agent: key, state: self.state.clone() }
+   }
+
+   fn lock(&self) -> MutexGuard<'_, MockState> {
+      self.state.lock().expect("Mock state lock should not be poisoned")
+   }
+}
+
+
+/// [`SnapmailApi`] of one agent of a [`MockNetwork`]
+#[derive(Debug, Clone)]
+pub struct MockSnapmail {
+   agent: AgentPubKey,
+   state: Arc<Mutex<MockState>>,
+}
+
+impl MockSnapmail {
+   /// Offline agents do not receive mails or acks until they come back and check their inbox
+   pub fn set_online(&self, online: bool) {
+      if let Ok(agent) = self.lock().agent_mut(&self.agent) {
+         agent.online = online;
+      }
+   }
+
+   /// Hold the hand-offs of this agent's new mails and acks: they stay `Unsent` and `AckUnsent`.
+   /// Held hand-offs are done when hand-offs are allowed again.
+   pub fn hold_hand_offs(&self, hold: bool) {
+      let mut state = self.lock();
+      if let Ok(agent) = state.agent_mut(&self.agent) {
+         agent.holds_hand_offs = hold;
+      }
+      if hold {
+         return;
+      }
+      for index in 0..state.outmails.len() {
+         if state.outmails[index].author == self.agent && !state.outmails[index].handed_off {
+            state.hand_off(index);
+         }
+      }
+      let unsent_acks: Vec<ActionHash> = state.agent(&self.agent).map(|agent| agent.inmails.iter()
+         .filter(|inmail| inmail.state == InMailState::AckUnsent)
+         .map(|inmail| inmail.ah.clone())
+         .collect())
+         .unwrap_or_default();
+      for ah in unsent_acks.iter() {
+         state.hand_off_ack(&self.agent, ah);
+      }
+   }
+
+   fn lock(&self) -> MutexGuard<'_, MockState> {
+      self.state.lock().expect("Mock state lock should not be poisoned")
+   }
+}
+
+
+impl SnapmailApi for MockSnapmail {
+   fn my_agent_pubkey(&self) -> AgentPubKey {
+      self.agent.clone()
+   }
+   fn peer_count(&self) -> anyhow::Result<usize> {
+      Ok(self.lock().agents.iter().filter(|agent| agent.online).count())
+   }
+
+   // -- Handles
+
+   fn get_my_handle(&self) -> SnapmailApiResult<String> {
+      Ok(self.lock().agent(&self.agent)?.handle.clone())
+   }
+   fn set_handle(&self, handle: String) -> SnapmailApiResult<EntryHash> {
+      let mut state = self.lock();
+      state.agent_mut(&self.agent)?.handle = handle;
+      Ok(state.new_entry_hash())
+   }
+   fn get_all_handles(&self) -> SnapmailApiResult<Vec<HandleItem>> {
+      let mut state = self.lock();
+      let agents: Vec<(String, AgentPubKey)> = state.agents.iter()
+         .filter(|agent| !agent.handle.is_empty())
+         .map(|agent| (agent.handle.clone(), agent.key.clone()))
+         .collect();
+      Ok(agents.into_iter()
+         .map(|(name, agent_id)| HandleItem {
+            name,
+            agentId: agent_id,
+            handle_eh: state.new_entry_hash(),
+         })
+         .collect())
+   }
+   fn find_agent(&self, handle: String) -> SnapmailApiResult<Vec<AgentPubKey>> {
+      Ok(self.lock().agents.iter()
+         .filter(|agent| agent.handle == handle)
+         .map(|agent| agent.key.clone())
+         .collect())
+   }
+   fn ping_agent(&self, agent: AgentPubKey) -> SnapmailApiResult<bool> {
+      Ok(self.lock().is_online(&agent))
+   }
+   fn get_enc_key(&self, agent: AgentPubKey) -> SnapmailApiResult<X25519PubKey> {
+      let state = self.lock();
+      let _ = state.agent(&agent)?;
+      let mut raw = [0u8; 32];
+      raw.copy_from_slice(agent.get_raw_32());
+      Ok(X25519PubKey::from(raw))
+   }
+
+   // -- Mails
+
+   fn send_mail(&self, input: SendMailInput) -> SnapmailApiResult<ActionHash> {
+      let mut state = self.lock();
+      /// Resolve attachments
+      let mut attachments = Vec::new();
+      for manifest_ah in input.manifest_address_list.iter() {
+         let (manifest_eh, manifest) = state.manifests.get(&AnyDhtHash::from(manifest_ah.clone()))
+            .cloned()
+            .ok_or(SnapmailApiError::Unique(format!("Unknown manifest: {}", manifest_ah)))?;
+         attachments.push(AttachmentInfo {
+            manifest_eh,
+            data_hash: manifest.data_hash,
+            filename: manifest.filename,
+            filetype: manifest.filetype,
+            orig_filesize: manifest.orig_filesize,
+         });
+      }
+      /// Commit outmail
+      let mail = Mail {
+         date_sent: now(),
+         subject: input.subject,
+         payload: input.payload,
+         to: input.to,
+         cc: input.cc,
+         attachments,
+      };
+      let ah = state.new_action_hash();
+      let mut outmail = MockOutMail {
+         ah: ah.clone(),
+         author: self.agent.clone(),
+         mail,
+         bcc: input.bcc,
+         deliveries: HashMap::new(),
+         acks: HashSet::new(),
+         deleted: false,
+         handed_off: false,
+      };
+      for recipient in outmail.recipients() {
+         let _ = state.agent(&recipient)?;
+         outmail.deliveries.insert(recipient, DeliveryState::Pending);
+      }
+      state.outmails.push(outmail);
+      /// Deliver to online recipients
+      if !state.holds_hand_offs(&self.agent) {
+         let index = state.outmails.len() - 1;
+         state.hand_off(index);
+      }
+      Ok(ah)
+   }
+   fn get_all_mails(&self) -> SnapmailApiResult<Vec<MailItem>> {
+      Ok(self.lock().mail_items(&self.agent))
+   }
+   fn get_mail(&self, ah: ActionHash) -> SnapmailApiResult<GetMailOutput> {
+      let state = self.lock();
+      if let Some(outmail) = state.outmails.iter().find(|outmail| outmail.ah == ah && outmail.author == self.agent) {
+         return Ok(GetMailOutput(Some(Err(OutMail { mail: outmail.mail.clone(), bcc: outmail.bcc.clone() }))));
+      }
+      let agent = state.agent(&self.agent)?;
+      let maybe_inmail = agent.inmails.iter()
+         .find(|inmail| inmail.ah == ah)
+         .map(|inmail| Ok(InMail {
+            mail: inmail.mail.clone(),
+            date_received: inmail.date_received,
+            outmail_eh: EntryHash::from_raw_36(inmail.outmail_ah.get_raw_36().to_vec()),
+            from: inmail.from.clone(),
+         }));
+      Ok(GetMailOutput(maybe_inmail))
+   }
+   fn delete_mail(&self, ah: ActionHash) -> SnapmailApiResult<DeleteMailOutput> {
+      let mut state = self.lock();
+      let me = self.agent.clone();
+      let mut found = false;
+      if let Some(outmail) = state.outmails.iter_mut().find(|outmail| outmail.ah == ah && outmail.author == me) {
+         outmail.deleted = true;
+         found = true;
+      }
+      if let Some(inmail) = state.agent_mut(&me)?.inmails.iter_mut().find(|inmail| inmail.ah == ah) {
+         inmail.state = InMailState::Deleted;
+         found = true;
+      }
+      if !found {
+         return Ok(DeleteMailOutput(None));
+      }
+      Ok(DeleteMailOutput(Some(state.new_action_hash())))
+   }
+   fn acknowledge_mail(&self, ah: ActionHash) -> SnapmailApiResult<EntryHash> {
+      let mut state = self.lock();
+      let me = self.agent.clone();
+      let inmail = state.agent(&me)?.inmails.iter()
+         .find(|inmail| inmail.ah == ah)
+         .cloned()
+         .ok_or(SnapmailApiError::Unique(format!("No inmail at {}", ah)))?;
+      if inmail.state != InMailState::Unacknowledged {
+         return Err(SnapmailApiError::Unique("Mail has already been acknowledged".to_string()));
+      }
+      if state.holds_hand_offs(&me) {
+         for stored in state.agent_mut(&me)?.inmails.iter_mut().filter(|stored| stored.ah == ah) {
+            stored.state = InMailState::AckUnsent;
+         }
+      } else {
+         state.hand_off_ack(&me, &ah);
+      }
+      Ok(state.new_entry_hash())
+   }
+   fn check_mail_inbox(&self) -> SnapmailApiResult<Vec<ActionHash>> {
+      let mut state = self.lock();
+      let before = state.agent(&self.agent)?.inmails.len();
+      for index in 0..state.outmails.len() {
+         state.deliver(index, &self.agent);
+      }
+      Ok(state.agent(&self.agent)?.inmails[before..].iter()
+         .map(|inmail| inmail.ah.clone())
+         .collect())
+   }
+   fn check_ack_inbox(&self) -> SnapmailApiResult<Vec<EntryHash>> {
+      let mut state = self.lock();
+      let mine: Vec<(AgentPubKey, ActionHash)> = state.pending_acks.iter()
+         .filter(|(_, outmail_ah)| state.outmails.iter().any(|outmail| outmail.ah == *outmail_ah && outmail.author == self.agent))
+         .cloned()
+         .collect();
+      let mut result = Vec::new();
+      for (from, outmail_ah) in mine {
+         state.deliver_ack(&from, &outmail_ah);
+         result.push(state.new_entry_hash());
+      }
+      Ok(result)
+   }
+   fn get_outmail_state(&self, ah: ActionHash) -> SnapmailApiResult<OutMailState> {
+      self.lock().outmails.iter()
+         .find(|outmail| outmail.ah == ah && outmail.author == self.agent)
+         .map(|outmail| outmail.state())
+         .ok_or(SnapmailApiError::Unique(format!("No outmail at {}", ah)))
+   }
+   fn get_outmail_delivery_state(&self, ah: ActionHash) -> SnapmailApiResult<HashMap<AgentPubKey, DeliveryState>> {
+      self.lock().outmails.iter()
+         .find(|outmail| outmail.ah == ah && outmail.author == self.agent)
+         .map(|outmail| outmail.deliveries.clone())
+         .ok_or(SnapmailApiError::Unique(format!("No outmail at {}", ah)))
+   }
+   fn resend_outmails(&self) -> SnapmailApiResult<Vec<ActionHash>> {
+      let mut state = self.lock();
+      let mut resent = Vec::new();
+      for index in 0..state.outmails.len() {
+         if state.outmails[index].author != self.agent || !state.outmails[index].handed_off {
+            continue;
+         }
+         let pendings: Vec<AgentPubKey> = state.outmails[index].deliveries.iter()
+            .filter(|(agent, delivery)| **delivery == DeliveryState::Pending && state.is_online(agent))
+            .map(|(agent, _)| agent.clone())
+            .collect();
+         for recipient in pendings.iter() {
+            state.deliver(index, recipient);
+         }
+         if !pendings.is_empty() {
+            resent.push(state.outmails[index].ah.clone());
+         }
+      }
+      Ok(resent)
+   }
+   fn resend_outacks(&self) -> SnapmailApiResult<Vec<ActionHash>> {
+      let mut state = self.lock();
+      let mine: Vec<ActionHash> = state.pending_acks.iter()
+         .filter(|(from, _)| *from == self.agent)
+         .map(|(_, outmail_ah)| outmail_ah.clone())
+         .collect();
+      let mut resent = Vec::new();
+      for outmail_ah in mine {
+         let is_sender_online = state.outmails.iter()
+            .find(|outmail| outmail.ah == outmail_ah)
+            .map(|outmail| state.is_online(&outmail.author))
+            .unwrap_or(false);
+         if is_sender_online {
+            state.deliver_ack(&self.agent, &outmail_ah);
+            resent.push(outmail_ah);
+         }
+      }
+      Ok(resent)
+   }
+   fn request_acks(&self) -> SnapmailApiResult<u32> {
+      Ok(self.check_ack_inbox()?.len() as u32)
+   }
+
+   // -- Files
+
+   fn write_chunk(&self, input: FileChunk) -> SnapmailApiResult<EntryHash> {
+      let mut state = self.lock();
+      let eh = state.new_entry_hash();
+      state.chunks.insert(eh.clone(), input.chunk);
+      Ok(eh)
+   }
+   fn write_manifest(&self, input: WriteManifestInput) -> SnapmailApiResult<ActionHash> {
+      let mut state = self.lock();
+      let manifest = FileManifest {
+         data_hash: input.data_hash,
+         filename: input.filename,
+         filetype: input.filetype,
+         orig_filesize: input.orig_filesize,
+         chunks: input.chunks,
+         content: None,
+      };
+      let ah = state.new_action_hash();
+      let eh = state.new_entry_hash();
+      /// Manifest can be fetched by action or by entry
+      state.manifests.insert(AnyDhtHash::from(ah.clone()), (eh.clone(), manifest.clone()));
+      state.manifests.insert(AnyDhtHash::from(eh.clone()), (eh, manifest));
+      Ok(ah)
+   }
+   fn get_manifest(&self, address: AnyDhtHash) -> SnapmailApiResult<FileManifest> {
+      self.lock().manifests.get(&address)
+         .map(|(_, manifest)| manifest.clone())
+         .ok_or(SnapmailApiError::Unique(format!("No manifest at {}", address)))
+   }
+   fn get_chunk(&self, eh: EntryHash) -> SnapmailApiResult<String> {
+      self.lock().chunks.get(&eh)
+         .cloned()
+         .ok_or(SnapmailApiError::Unique(format!("No chunk at {}", eh)))
+   }
+}
@@ -0,0 +1,177 @@
+//! Snapmail networks joined by a session.
+//!
+//! A session's agent can take part in several snapmail networks, each with its own network seed.
+//! Each network is a cell installed as its own app and is addressed by a user defined alias.
+//! The network created by `setup` has the alias [`DEFAULT_NETWORK_ALIAS`]
+//! and its seed stays in [`APP_CONFIG_FILENAME`].
+
+use crate::{
+   globals::*,
+   cell::SnapmailCell,
+   conductor::install_dna_app,
+};
+use holochain::conductor::ConductorHandle;
+use holochain_types::dna::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+
+/// A snapmail network joined by a session
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NetworkEntry {
+   pub alias: String,
+   pub network_seed: String,
+   pub app_id: String,
+   /// DNA hash of the installed cell. Unknown for sessions set up before multi-network support.
+   pub dna_hash: Option<String>,
+   /// Cell of an older DNA kept readable after a `migrate`. Its wasm is not checked at startup.
+   #[serde(default)]
+   pub archived: bool,
+}
+
+/// All networks joined by a session, stored in [`NETWORKS_FILENAME`]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SessionNetworks {
+   pub networks: Vec<NetworkEntry>,
+}
+
+
+impl SessionNetworks {
+   /// Path of the networks file of a session
+   pub fn filepath(sid: &str) -> PathBuf {
+      config_path().join(sid).join(NETWORKS_FILENAME)
+   }
+
+   /// Load networks of a session. The default network always comes first.
+   pub fn load(sid: &str) -> anyhow::Result<Self> {
+      let filepath = Self::filepath(sid);
+      let mut networks = match std::fs::read_to_string(&filepath) {
+         Ok(yaml) => serde_yaml::from_str::<SessionNetworks>(&yaml)?,
+         Err(e) if e.kind() == std::io::ErrorKind::NotFound => SessionNetworks::default(),
+         Err(e) => anyhow::bail!("Failed to read networks file {:?}: {}", filepath, e),
+      };
+      /// Default network seed can be changed with "change --uid"
+      let app_filepath = config_path().join(sid).join(APP_CONFIG_FILENAME);
+      let default_seed = std::fs::read_to_string(app_filepath).unwrap_or_default();
+      let maybe_default = networks.networks.iter()
+         .find(|entry| entry.alias == DEFAULT_NETWORK_ALIAS)
+         .cloned();
+      networks.networks.retain(|entry| entry.alias != DEFAULT_NETWORK_ALIAS);
+      networks.networks.insert(0, NetworkEntry {
+         alias: DEFAULT_NETWORK_ALIAS.to_string(),
+         network_seed: default_seed,
+         /// App ID changes when the default network is migrated
+         app_id: maybe_default.as_ref().map(|entry| entry.app_id.clone()).unwrap_or(SNAPMAIL_APP_ID.to_string()),
+         dna_hash: maybe_default.and_then(|entry| entry.dna_hash),
+         archived: false,
+      });
+      Ok(networks)
+   }
+
+   /// Write networks to the session's networks file
+   pub fn save(&self, sid: &str) -> anyhow::Result<()> {
+      let yaml = serde_yaml::to_string(self)?;
+      std::fs::write(Self::filepath(sid), yaml)?;
+      Ok(())
+   }
+
+   ///
+   pub fn get(&self, alias: &str) -> anyhow::Result<&NetworkEntry> {
+      self.networks.iter()
+         .find(|entry| entry.alias == alias)
+         .ok_or_else(|| anyhow::anyhow!("Unknown network \"{}\". Known networks: {}", alias, self.aliases().join(", ")))
+   }
+
+   ///
+   pub fn aliases(&self) -> Vec<String> {
+      self.networks.iter().map(|entry| entry.alias.clone()).collect()
+   }
+
+   /// DNA hashes of archived networks
+   pub fn archived_dnas(&self) -> Vec<String> {
+      self.networks.iter()
+         .filter(|entry| entry.archived)
+         .filter_map(|entry| entry.dna_hash.clone())
+         .collect()
+   }
+
+   /// Record the DNA hash of the default network
+   pub fn set_default_dna(sid: &str, dna_hash: &DnaHash) -> anyhow::Result<()> {
+      let mut networks = Self::load(sid)?;
+      networks.networks[0].dna_hash = Some(dna_hash.to_string());
+      networks.save(sid)
+   }
+}
+
+
+/// Get the cell of a session's network. Uses the default network if no alias is given.
+pub fn network_cell(conductor: ConductorHandle, sid: &str, maybe_alias: Option<&str>) -> anyhow::Result<SnapmailCell> {
+   let alias = maybe_alias.unwrap_or(DEFAULT_NETWORK_ALIAS);
+   let networks = SessionNetworks::load(sid)?;
+   let entry = networks.get(alias)?;
+   let cell_ids = conductor.list_cell_ids(None);
+   let maybe_cell_id = match &entry.dna_hash {
+      Some(dna_hash) => cell_ids.into_iter().find(|cell_id| &cell_id.dna_hash().to_string() == dna_hash),
+      /// Sessions set up before multi-network support have a single cell
+      None if cell_ids.len() == 1 => cell_ids.into_iter().next(),
+      None => None,
+   };
+   let cell_id = maybe_cell_id
+      .ok_or_else(|| anyhow::anyhow!("No installed cell found for network \"{}\" of session \"{}\"", alias, sid))?;
+   Ok(SnapmailCell::new(alias, conductor, cell_id))
+}
+
+/// Get the cells of all networks of a session
+pub fn all_network_cells(conductor: ConductorHandle, sid: &str) -> anyhow::Result<Vec<SnapmailCell>> {
+   let networks = SessionNetworks::load(sid)?;
+   let mut cells = Vec::new();
+   for alias in networks.aliases() {
+      cells.push(network_cell(conductor.clone(), sid, Some(&alias))?);
+   }
+   Ok(cells)
+}
+
+
+/// Join another snapmail network with the session's agent
+pub async fn join_network(
+   conductor: ConductorHandle,
+   sid: &str,
+   alias: &str,
+   network_seed: String,
+   maybe_dna_path: Option<PathBuf>,
+) -> anyhow::Result<DnaHash> {
+   let mut networks = SessionNetworks::load(sid)?;
+   if networks.get(alias).is_ok() {
+      anyhow::bail!("Network \"{}\" already joined", alias);
+   }
+   if let Some(entry) = networks.networks.iter().find(|entry| !entry.archived && entry.network_seed == network_seed) {
+      anyhow::bail!("Network seed \"{}\" already joined as \"{}\"", network_seed, entry.alias);
+   }
+   /// Same agent on every network
+   let default_cell = network_cell(conductor.clone(), sid, None)?;
+   let agent_key = default_cell.agent_pubkey().clone();
+   let app_id = format!("{}-{}", SNAPMAIL_APP_ID, alias);
+   let dna_hash = install_dna_app(conductor, app_id.clone(), agent_key, network_seed.clone(), maybe_dna_path).await?;
+   networks.networks.push(NetworkEntry {
+      alias: alias.to_string(),
+      network_seed,
+      app_id,
+      dna_hash: Some(dna_hash.to_string()),
+      archived: false,
+   });
+   networks.save(sid)?;
+   Ok(dna_hash)
+}
+
+/// Leave a snapmail network. Its cell and data are removed from the conductor.
+pub async fn leave_network(conductor: ConductorHandle, sid: &str, alias: &str) -> anyhow::Result<()> {
+   if alias == DEFAULT_NETWORK_ALIAS {
+      anyhow::bail!("The default network can not be left");
+   }
+   let mut networks = SessionNetworks::load(sid)?;
+   let app_id = networks.get(alias)?.app_id.clone();
+   conductor.clone().uninstall_app(&app_id).await?;
+   networks.networks.retain(|entry| entry.alias != alias);
+   networks.save(sid)?;
+   Ok(())
+}
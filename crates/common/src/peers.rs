@@ -0,0 +1,136 @@
+//! Network diagnostics: agents known by a cell and reachability of the network services.
+//!
+//! Peers come from the agent infos held by the conductor's p2p agent store.
+//! Each agent info is signed by its agent and tells which part of the DHT it stores,
+//! where it can be reached and until when the info is valid.
+
+use crate::{
+   api::SnapmailApi,
+   cell::SnapmailCell,
+   config::{read_config, network_urls},
+   session::session_path,
+};
+use chrono::{DateTime, Local, TimeZone};
+use holochain_p2p::AgentPubKeyExt;
+use holochain_types::dna::*;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use url2::Url2;
+
+/// Time allowed to reach a network service
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+
+/// An agent known on the network of a cell
+#[derive(Serialize, Debug, Clone)]
+pub struct PeerInfo {
+   pub agent: AgentPubKey,
+   /// Handle published by the agent, if it could be resolved
+   pub handle: Option<String>,
+   pub is_me: bool,
+   /// Part of the DHT the agent claims to store
+   pub storage_arc: String,
+   pub signed_at: DateTime<Local>,
+   pub expires_at: DateTime<Local>,
+   /// Transport URLs the agent can be reached at
+   pub urls: Vec<String>,
+}
+
+impl PeerInfo {
+   /// Agent info is no longer valid: the agent has not refreshed it and is probably offline
+   pub fn is_expired(&self) -> bool {
+      self.expires_at < Local::now()
+   }
+}
+
+/// Outcome of contacting a network service
+#[derive(Serialize, Debug, Clone)]
+pub struct ServiceCheck {
+   /// "bootstrap" or "proxy"
+   pub service: &'static str,
+   /// `None` if the service is not configured
+   pub url: Option<String>,
+   pub is_ok: bool,
+   pub message: String,
+}
+
+
+///
+fn local_time(ms: u64) -> DateTime<Local> {
+   Local.timestamp_millis_opt(ms as i64).single().unwrap_or_else(Local::now)
+}
+
+/// Agents known on the network of a cell, self first
+pub async fn list_peers(cell: &SnapmailCell) -> anyhow::Result<Vec<PeerInfo>> {
+   let agent_infos = cell.conductor.get_agent_infos(Some(cell.cell_id.clone())).await?;
+   let handle_list = cell.get_all_handles().unwrap_or_default();
+   let mut peers: Vec<PeerInfo> = agent_infos.iter()
+      .map(|info| {
+         let agent = AgentPubKey::from_kitsune(&info.agent);
+         PeerInfo {
+            handle: handle_list.iter().find(|item| item.agentId == agent).map(|item| item.name.clone()),
+            is_me: agent == *cell.agent_pubkey(),
+            storage_arc: format!("{:?}", info.storage_arc),
+            signed_at: local_time(info.signed_at_ms),
+            expires_at: local_time(info.expires_at_ms),
+            urls: info.url_list.iter().map(|url| url.as_str().to_string()).collect(),
+            agent,
+         }
+      })
+      .collect();
+   peers.sort_by_key(|peer| (!peer.is_me, peer.handle.clone()));
+   Ok(peers)
+}
+
+
+/// Host and port of a service URL.
+/// Proxy URLs carry them in their path ("kitsune-proxy://<id>/kitsune-quic/h/<host>/p/<port>/--").
+fn service_address(url: &Url2) -> Option<(String, u16)> {
+   if let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) {
+      return Some((host.to_string(), port));
+   }
+   let segments: Vec<&str> = url.path_segments()?.collect();
+   let host = segments.windows(2).find(|pair| pair[0] == "h").map(|pair| pair[1])?;
+   let port = segments.windows(2).find(|pair| pair[0] == "p").and_then(|pair| pair[1].parse().ok())?;
+   Some((host.to_string(), port))
+}
+
+/// Try to reach a service. QUIC services can only be resolved, as they do not accept TCP.
+async fn probe(service: &'static str, url: &Url2) -> ServiceCheck {
+   let url_str = url.to_string();
+   let check = |is_ok: bool, message: String| ServiceCheck { service, url: Some(url_str.clone()), is_ok, message };
+   let Some((host, port)) = service_address(url) else {
+      return check(false, "No host or port in URL".to_string());
+   };
+   if url_str.contains("kitsune-quic") {
+      return match tokio::net::lookup_host((host.as_str(), port)).await {
+         Ok(mut addrs) => match addrs.next() {
+            Some(addr) => check(true, format!("{} resolves to {} (QUIC, not probed)", host, addr)),
+            None => check(false, format!("{} does not resolve", host)),
+         },
+         Err(e) => check(false, format!("{} does not resolve: {}", host, e)),
+      };
+   }
+   let start = Instant::now();
+   match tokio::time::timeout(PROBE_TIMEOUT, tokio::net::TcpStream::connect((host.as_str(), port))).await {
+      Ok(Ok(_stream)) => check(true, format!("Reachable in {} ms", start.elapsed().as_millis())),
+      Ok(Err(e)) => check(false, format!("Connection to {}:{} failed: {}", host, port, e)),
+      Err(_) => check(false, format!("No answer from {}:{} after {:?}", host, port, PROBE_TIMEOUT)),
+   }
+}
+
+/// Check the bootstrap and proxy services configured for a session
+pub async fn check_services(sid: &str) -> anyhow::Result<Vec<ServiceCheck>> {
+   let config = read_config(session_path(sid))?
+      .ok_or_else(|| anyhow::anyhow!("No conductor config for session \"{}\"", sid))?;
+   let (maybe_proxy, maybe_bootstrap) = network_urls(&config);
+   let mut checks = Vec::new();
+   for (service, maybe_url) in [("bootstrap", maybe_bootstrap), ("proxy", maybe_proxy)] {
+      let check = match maybe_url {
+         Some(url) => probe(service, &url).await,
+         None => ServiceCheck { service, url: None, is_ok: true, message: "Not configured".to_string() },
+      };
+      checks.push(check);
+   }
+   Ok(checks)
+}
@@ -0,0 +1,179 @@
+//! Presence of contacts: which agents answer pings, and when they were last seen.
+//!
+//! A [`PresenceTracker`] pings all known contacts at a regular interval, a few at a time.
+//! Last-seen times are cached in the session folder so they survive restarts.
+
+use crate::{
+   api::SnapmailApi,
+   globals::*,
+   session::session_path,
+};
+use chrono::{DateTime, Local};
+use holochain_types::dna::*;
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+/// Default maximum number of pings in flight during a sweep
+pub const DEFAULT_MAX_CONCURRENT_PINGS: usize = 4;
+
+
+/// Presence of a contact
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Presence {
+   /// Outcome of the last ping. `None` if not pinged yet.
+   pub is_online: Option<bool>,
+   /// Last time the contact answered a ping
+   pub last_seen: Option<DateTime<Local>>,
+}
+
+impl Presence {
+   /// Short description for display: "online", "offline", "seen 5m ago"...
+   pub fn label(&self) -> String {
+      match (self.is_online, self.last_seen) {
+         (Some(true), _) => "online".to_string(),
+         (_, Some(last_seen)) => format!("seen {}", elapsed_str(Local::now() - last_seen)),
+         (Some(false), None) => "offline".to_string(),
+         (None, None) => "?".to_string(),
+      }
+   }
+}
+
+/// Compact elapsed time, ex: "5m ago"
+fn elapsed_str(elapsed: chrono::Duration) -> String {
+   if elapsed.num_days() > 0 {
+      format!("{}d ago", elapsed.num_days())
+   } else if elapsed.num_hours() > 0 {
+      format!("{}h ago", elapsed.num_hours())
+   } else {
+      format!("{}m ago", elapsed.num_minutes().max(1))
+   }
+}
+
+
+/// Last-seen times stored in the session folder, by agent key
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct PresenceCache {
+   last_seen: BTreeMap<String, DateTime<Local>>,
+}
+
+
+/// Pings contacts in the background and keeps their presence
+#[derive(Clone)]
+pub struct PresenceTracker {
+   api: Arc<dyn SnapmailApi>,
+   maybe_filepath: Option<PathBuf>,
+   max_concurrent: usize,
+   /// Presence by agent key string
+   entries: Arc<RwLock<HashMap<String, Presence>>>,
+}
+
+impl PresenceTracker {
+   /// Tracker whose last-seen times are cached in the session's folder
+   pub fn load(sid: &str, api: Arc<dyn SnapmailApi>) -> Self {
+      let filepath = session_path(sid).join(PRESENCE_FILENAME);
+      let cache = std::fs::read_to_string(&filepath).ok()
+         .and_then(|yaml| serde_yaml::from_str::<PresenceCache>(&yaml).ok())
+         .unwrap_or_default();
+      let entries = cache.last_seen.into_iter()
+         .map(|(agent, last_seen)| (agent, Presence { is_online: None, last_seen: Some(last_seen) }))
+         .collect();
+      Self {
+         api,
+         maybe_filepath: Some(filepath),
+         max_concurrent: DEFAULT_MAX_CONCURRENT_PINGS,
+         entries: Arc::new(RwLock::new(entries)),
+      }
+   }
+
+   /// Tracker without cache on disk
+   pub fn in_memory(api: Arc<dyn SnapmailApi>) -> Self {
+      Self {
+         api,
+         maybe_filepath: None,
+         max_concurrent: DEFAULT_MAX_CONCURRENT_PINGS,
+         entries: Arc::new(RwLock::new(HashMap::new())),
+      }
+   }
+
+   ///
+   pub fn with_max_concurrent(mut self, max_concurrent: usize) -> Self {
+      self.max_concurrent = max_concurrent.max(1);
+      self
+   }
+
+   /// Presence of an agent. Default if never pinged nor seen.
+   pub fn get(&self, agent: &AgentPubKey) -> Presence {
+      self.entries.read().unwrap()
+         .get(&agent.to_string())
+         .cloned()
+         .unwrap_or_default()
+   }
+
+   /// Ping these agents, at most `max_concurrent` at a time, then save last-seen times
+   pub async fn sweep(&self, agents: Vec<AgentPubKey>) {
+      let semaphore = Arc::new(Semaphore::new(self.max_concurrent));
+      let mut tasks = Vec::new();
+      for agent in agents {
+         let permit = semaphore.clone().acquire_owned().await.expect("Semaphore is never closed");
+         let api = self.api.clone();
+         let entries = self.entries.clone();
+         tasks.push(tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let is_online = api.ping_agent(agent.clone()).unwrap_or(false);
+            let mut entries = entries.write().unwrap();
+            let entry = entries.entry(agent.to_string()).or_default();
+            entry.is_online = Some(is_online);
+            if is_online {
+               entry.last_seen = Some(Local::now());
+            }
+         }));
+      }
+      for task in tasks {
+         let _ = task.await;
+      }
+      if let Err(e) = self.save() {
+         err_msg!("Failed to save presence cache: {}", e);
+      }
+   }
+
+   /// Ping every agent with a handle, except self
+   pub async fn sweep_contacts(&self) -> anyhow::Result<()> {
+      let me = self.api.my_agent_pubkey();
+      let agents = self.api.get_all_handles()?.into_iter()
+         .map(|item| item.agentId)
+         .filter(|agent| *agent != me)
+         .collect();
+      self.sweep(agents).await;
+      Ok(())
+   }
+
+   /// Sweep contacts every `interval`, until the returned task is aborted
+   pub fn spawn(&self, interval: Duration) -> JoinHandle<()> {
+      let tracker = self.clone();
+      tokio::spawn(async move {
+         loop {
+            if let Err(e) = tracker.sweep_contacts().await {
+               err_msg!("Presence sweep failed: {}", e);
+            }
+            tokio::time::sleep(interval).await;
+         }
+      })
+   }
+
+   ///
+   fn save(&self) -> anyhow::Result<()> {
+      let Some(filepath) = &self.maybe_filepath else { return Ok(()) };
+      let last_seen = self.entries.read().unwrap().iter()
+         .filter_map(|(agent, presence)| presence.last_seen.map(|last_seen| (agent.clone(), last_seen)))
+         .collect();
+      let yaml = serde_yaml::to_string(&PresenceCache { last_seen })?;
+      std::fs::write(filepath, yaml)?;
+      Ok(())
+   }
+}
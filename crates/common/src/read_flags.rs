@@ -0,0 +1,84 @@
+//! Local read/unread flags of mails.
+//!
+//! Reading a mail is local and says nothing to its sender: acknowledging it is a separate,
+//! explicit action (see [`AckPolicy`](crate::settings::AckPolicy)).
+//! Flags are stored in the session's read file. Sent mails are always read.
+
+use crate::{
+   globals::*,
+   session::session_path,
+};
+use holochain_types::dna::*;
+use serde::{Serialize, Deserialize};
+use snapmail::mail::*;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+
+/// Received mails read in a session
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ReadFlags {
+   /// Action hashes of read mails
+   pub read: BTreeSet<String>,
+}
+
+impl ReadFlags {
+   ///
+   pub fn filepath(sid: &str) -> PathBuf {
+      session_path(sid).join(READ_FLAGS_FILENAME)
+   }
+
+   /// Read flags of a session. Nothing read if there is no read file.
+   pub fn load(sid: &str) -> anyhow::Result<Self> {
+      let filepath = Self::filepath(sid);
+      match std::fs::read_to_string(&filepath) {
+         Ok(yaml) => Ok(serde_yaml::from_str::<ReadFlags>(&yaml)?),
+         Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ReadFlags::default()),
+         Err(e) => anyhow::bail!("Failed to read read flags file {:?}: {}", filepath, e),
+      }
+   }
+
+   ///
+   pub fn save(&self, sid: &str) -> anyhow::Result<()> {
+      let yaml = serde_yaml::to_string(self)?;
+      std::fs::write(Self::filepath(sid), yaml)?;
+      Ok(())
+   }
+
+   ///
+   pub fn is_read(&self, item: &MailItem) -> bool {
+      matches!(item.state, MailState::Out(_)) || self.read.contains(&item.address.to_string())
+   }
+
+   /// Returns false if the mail was already read
+   pub fn mark_read(&mut self, ah: &ActionHash) -> bool {
+      self.read.insert(ah.to_string())
+   }
+
+   /// Returns false if the mail was not read
+   pub fn mark_unread(&mut self, ah: &ActionHash) -> bool {
+      self.read.remove(&ah.to_string())
+   }
+}
+
+
+/// Flag a mail of a session as read. Returns false if it already was.
+pub fn mark_read(sid: &str, ah: &ActionHash) -> anyhow::Result<bool> {
+   let mut flags = ReadFlags::load(sid)?;
+   let changed = flags.mark_read(ah);
+   if changed {
+      flags.save(sid)?;
+   }
+   Ok(changed)
+}
+
+/// Flag a mail of a session as unread. Returns false if it already was.
+pub fn mark_unread(sid: &str, ah: &ActionHash) -> anyhow::Result<bool> {
+   let mut flags = ReadFlags::load(sid)?;
+   let changed = flags.mark_unread(ah);
+   if changed {
+      flags.save(sid)?;
+   }
+   Ok(changed)
+}
@@ -0,0 +1,355 @@
+//! Inbox rules: automatic actions on newly received mails.
+//!
+//! Rules are defined in the session's settings file and run, in order, on the mails brought in
+//! by `check_mail_inbox` or by a `ReceivedMail` signal. Mails from blocked senders are skipped.
+//!
+//! ```yaml
+//! rules:
+//!   - name: invoices
+//!     subject: "(?i)invoice"
+//!     attachment_type: pdf
+//!     actions:
+//!       - label: invoices
+//!       - download: /home/me/invoices
+//!       - acknowledge
+//! ```
+
+use crate::{
+   api::SnapmailApi,
+   attachment::*,
+   blocklist::*,
+   labels::MailLabels,
+   settings::SessionSettings,
+   trash::trash_mail,
+};
+use holochain_types::dna::*;
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use snapmail::mail::*;
+use snapmail::mail::entries::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+
+/// What a rule does to a matching mail
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+   /// Add a local label
+   Label(String),
+   /// Move to the trash
+   Trash,
+   /// Acknowledge, whatever the ack policy
+   Acknowledge,
+   /// Download all attachments to this folder, or to the download folder if unset
+   Download(Option<PathBuf>),
+   /// Send a copy, with its attachments, to the agent with this handle
+   Forward(String),
+   /// Run this shell command. The mail is described in `SNAPMAIL_*` environment variables.
+   Hook(String),
+}
+
+impl std::fmt::Display for RuleAction {
+   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      match self {
+         Self::Label(label) => write!(f, "label \"{}\"", label),
+         Self::Trash => write!(f, "trash"),
+         Self::Acknowledge => write!(f, "acknowledge"),
+         Self::Download(Some(folder)) => write!(f, "download to {:?}", folder),
+         Self::Download(None) => write!(f, "download"),
+         Self::Forward(handle) => write!(f, "forward to {}", handle),
+         Self::Hook(command) => write!(f, "hook \"{}\"", command),
+      }
+   }
+}
+
+
+/// Conditions, all of which a received mail must meet, and the actions to run on it.
+/// Unset conditions match any mail.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Rule {
+   pub name: String,
+   pub disabled: bool,
+   /// Handle pattern of the sender. `*` matches any characters. Case insensitive.
+   pub from: Option<String>,
+   /// Handle pattern of at least one To or Cc recipient
+   pub to: Option<String>,
+   /// Regular expression the subject must match
+   pub subject: Option<String>,
+   /// File type of at least one attachment, ex: "pdf"
+   pub attachment_type: Option<String>,
+   /// Size in bytes at least one attachment must reach. Combined with `attachment_type` if both are set.
+   pub attachment_min_size: Option<usize>,
+   /// Size in bytes at least one attachment must not exceed
+   pub attachment_max_size: Option<usize>,
+   pub actions: Vec<RuleAction>,
+   /// Do not run the following rules on a mail matching this one
+   pub stop: bool,
+}
+
+impl Rule {
+   /// Rule with its subject regex compiled, to run it on many mails
+   pub fn compile(&self) -> anyhow::Result<CompiledRule<'_>> {
+      let maybe_subject = match &self.subject {
+         None => None,
+         Some(subject) => Some(Regex::new(subject)
+            .map_err(|e| anyhow::anyhow!("Rule \"{}\" has an invalid subject regex: {}", self.name, e))?),
+      };
+      Ok(CompiledRule { rule: self, maybe_subject })
+   }
+
+   /// Each set condition, with whether the mail meets it
+   pub fn explain(&self, item: &MailItem, handle_map: &HashMap<AgentPubKey, String>) -> anyhow::Result<Vec<(String, bool)>> {
+      Ok(self.compile()?.explain(item, handle_map))
+   }
+
+   /// True if the rule is enabled and a received mail meets all its conditions
+   pub fn matches(&self, item: &MailItem, handle_map: &HashMap<AgentPubKey, String>) -> anyhow::Result<bool> {
+      if self.disabled {
+         return Ok(false);
+      }
+      Ok(self.compile()?.matches(item, handle_map))
+   }
+
+   ///
+   fn matches_attachment(&self, attachment: &AttachmentInfo) -> bool {
+      if let Some(filetype) = &self.attachment_type {
+         if !attachment.filetype.eq_ignore_ascii_case(filetype.trim_start_matches('.')) {
+            return false;
+         }
+      }
+      self.attachment_min_size.map(|min| attachment.orig_filesize >= min).unwrap_or(true)
+         && self.attachment_max_size.map(|max| attachment.orig_filesize <= max).unwrap_or(true)
+   }
+}
+
+/// A [`Rule`] ready to run
+pub struct CompiledRule<'a> {
+   pub rule: &'a Rule,
+   maybe_subject: Option<Regex>,
+}
+
+impl CompiledRule<'_> {
+   /// Each set condition, with whether the mail meets it
+   pub fn explain(&self, item: &MailItem, handle_map: &HashMap<AgentPubKey, String>) -> Vec<(String, bool)> {
+      let mut res = Vec::new();
+      if let Some(from) = &self.rule.from {
+         let sender = handle_map.get(&item.author).map(|handle| handle.as_str()).unwrap_or_default();
+         res.push((format!("from \"{}\"", from), matches_handle(from, sender)));
+      }
+      if let Some(to) = &self.rule.to {
+         let is_met = item.mail.to.iter().chain(item.mail.cc.iter())
+            .filter_map(|agent| handle_map.get(agent))
+            .any(|handle| matches_handle(to, handle));
+         res.push((format!("to \"{}\"", to), is_met));
+      }
+      if let Some(regex) = &self.maybe_subject {
+         res.push((format!("subject /{}/", regex.as_str()), regex.is_match(&item.mail.subject)));
+      }
+      if self.rule.attachment_type.is_some() || self.rule.attachment_min_size.is_some() || self.rule.attachment_max_size.is_some() {
+         let is_met = item.mail.attachments.iter().any(|attachment| self.rule.matches_attachment(attachment));
+         res.push(("attachment".to_string(), is_met));
+      }
+      res
+   }
+
+   /// True if the rule is enabled and a received mail meets all its conditions
+   pub fn matches(&self, item: &MailItem, handle_map: &HashMap<AgentPubKey, String>) -> bool {
+      if self.rule.disabled || !matches!(item.state, MailState::In(_)) {
+         return false;
+      }
+      self.explain(item, handle_map).iter().all(|(_, is_met)| *is_met)
+   }
+}
+
+/// Compile the enabled rules, in order. Fails on the first invalid one.
+pub fn compile_rules(rules: &[Rule]) -> anyhow::Result<Vec<CompiledRule<'_>>> {
+   rules.iter()
+      .filter(|rule| !rule.disabled)
+      .map(|rule| rule.compile())
+      .collect()
+}
+
+///
+fn matches_handle(pattern: &str, handle: &str) -> bool {
+   matches_pattern(&pattern.to_lowercase(), &handle.to_lowercase())
+}
+
+
+/// An action a rule ran, or would run, on a mail
+#[derive(Debug)]
+pub struct RuleOutcome {
+   pub mail: ActionHash,
+   pub rule: String,
+   pub action: RuleAction,
+   /// What was done, or why it failed. `None` on a dry run.
+   pub result: Option<anyhow::Result<String>>,
+}
+
+impl std::fmt::Display for RuleOutcome {
+   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      write!(f, "[{}] {}", self.rule, self.action)?;
+      match &self.result {
+         None => Ok(()),
+         Some(Ok(done)) => write!(f, ": {}", done),
+         Some(Err(e)) => write!(f, ": FAILED - {}", e),
+      }
+   }
+}
+
+
+/// Actions of the rules a mail matches, in order. Invalid rules are reported as errors.
+pub fn matching_actions(
+   rules: &[Rule],
+   item: &MailItem,
+   handle_map: &HashMap<AgentPubKey, String>,
+) -> anyhow::Result<Vec<(String, RuleAction)>> {
+   Ok(compiled_matching_actions(&compile_rules(rules)?, item, handle_map))
+}
+
+/// Actions of the compiled rules a mail matches, in order
+fn compiled_matching_actions(
+   rules: &[CompiledRule],
+   item: &MailItem,
+   handle_map: &HashMap<AgentPubKey, String>,
+) -> Vec<(String, RuleAction)> {
+   let mut res = Vec::new();
+   for compiled in rules.iter() {
+      if !compiled.matches(item, handle_map) {
+         continue;
+      }
+      res.extend(compiled.rule.actions.iter().map(|action| (compiled.rule.name.clone(), action.clone())));
+      if compiled.rule.stop {
+         break;
+      }
+   }
+   res
+}
+
+
+/// Run the session's rules on newly received mails
+pub fn apply_rules(sid: &str, api: &dyn SnapmailApi, new_mails: &[ActionHash]) -> anyhow::Result<Vec<RuleOutcome>> {
+   let settings = SessionSettings::load(sid)?;
+   if settings.rules.is_empty() || new_mails.is_empty() {
+      return Ok(Vec::new());
+   }
+   let handle_map: HashMap<AgentPubKey, String> = api.get_all_handles()?.into_iter()
+      .map(|item| (item.agentId, item.name))
+      .collect();
+   let classes = classify_network_mails(sid, api)?;
+   let rules = compile_rules(&settings.rules)?;
+   let mut outcomes = Vec::new();
+   for item in api.get_all_mails()?.iter().filter(|item| new_mails.contains(&item.address)) {
+      if classes.get(&item.address) == Some(&MailClass::Blocked) {
+         continue;
+      }
+      for (rule, action) in compiled_matching_actions(&rules, item, &handle_map) {
+         let result = run_action(sid, api, &settings, item, &handle_map, &action);
+         outcomes.push(RuleOutcome { mail: item.address.clone(), rule, action, result: Some(result) });
+      }
+   }
+   Ok(outcomes)
+}
+
+
+/// Run the session's rules on newly received mails and log what they did
+pub fn run_rules(sid: &str, api: &dyn SnapmailApi, new_mails: &[ActionHash]) -> Vec<RuleOutcome> {
+   match apply_rules(sid, api, new_mails) {
+      Ok(outcomes) => {
+         for outcome in outcomes.iter() {
+            msg!("Rule on mail {}: {}", outcome.mail, outcome);
+         }
+         outcomes
+      },
+      Err(e) => {
+         err_msg!("Inbox rules failed: {}", e);
+         Vec::new()
+      },
+   }
+}
+
+
+/// Run one action on a mail. Returns what was done.
+fn run_action(
+   sid: &str,
+   api: &dyn SnapmailApi,
+   settings: &SessionSettings,
+   item: &MailItem,
+   handle_map: &HashMap<AgentPubKey, String>,
+   action: &RuleAction,
+) -> anyhow::Result<String> {
+   let ah = &item.address;
+   match action {
+      RuleAction::Label(label) => {
+         let mut labels = MailLabels::load(sid)?;
+         labels.add(ah, label)?;
+         labels.save(sid)?;
+         Ok(format!("labelled \"{}\"", label))
+      },
+      RuleAction::Trash => {
+         trash_mail(sid, api, ah)?;
+         Ok("trashed".to_string())
+      },
+      RuleAction::Acknowledge => {
+         let eh = api.acknowledge_mail(ah.clone())?;
+         Ok(format!("acknowledged ({})", eh))
+      },
+      RuleAction::Download(maybe_folder) => {
+         let folder = maybe_folder.clone().unwrap_or_else(|| settings.download_folder());
+         std::fs::create_dir_all(&folder)?;
+         for attachment in item.mail.attachments.iter() {
+            get_attachment(api, attachment.manifest_eh.clone(), folder.clone())?;
+         }
+         Ok(format!("{} attachment(s) written in {:?}", item.mail.attachments.len(), folder))
+      },
+      RuleAction::Forward(handle) => {
+         let agent = api.find_agent(handle.clone())?.into_iter().next()
+            .ok_or_else(|| anyhow::anyhow!("No agent found for handle \"{}\"", handle))?;
+         /// Attachments are downloaded then written again for the new mail
+         let folder = std::env::temp_dir().join(format!("snapmail-forward-{}", ah));
+         std::fs::create_dir_all(&folder)?;
+         let copy_attachments = || -> anyhow::Result<Vec<ActionHash>> {
+            let mut manifest_address_list = Vec::new();
+            for attachment in item.mail.attachments.iter() {
+               let path = get_attachment(api, attachment.manifest_eh.clone(), folder.clone())?;
+               manifest_address_list.push(write_attachment(api, path)?);
+            }
+            Ok(manifest_address_list)
+         };
+         let res = copy_attachments();
+         /// Removed whether the copy succeeded or not
+         let _ = std::fs::remove_dir_all(&folder);
+         let manifest_address_list = res?;
+         let sender = handle_map.get(&item.author).cloned().unwrap_or_else(|| item.author.to_string());
+         let input = SendMailInput {
+            subject: format!("Fwd: {}", item.mail.subject),
+            payload: format!("---------- Forwarded mail from {} ----------\n{}", sender, item.mail.payload),
+            to: vec![agent],
+            cc: vec![],
+            bcc: vec![],
+            manifest_address_list,
+         };
+         let sent_ah = api.send_mail(input)?;
+         Ok(format!("forwarded to {} ({})", handle, sent_ah))
+      },
+      RuleAction::Hook(command) => {
+         let sender = handle_map.get(&item.author).cloned().unwrap_or_default();
+         let status = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("SNAPMAIL_SID", sid)
+            .env("SNAPMAIL_MAIL_HASH", ah.to_string())
+            .env("SNAPMAIL_FROM", sender)
+            .env("SNAPMAIL_FROM_AGENT", item.author.to_string())
+            .env("SNAPMAIL_SUBJECT", &item.mail.subject)
+            .env("SNAPMAIL_ATTACHMENT_COUNT", item.mail.attachments.len().to_string())
+            .status()?;
+         if !status.success() {
+            anyhow::bail!("hook exited with {}", status);
+         }
+         Ok("hook ran".to_string())
+      },
+   }
+}
@@ -0,0 +1,290 @@
+//! Sessions stored under [`config_path()`].
+//!
+//! A session is a folder holding a conductor config, databases, keystore and app files
+//! for one agent on one network.
+
+use crate::{
+   globals::*,
+   config::*,
+   conductor::*,
+   settings::SessionSettings,
+   lock::*,
+   api::SnapmailApi,
+   networks::*,
+   shutdown::graceful_shutdown,
+   holochain::try_conductor_from_config,
+};
+use chrono::{DateTime, Local};
+use holochain::conductor::ConductorHandle;
+use holochain_types::dna::*;
+use kitsune_p2p_types::config::KitsuneP2pConfig;
+use serde::Serialize;
+use snapmail::{
+   handle::*,
+   mail::entries::*,
+};
+use std::path::PathBuf;
+
+
+/// Mail statistics of a session
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct MailCounts {
+   pub total: usize,
+   pub inbox: usize,
+   pub unacknowledged: usize,
+   pub sent: usize,
+}
+
+/// Description of a session
+#[derive(Serialize, Debug, Clone)]
+pub struct SessionInfo {
+   pub sid: String,
+   pub path: PathBuf,
+   pub network_seed: Option<String>,
+   pub last_used: Option<DateTime<Local>>,
+   pub is_default: bool,
+   /// Fields below require starting the session's conductor
+   pub handle: Option<String>,
+   pub agent_pubkey: Option<String>,
+   pub dna_hash: Option<String>,
+   pub mail_counts: Option<MailCounts>,
+   /// Why the fields above are missing, if the conductor failed to start
+   pub error: Option<String>,
+}
+
+
+/// Path of a session's folder
+pub fn session_path(sid: &str) -> PathBuf {
+   config_path().join(sid)
+}
+
+/// Names of all sessions set up on this computer
+pub fn list_sids() -> anyhow::Result<Vec<String>> {
+   let mut sids = Vec::new();
+   let root = config_path();
+   if !root.is_dir() {
+      return Ok(sids);
+   }
+   for entry in std::fs::read_dir(root)? {
+      let entry = entry?;
+      if entry.path().join(CONDUCTOR_CONFIG_FILENAME).is_file() {
+         sids.push(entry.file_name().to_string_lossy().to_string());
+      }
+   }
+   sids.sort();
+   Ok(sids)
+}
+
+
+/// Record that a session has just been used
+pub fn touch_last_used(sid: &str) {
+   let filepath = session_path(sid).join(LAST_USED_FILENAME);
+   let _ = std::fs::write(filepath, Local::now().to_rfc3339());
+}
+
+///
+fn read_last_used(sid: &str) -> Option<DateTime<Local>> {
+   let filepath = session_path(sid).join(LAST_USED_FILENAME);
+   let s = std::fs::read_to_string(filepath).ok()?;
+   DateTime::parse_from_rfc3339(s.trim()).ok().map(|date| date.with_timezone(&Local))
+}
+
+
+/// Session to use when none is given: `SNAPMAIL_SESSION` or the one set with [`set_default_sid`]
+pub fn default_sid() -> Option<String> {
+   if let Ok(sid) = std::env::var(SESSION_ENV_VAR) {
+      if !sid.is_empty() {
+         return Some(sid);
+      }
+   }
+   let filepath = config_path().join(DEFAULT_SESSION_FILENAME);
+   std::fs::read_to_string(filepath)
+      .ok()
+      .map(|s| s.trim().to_string())
+      .filter(|s| !s.is_empty())
+}
+
+///
+pub fn set_default_sid(sid: &str) -> anyhow::Result<()> {
+   if !session_path(sid).is_dir() {
+      anyhow::bail!("Session \"{}\" not found", sid);
+   }
+   std::fs::create_dir_all(config_path())?;
+   std::fs::write(config_path().join(DEFAULT_SESSION_FILENAME), sid.as_bytes())?;
+   Ok(())
+}
+
+/// Session ID from the command line or the default session
+pub fn resolve_sid(maybe_sid: Option<String>) -> anyhow::Result<String> {
+   maybe_sid
+      .or_else(default_sid)
+      .ok_or_else(|| anyhow::anyhow!(
+         "No session given. Pass a session ID, set {} or run \"snapmail-cli sessions default <sid>\"",
+         SESSION_ENV_VAR,
+      ))
+}
+
+
+/// Gather info about a session. Read only: the session's last used time is not changed.
+/// If `with_conductor` is set, the session's conductor is started to get agent and mail data.
+/// A conductor failing to start is reported in the info's `error`.
+pub async fn session_info(sid: &str, with_conductor: bool) -> SessionInfo {
+   let path = session_path(sid);
+   let network_seed = std::fs::read_to_string(path.join(APP_CONFIG_FILENAME)).ok();
+   let mut info = SessionInfo {
+      sid: sid.to_string(),
+      path,
+      network_seed,
+      last_used: read_last_used(sid),
+      is_default: default_sid().as_deref() == Some(sid),
+      handle: None,
+      agent_pubkey: None,
+      dna_hash: None,
+      mail_counts: None,
+      error: None,
+   };
+   if with_conductor {
+      if let Err(e) = fill_conductor_info(&mut info).await {
+         info.error = Some(e.to_string());
+      }
+   }
+   info
+}
+
+/// Start the session's conductor, without touching its last used time, and fill the fields needing it
+async fn fill_conductor_info(info: &mut SessionInfo) -> anyhow::Result<()> {
+   let config = read_config(info.path.clone())?
+      .ok_or_else(|| anyhow::anyhow!("No conductor config found in {:?}", info.path))?;
+   acquire_session_lock(&info.sid)?;
+   let conductor = match try_conductor_from_config(config).await {
+      Ok(conductor) => conductor,
+      Err(e) => {
+         release_session_lock(&info.sid);
+         return Err(e);
+      },
+   };
+   let res = fill_cell_info(info, conductor.clone());
+   conductor.shutdown();
+   release_session_lock(&info.sid);
+   res
+}
+
+///
+fn fill_cell_info(info: &mut SessionInfo, conductor: ConductorHandle) -> anyhow::Result<()> {
+   let cell = network_cell(conductor, &info.sid, None)?;
+   info.agent_pubkey = Some(cell.agent_pubkey().to_string());
+   info.dna_hash = Some(cell.dna_hash().to_string());
+   info.handle = cell.get_my_handle().ok();
+   let all_mail_list = cell.get_all_mails()?;
+   let mut counts = MailCounts { total: all_mail_list.len(), ..Default::default() };
+   for item in all_mail_list.iter() {
+      match &item.state {
+         MailState::In(InMailState::Deleted) | MailState::Out(OutMailState::Deleted) => {},
+         MailState::In(state) => {
+            counts.inbox += 1;
+            if state == &InMailState::Unacknowledged {
+               counts.unacknowledged += 1;
+            }
+         },
+         MailState::Out(_) => counts.sent += 1,
+      }
+   }
+   info.mail_counts = Some(counts);
+   Ok(())
+}
+
+
+/// Create a new session: conductor config, databases, keystore, installed app and handle.
+/// Its conductor is shut down once the handle is published, and the session lock released.
+pub async fn create_session(
+   sid: &str,
+   network_seed: String,
+   maybe_network: Option<KitsuneP2pConfig>,
+   maybe_dna_path: Option<PathBuf>,
+) -> anyhow::Result<DnaHash> {
+   let (conductor, dna_hash) = setup_session(sid, network_seed, maybe_network, maybe_dna_path).await?;
+   graceful_shutdown(conductor, sid, SessionSettings::default().timeouts.shutdown_max_wait()).await;
+   release_session_lock(sid);
+   Ok(dna_hash)
+}
+
+/// [`create_session`] that also returns the session's running conductor
+pub async fn setup_session(
+   sid: &str,
+   network_seed: String,
+   maybe_network: Option<KitsuneP2pConfig>,
+   maybe_dna_path: Option<PathBuf>,
+) -> anyhow::Result<(ConductorHandle, DnaHash)> {
+   if session_path(sid).exists() {
+      anyhow::bail!("Session \"{}\" already exists", sid);
+   }
+   let root = config_path();
+   let _ = generate(root, Some(PathBuf::from(sid)), maybe_network)?;
+   SessionSettings::default().save(sid)?;
+
+   let (conductor, dna_hash) = install_app(sid.to_string(), network_seed, maybe_dna_path).await?;
+   msg!("    Using DNA: {}", dna_hash);
+   touch_last_used(sid);
+   let handle_res = network_cell(conductor.clone(), sid, None)
+      .and_then(|cell| Ok(cell.set_handle(sid.to_string())?));
+   match handle_res {
+      Ok(hash) => msg!(" handle set: {} - {:?}", sid, hash),
+      Err(e) => {
+         conductor.shutdown();
+         release_session_lock(sid);
+         return Err(e);
+      },
+   }
+   Ok((conductor, dna_hash))
+}
+
+
+/// Rename a session's folder and update the paths stored in its conductor config
+pub fn rename_session(sid: &str, new_sid: &str) -> anyhow::Result<()> {
+   let old_path = session_path(sid);
+   let new_path = session_path(new_sid);
+   if !old_path.is_dir() {
+      anyhow::bail!("Session \"{}\" not found", sid);
+   }
+   if new_path.exists() {
+      anyhow::bail!("Session \"{}\" already exists", new_sid);
+   }
+   if let Some(holder) = session_lock_holder(sid) {
+      anyhow::bail!("Session \"{}\" is in use by {}", sid, holder);
+   }
+   std::fs::rename(&old_path, &new_path)?;
+   /// Conductor config stores absolute paths
+   let mut config = read_config(new_path.clone())?
+      .ok_or_else(|| anyhow::anyhow!("No conductor config found in {:?}", new_path))?;
+   let fresh_config = create_config(new_path.clone());
+   config.environment_path = fresh_config.environment_path;
+   config.keystore = fresh_config.keystore;
+   write_config(new_path, &config);
+   /// Keep default session
+   if default_sid().as_deref() == Some(sid) {
+      set_default_sid(new_sid)?;
+   }
+   Ok(())
+}
+
+
+/// Create a new session with a new agent on the same network as an existing session.
+/// The new session uses the embedded DNA, so cloning a session set up from another DNA
+/// (ex: a `.dna` file) is refused.
+pub async fn clone_session(sid: &str, new_sid: &str) -> anyhow::Result<DnaHash> {
+   let path = session_path(sid);
+   let network_seed = std::fs::read_to_string(path.join(APP_CONFIG_FILENAME))
+      .map_err(|e| anyhow::anyhow!("Failed to read network seed of session \"{}\": {}", sid, e))?;
+   let config = read_config(path.clone())?
+      .ok_or_else(|| anyhow::anyhow!("No conductor config found in {:?}", path))?;
+   let embedded_hash = embedded_dna_hash(network_seed.clone()).await;
+   match SessionNetworks::load(sid)?.networks[0].dna_hash.as_ref() {
+      Some(dna_hash) if *dna_hash != embedded_hash.to_string() => anyhow::bail!(
+         "Session \"{}\" does not use the embedded DNA ({} instead of {}). Set up the new session from the same DNA file.",
+         sid, dna_hash, embedded_hash,
+      ),
+      Some(_) => {},
+      None => msg!("DNA of session \"{}\" is unknown. Using the embedded DNA.", sid),
+   }
+   create_session(new_sid, network_seed, config.network, None).await
+}
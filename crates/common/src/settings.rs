@@ -0,0 +1,341 @@
+//! Typed and versioned per-session user settings.
+//!
+//! Stored as YAML in [`SETTINGS_FILENAME`] inside the session's config folder
+//! and shared by the CLI and the TUI.
+
+use crate::{
+   globals::*,
+   rules::Rule,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Current version of the settings file format
+pub const SETTINGS_VERSION: u32 = 1;
+
+
+/// When should received mails be acknowledged
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AckPolicy {
+   /// Acknowledge as soon as a mail is opened
+   Always,
+   /// Only acknowledge on explicit request
+   Manual,
+   /// Acknowledge when opened if the sender is in `ack_contacts`, otherwise on explicit request
+   #[serde(rename = "per_contact")]
+   PerContact,
+}
+
+impl FromStr for AckPolicy {
+   type Err = String;
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      match s.to_lowercase().as_str() {
+         "always" => Ok(AckPolicy::Always),
+         "manual" => Ok(AckPolicy::Manual),
+         "per_contact" | "per-contact" | "percontact" => Ok(AckPolicy::PerContact),
+         _ => Err(format!("Unknown ack policy \"{}\"", s)),
+      }
+   }
+}
+
+
+/// What to do with mails from blocked senders
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockAction {
+   /// Keep them, only shown in the "All" folder
+   Hide,
+   /// Delete them in the zome. Can not be undone.
+   Delete,
+}
+
+
+/// Flags unknown senders that send too many mails as spammers
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SpamFilter {
+   /// Number of mails from an unknown sender within the window above which it is a spammer. 0 disables the filter.
+   pub max_unknown_mails: usize,
+   /// Length in minutes of the window
+   pub window_mins: u64,
+}
+
+impl Default for SpamFilter {
+   fn default() -> Self {
+      Self {
+         max_unknown_mails: 5,
+         window_mins: 60,
+      }
+   }
+}
+
+impl SpamFilter {
+   /// Dates (in seconds) of a flood: those sharing a window with more than `max_unknown_mails` of these dates
+   pub fn flood_dates(&self, dates: &[i64]) -> HashSet<i64> {
+      let mut flooded = HashSet::new();
+      if self.max_unknown_mails == 0 || dates.len() <= self.max_unknown_mails {
+         return flooded;
+      }
+      let mut dates = dates.to_vec();
+      dates.sort_unstable();
+      let window = (self.window_mins * 60) as i64;
+      for run in dates.windows(self.max_unknown_mails + 1) {
+         if run[run.len() - 1] - run[0] <= window {
+            flooded.extend(run.iter().copied());
+         }
+      }
+      flooded
+   }
+}
+
+
+/// Download of the attachments of every received mail
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AutoDownload {
+   pub enabled: bool,
+   /// Folder of a mail's attachments, in the download folder. `{sender}` and `{date}` are replaced.
+   pub folder_template: String,
+   /// Attachments bigger than this, in bytes, are skipped. None are if unset.
+   pub max_size: Option<usize>,
+}
+
+impl Default for AutoDownload {
+   fn default() -> Self {
+      Self {
+         enabled: false,
+         folder_template: "{sender}/{date}".to_string(),
+         max_size: None,
+      }
+   }
+}
+
+
+/// TUI color theme
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+   Dark,
+   Light,
+}
+
+
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Timeouts {
+   /// Interval in seconds between each status print of `listen`
+   pub listen_tick_secs: u64,
+   /// Maximum time in seconds to wait at shutdown for the publishes, mails and acks of the current run
+   #[serde(alias = "post_commit_wait_secs")]
+   pub shutdown_max_wait_secs: u64,
+   /// Interval in seconds between each ping of all contacts, for their presence
+   pub presence_sweep_secs: u64,
+}
+
+impl Default for Timeouts {
+   fn default() -> Self {
+      Self {
+         listen_tick_secs: 10,
+         shutdown_max_wait_secs: 30,
+         presence_sweep_secs: 60,
+      }
+   }
+}
+
+impl Timeouts {
+   ///
+   pub fn shutdown_max_wait(&self) -> Duration {
+      Duration::from_secs(self.shutdown_max_wait_secs)
+   }
+
+   ///
+   pub fn presence_sweep(&self) -> Duration {
+      Duration::from_secs(self.presence_sweep_secs)
+   }
+}
+
+
+/// User settings of a session
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SessionSettings {
+   pub version: u32,
+   /// Where attachments are downloaded. Defaults to current directory.
+   pub download_folder: Option<PathBuf>,
+   /// Handles to send to when no recipient is given
+   pub default_recipients: Vec<String>,
+   /// Text appended to every mail sent
+   pub signature: String,
+   pub ack_policy: AckPolicy,
+   /// Handles whose mails are acknowledged when opened, with the "per_contact" ack policy
+   pub ack_contacts: Vec<String>,
+   pub theme: Theme,
+   /// Log output format of the CLI ("pretty" or "json")
+   pub log_format: String,
+   /// TUI key overrides: action name -> key
+   pub keybindings: BTreeMap<String, char>,
+   pub timeouts: Timeouts,
+   /// Days after which trashed mails are deleted for good. Kept forever if unset.
+   pub trash_retention_days: Option<u32>,
+   pub block_action: BlockAction,
+   pub spam_filter: SpamFilter,
+   /// Inbox rules, run in order on each newly received mail
+   pub rules: Vec<Rule>,
+   pub auto_download: AutoDownload,
+}
+
+impl Default for SessionSettings {
+   fn default() -> Self {
+      Self {
+         version: SETTINGS_VERSION,
+         download_folder: None,
+         default_recipients: Vec::new(),
+         signature: String::new(),
+         ack_policy: AckPolicy::Always,
+         ack_contacts: Vec::new(),
+         theme: Theme::Dark,
+         log_format: "pretty".to_string(),
+         keybindings: BTreeMap::new(),
+         timeouts: Timeouts::default(),
+         trash_retention_days: None,
+         block_action: BlockAction::Hide,
+         spam_filter: SpamFilter::default(),
+         rules: Vec::new(),
+         auto_download: AutoDownload::default(),
+      }
+   }
+}
+
+
+impl SessionSettings {
+   /// Path of the settings file of a session
+   pub fn filepath(sid: &str) -> PathBuf {
+      config_path().join(sid).join(SETTINGS_FILENAME)
+   }
+
+   /// Load settings of a session. Returns default settings if there is no settings file.
+   pub fn load(sid: &str) -> anyhow::Result<Self> {
+      let filepath = Self::filepath(sid);
+      let mut settings = match std::fs::read_to_string(&filepath) {
+         Ok(yaml) => serde_yaml::from_str::<SessionSettings>(&yaml)?,
+         Err(e) if e.kind() == std::io::ErrorKind::NotFound => SessionSettings::default(),
+         Err(e) => anyhow::bail!("Failed to read settings file {:?}: {}", filepath, e),
+      };
+      if settings.version > SETTINGS_VERSION {
+         anyhow::bail!(
+            "Settings file {:?} has version {} but this app only supports up to version {}",
+            filepath, settings.version, SETTINGS_VERSION,
+         );
+      }
+      /// Import legacy download folder file
+      if settings.download_folder.is_none() {
+         let dl_filepath = config_path().join(sid).join(APP_DL_CONFIG_FILENAME);
+         if let Ok(s) = std::fs::read_to_string(dl_filepath) {
+            settings.download_folder = Some(PathBuf::from(s.trim()));
+         }
+      }
+      settings.version = SETTINGS_VERSION;
+      Ok(settings)
+   }
+
+   /// Write settings to the session's settings file
+   pub fn save(&self, sid: &str) -> anyhow::Result<()> {
+      let yaml = serde_yaml::to_string(self)?;
+      std::fs::write(Self::filepath(sid), yaml)?;
+      Ok(())
+   }
+
+   /// Folder where attachments should be downloaded
+   pub fn download_folder(&self) -> PathBuf {
+      self.download_folder.clone()
+         .unwrap_or_else(|| std::env::current_dir().unwrap_or_default())
+   }
+
+   /// True if opening a mail from this sender should acknowledge it
+   pub fn acks_on_open(&self, maybe_sender: Option<&str>) -> bool {
+      match self.ack_policy {
+         AckPolicy::Always => true,
+         AckPolicy::Manual => false,
+         AckPolicy::PerContact => maybe_sender
+            .map(|sender| self.ack_contacts.iter().any(|handle| handle == sender))
+            .unwrap_or(false),
+      }
+   }
+
+   /// List all settings as (dotted key, value) pairs
+   pub fn list(&self) -> anyhow::Result<Vec<(String, String)>> {
+      let value = serde_yaml::to_value(self)?;
+      let mut res = Vec::new();
+      flatten_value(String::new(), &value, &mut res);
+      Ok(res)
+   }
+
+   /// Get a setting's value from its dotted key, ex: "timeouts.listen_tick_secs"
+   pub fn get(&self, key: &str) -> anyhow::Result<String> {
+      let value = serde_yaml::to_value(self)?;
+      let mut current = &value;
+      for part in key.split('.') {
+         current = current.get(part)
+            .ok_or_else(|| anyhow::anyhow!("Unknown setting \"{}\"", key))?;
+      }
+      Ok(value_to_string(current))
+   }
+
+   /// Set a setting from its dotted key. The value is parsed as YAML and type checked.
+   pub fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+      if key == "version" {
+         anyhow::bail!("\"version\" is not editable");
+      }
+      let mut root = serde_yaml::to_value(&*self)?;
+      let new_value: serde_yaml::Value = serde_yaml::from_str(value)?;
+      let mut current = &mut root;
+      let parts: Vec<&str> = key.split('.').collect();
+      for (i, part) in parts.iter().enumerate() {
+         let is_last = i == parts.len() - 1;
+         let mapping = current.as_mapping_mut()
+            .ok_or_else(|| anyhow::anyhow!("Unknown setting \"{}\"", key))?;
+         let part_key = serde_yaml::Value::String(part.to_string());
+         /// keybindings accept new keys
+         if !mapping.contains_key(&part_key) && !(is_last && i > 0 && parts[i - 1] == "keybindings") {
+            anyhow::bail!("Unknown setting \"{}\"", key);
+         }
+         if is_last {
+            mapping.insert(part_key, new_value.clone());
+            break;
+         }
+         current = mapping.get_mut(&part_key).unwrap();
+      }
+      *self = serde_yaml::from_value(root)
+         .map_err(|e| anyhow::anyhow!("Invalid value for \"{}\": {}", key, e))?;
+      Ok(())
+   }
+}
+
+
+///
+fn value_to_string(value: &serde_yaml::Value) -> String {
+   match value {
+      serde_yaml::Value::Null => String::new(),
+      serde_yaml::Value::String(s) => s.clone(),
+      _ => serde_yaml::to_string(value).unwrap_or_default().trim().to_string(),
+   }
+}
+
+///
+fn flatten_value(prefix: String, value: &serde_yaml::Value, res: &mut Vec<(String, String)>) {
+   match value {
+      serde_yaml::Value::Mapping(mapping) if !mapping.is_empty() => {
+         for (key, sub_value) in mapping.iter() {
+            let key = value_to_string(key);
+            let full_key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+            flatten_value(full_key, sub_value, res);
+         }
+      },
+      _ => res.push((prefix, value_to_string(value))),
+   }
+}
@@ -0,0 +1,189 @@
+//! Graceful conductor shutdown.
+//!
+//! Work started by a zome call goes on in the background after the call returns:
+//! DHT ops wait to be published, and the zome's post-commit callbacks hand mails and acks off
+//! to their recipients or to the DHT.
+//! Stopping the conductor before that work is done loses it until the next start.
+//! [`graceful_shutdown`] waits for it to drain, up to a maximum wait, then stops the conductor.
+//!
+//! Mails to offline recipients can stay unsent for days, so only work created or changed since
+//! the conductor started is waited for: [`snapshot_pending_work`] records what was already pending.
+
+use crate::{
+   api::SnapmailApi,
+   cell::SnapmailCell,
+   networks::all_network_cells,
+};
+use holochain::conductor::ConductorHandle;
+use holochain_state::prelude::DatabaseResult;
+use holochain_types::dna::*;
+use serde::Serialize;
+use snapmail::mail::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Delay between two checks of pending work
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Ops of the agent's source chain that were never published.
+/// Same filter as the conductor's publish workflow: private entries are never published.
+const UNPUBLISHED_OPS_SQL: &str = "
+   SELECT COUNT(DhtOp.hash) FROM DhtOp
+   JOIN Action ON DhtOp.action_hash = Action.hash
+   WHERE DhtOp.withhold_publish IS NULL
+   AND DhtOp.last_publish_time IS NULL
+   AND DhtOp.receipts_complete IS NULL
+   AND (DhtOp.type != 'StoreEntry' OR Action.private_entry = 0)
+";
+
+lazy_static! {
+   /// Session ID -> work already pending when its conductor started
+   static ref STARTUP_PENDING: Mutex<HashMap<String, PendingWork>> = Mutex::new(HashMap::new());
+}
+
+
+/// Background work a conductor still has to do
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct PendingWork {
+   /// Authored DHT ops not published yet
+   pub unpublished_ops: usize,
+   /// Sent mails not handed off yet
+   pub unsent_outmails: Vec<ActionHash>,
+   /// Received mails acknowledged but whose ack is not sent yet
+   pub unsent_acks: Vec<ActionHash>,
+}
+
+impl PendingWork {
+   ///
+   pub fn is_empty(&self) -> bool {
+      self.unpublished_ops == 0 && self.unsent_outmails.is_empty() && self.unsent_acks.is_empty()
+   }
+
+   /// Work not already pending in `baseline`.
+   /// Ops can't be told apart, so only the ones above the baseline's count are kept.
+   pub fn since(&self, baseline: &PendingWork) -> PendingWork {
+      PendingWork {
+         unpublished_ops: self.unpublished_ops.saturating_sub(baseline.unpublished_ops),
+         unsent_outmails: self.unsent_outmails.iter()
+            .filter(|ah| !baseline.unsent_outmails.contains(ah))
+            .cloned()
+            .collect(),
+         unsent_acks: self.unsent_acks.iter()
+            .filter(|ah| !baseline.unsent_acks.contains(ah))
+            .cloned()
+            .collect(),
+      }
+   }
+
+   ///
+   fn add(&mut self, other: PendingWork) {
+      self.unpublished_ops += other.unpublished_ops;
+      self.unsent_outmails.extend(other.unsent_outmails);
+      self.unsent_acks.extend(other.unsent_acks);
+   }
+}
+
+impl std::fmt::Display for PendingWork {
+   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      write!(f, "{} unpublished op(s), {} unsent mail(s), {} unsent ack(s)",
+         self.unpublished_ops, self.unsent_outmails.len(), self.unsent_acks.len())
+   }
+}
+
+
+/// Outcome of [`graceful_shutdown`]
+#[derive(Serialize, Debug, Clone)]
+pub struct ShutdownReport {
+   pub elapsed: Duration,
+   /// True if the conductor was stopped with work still pending
+   pub timed_out: bool,
+   /// Work still pending when the conductor was stopped
+   pub pending: PendingWork,
+}
+
+
+/// Mails and acks not handed off yet
+pub fn pending_mails(api: &dyn SnapmailApi) -> anyhow::Result<PendingWork> {
+   let mut pending = PendingWork::default();
+   for item in api.get_all_mails()? {
+      match item.state {
+         MailState::Out(OutMailState::Unsent) => pending.unsent_outmails.push(item.address),
+         MailState::In(InMailState::AckUnsent) => pending.unsent_acks.push(item.address),
+         _ => {},
+      }
+   }
+   Ok(pending)
+}
+
+/// Background work pending on a cell
+pub async fn pending_work(cell: &SnapmailCell) -> anyhow::Result<PendingWork> {
+   let mut pending = pending_mails(cell)?;
+   pending.unpublished_ops = unpublished_op_count(cell).await?;
+   Ok(pending)
+}
+
+///
+async fn pending_work_of_cells(cells: &[SnapmailCell]) -> PendingWork {
+   let mut pending = PendingWork::default();
+   for cell in cells.iter() {
+      match pending_work(cell).await {
+         Ok(cell_pending) => pending.add(cell_pending),
+         Err(e) => err_msg!("Failed to check pending work of network \"{}\": {}", cell.alias, e),
+      }
+   }
+   pending
+}
+
+///
+async fn unpublished_op_count(cell: &SnapmailCell) -> anyhow::Result<usize> {
+   let db = cell.conductor.get_or_create_authored_db(cell.dna_hash(), cell.agent_pubkey().clone())?;
+   let count = db.read_async(|txn| -> DatabaseResult<usize> {
+      Ok(txn.query_row(UNPUBLISHED_OPS_SQL, [], |row| row.get(0))?)
+   }).await?;
+   Ok(count)
+}
+
+
+/// Record the work pending when a session's conductor starts. Call it right after starting the conductor.
+pub async fn snapshot_pending_work(conductor: ConductorHandle, sid: &str) {
+   let cells = all_network_cells(conductor, sid).unwrap_or_default();
+   let pending = pending_work_of_cells(&cells).await;
+   STARTUP_PENDING.lock().unwrap().insert(sid.to_string(), pending);
+}
+
+
+
+/// Wait until no cell has pending work other than `baseline`'s, or until `max_wait` is over
+pub async fn wait_for_pending_work(cells: &[SnapmailCell], baseline: &PendingWork, max_wait: Duration) -> ShutdownReport {
+   let start = Instant::now();
+   loop {
+      let pending = pending_work_of_cells(cells).await.since(baseline);
+      let timed_out = start.elapsed() >= max_wait;
+      if pending.is_empty() || timed_out {
+         return ShutdownReport { elapsed: start.elapsed(), timed_out: !pending.is_empty(), pending };
+      }
+      tokio::time::sleep(POLL_INTERVAL).await;
+   }
+}
+
+/// Stop a session's conductor once the work its networks started since the conductor started is done,
+/// or after `max_wait` at most.
+pub async fn graceful_shutdown(conductor: ConductorHandle, sid: &str, max_wait: Duration) -> ShutdownReport {
+   let cells = all_network_cells(conductor.clone(), sid).unwrap_or_default();
+   let baseline = STARTUP_PENDING.lock().unwrap().remove(sid).unwrap_or_default();
+   let report = wait_for_pending_work(&cells, &baseline, max_wait).await;
+   if report.timed_out {
+      err_msg!("Shutting down after {:.1}s with work still pending: {}", report.elapsed.as_secs_f32(), report.pending);
+      for ah in report.pending.unsent_outmails.iter() {
+         err_msg!(" - unsent mail: {}", ah);
+      }
+      for ah in report.pending.unsent_acks.iter() {
+         err_msg!(" - unsent ack for mail: {}", ah);
+      }
+   } else {
+      msg!("Pending work done in {:.1}s", report.elapsed.as_secs_f32());
+   }
+   conductor.shutdown();
+   report
+}
@@ -0,0 +1,235 @@
+//! In-process multi-agent harness for integration tests.
+//!
+//! Spins up one conductor per agent, each in its own session of a temporary sessions root,
+//! all on a local-only network (mDNS discovery, no bootstrap or proxy service).
+//! Each agent installs the embedded DNA on a fresh network seed and publishes its name as handle.
+//!
+//! Tests that only need session files, ex: on the [`mock`](crate::mock) API,
+//! get a temporary sessions root with [`sessions_root`].
+//!
+//! The sessions root is process-wide (see [`set_config_root`]),
+//! so harnesses are serialized: a [`TestNetwork`] or a [`sessions_root`] guard holds a global lock until dropped.
+//! Requires the `test-support` feature.
+
+use crate::{
+   globals::*,
+   api::SnapmailApi,
+   cell::SnapmailCell,
+   lock::release_session_lock,
+   networks::{network_cell, SessionNetworks},
+   session::setup_session,
+};
+use holochain::conductor::ConductorHandle;
+use holochain_p2p::kitsune_p2p::{KitsuneP2pConfig, TransportConfig, NetworkType};
+use holochain_types::app::DisabledAppReason;
+use holochain_types::dna::*;
+use holochain_types::signal::Signal;
+use snapmail::{
+   mail::*,
+   mail::entries::*,
+   signal_protocol::SignalProtocol,
+};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio_stream::StreamExt;
+
+/// Default time allowed for agents to see each other or for a mail to be delivered
+pub const DEFAULT_WAIT: Duration = Duration::from_secs(60);
+/// Delay between two checks of a condition
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+lazy_static! {
+   static ref HARNESS_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
+
+
+/// An agent of a [`TestNetwork`]
+pub struct TestAgent {
+   /// Session ID, also used as handle
+   pub name: String,
+   pub conductor: ConductorHandle,
+   pub cell: SnapmailCell,
+   signals: Arc<Mutex<Vec<SignalProtocol>>>,
+}
+
+impl TestAgent {
+   /// Snapmail signals received by this agent so far
+   pub fn signals(&self) -> MutexGuard<'_, Vec<SignalProtocol>> {
+      self.signals.lock().unwrap()
+   }
+
+   /// Wait until this agent received a signal matching `predicate`
+   pub async fn wait_for_signal<F>(&self, timeout: Duration, predicate: F) -> anyhow::Result<()>
+      where F: Fn(&SignalProtocol) -> bool
+   {
+      let name = self.name.clone();
+      wait_until(timeout, || self.signals().iter().any(|signal| predicate(signal)))
+         .await
+         .map_err(|_| anyhow::anyhow!("{} did not receive the expected signal", name))
+   }
+
+   /// Take this agent off the network by disabling its app. Mails and acks to it stay pending.
+   pub async fn go_offline(&self) -> anyhow::Result<()> {
+      self.conductor.clone().disable_app(self.app_id()?, DisabledAppReason::User).await?;
+      Ok(())
+   }
+
+   /// Bring this agent back on the network
+   pub async fn go_online(&self) -> anyhow::Result<()> {
+      self.conductor.clone().enable_app(self.app_id()?).await?;
+      Ok(())
+   }
+
+   /// App of the agent's default network
+   fn app_id(&self) -> anyhow::Result<String> {
+      Ok(SessionNetworks::load(&self.name)?.networks[0].app_id.clone())
+   }
+
+   /// Collect the snapmail signals of this agent's cell
+   fn listen(&self) {
+      let conductor = self.conductor.clone();
+      let cell_id = self.cell.cell_id.clone();
+      let signals = self.signals.clone();
+      tokio::spawn(async move {
+         let stream = conductor.signal_broadcaster().await.subscribe_merged();
+         tokio::pin!(stream);
+         while let Some(signal) = stream.next().await {
+            if let Signal::App(signal_cell_id, app_signal) = signal {
+               if signal_cell_id != cell_id {
+                  continue;
+               }
+               if let Ok(snapmail_signal) = app_signal.into_inner().decode::<SignalProtocol>() {
+                  signals.lock().unwrap().push(snapmail_signal);
+               }
+            }
+         }
+      });
+   }
+}
+
+
+/// Agents sharing a local-only snapmail network
+pub struct TestNetwork {
+   pub agents: Vec<TestAgent>,
+   pub network_seed: String,
+   root: TempDir,
+   _guard: tokio::sync::MutexGuard<'static, ()>,
+}
+
+impl TestNetwork {
+   /// Set up one session per name on a new network and wait until every agent sees all handles
+   pub async fn new(names: &[&str]) -> anyhow::Result<Self> {
+      let guard = HARNESS_LOCK.lock().await;
+      let root = tempfile::Builder::new().prefix("snapmail-test-").tempdir()?;
+      set_config_root(root.path().to_path_buf());
+      let network_seed = format!("test-{}", root.path().file_name().unwrap().to_string_lossy());
+      let mut agents = Vec::new();
+      for name in names {
+         let (conductor, _dna_hash) = setup_session(name, network_seed.clone(), Some(local_network()), None).await?;
+         let cell = network_cell(conductor.clone(), name, None)?;
+         let agent = TestAgent {
+            name: name.to_string(),
+            conductor,
+            cell,
+            signals: Arc::new(Mutex::new(Vec::new())),
+         };
+         agent.listen();
+         agents.push(agent);
+      }
+      let network = TestNetwork { agents, network_seed, root, _guard: guard };
+      network.wait_for_handles(DEFAULT_WAIT).await?;
+      Ok(network)
+   }
+
+   /// Agent with this name
+   pub fn agent(&self, name: &str) -> &TestAgent {
+      self.agents.iter()
+         .find(|agent| agent.name == name)
+         .expect("Agent should be part of the test network")
+   }
+
+   /// Temporary sessions root of this network
+   pub fn root(&self) -> &std::path::Path {
+      self.root.path()
+   }
+
+   /// Wait until every agent sees the handle of every other agent
+   pub async fn wait_for_handles(&self, timeout: Duration) -> anyhow::Result<()> {
+      let count = self.agents.len();
+      wait_until(timeout, || {
+         self.agents.iter().all(|agent| {
+            agent.cell.get_all_handles().map(|handles| handles.len() >= count).unwrap_or(false)
+         })
+      }).await
+         .map_err(|_| anyhow::anyhow!("Agents did not discover each other"))
+   }
+}
+
+impl Drop for TestNetwork {
+   fn drop(&mut self) {
+      for agent in self.agents.iter() {
+         agent.conductor.shutdown();
+         release_session_lock(&agent.name);
+      }
+   }
+}
+
+
+/// Temporary sessions root with a folder for each session.
+/// Hold the guard until the end of the test.
+pub fn sessions_root(sids: &[&str]) -> (tokio::sync::MutexGuard<'static, ()>, TempDir) {
+   /// Tests run on their own threads and runtimes: wait for the lock without blocking on a runtime
+   let guard = loop {
+      match HARNESS_LOCK.try_lock() {
+         Ok(guard) => break guard,
+         Err(_) => std::thread::sleep(POLL_INTERVAL),
+      }
+   };
+   let root = tempfile::tempdir().expect("Should create a temporary folder");
+   set_config_root(root.path().to_path_buf());
+   for sid in sids {
+      std::fs::create_dir_all(root.path().join(sid)).expect("Should create a session folder");
+   }
+   (guard, root)
+}
+
+/// Mail input to the given agents
+pub fn new_mail<A: SnapmailApi>(subject: &str, to: Vec<&A>, manifest_address_list: Vec<ActionHash>) -> SendMailInput {
+   SendMailInput {
+      subject: subject.to_string(),
+      payload: format!("Content of {}", subject),
+      to: to.iter().map(|agent| agent.my_agent_pubkey()).collect(),
+      cc: vec![],
+      bcc: vec![],
+      manifest_address_list,
+   }
+}
+
+
+/// Network config with mDNS discovery on the loopback interface only
+pub fn local_network() -> KitsuneP2pConfig {
+   let mut kit = KitsuneP2pConfig::default();
+   kit.network_type = NetworkType::QuicMdns;
+   kit.bootstrap_service = None;
+   kit.transport_pool = vec![TransportConfig::Quic {
+      bind_to: Some(url2!("kitsune-quic://127.0.0.1:0")),
+      override_host: None,
+      override_port: None,
+   }];
+   kit
+}
+
+/// Poll `condition` until it is true. Fails after `timeout`.
+pub async fn wait_until<F>(timeout: Duration, mut condition: F) -> anyhow::Result<()>
+   where F: FnMut() -> bool
+{
+   let start = std::time::Instant::now();
+   while start.elapsed() < timeout {
+      if condition() {
+         return Ok(());
+      }
+      tokio::time::sleep(POLL_INTERVAL).await;
+   }
+   anyhow::bail!("Condition not met after {:?}", timeout)
+}
@@ -0,0 +1,148 @@
+//! Trash of a session: mails put aside before being deleted for good.
+//!
+//! Deleting a mail in the zome can not be undone, so trashing a mail only records it
+//! in the session's trash file. Purging a trashed mail deletes it in the zome.
+//! Mails deleted in the zome are purged: they are no longer shown in any folder but "All".
+
+use crate::{
+   api::SnapmailApi,
+   globals::*,
+   session::session_path,
+   settings::SessionSettings,
+};
+use chrono::{DateTime, Duration, Local};
+use holochain_types::dna::*;
+use serde::{Serialize, Deserialize};
+use snapmail::mail::*;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+
+/// Mails in the trash of a session, with the time they were trashed
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Trash {
+   /// Mail action hash -> time trashed
+   pub mails: BTreeMap<String, DateTime<Local>>,
+}
+
+impl Trash {
+   ///
+   pub fn filepath(sid: &str) -> PathBuf {
+      session_path(sid).join(TRASH_FILENAME)
+   }
+
+   /// Trash of a session. Empty if there is no trash file.
+   pub fn load(sid: &str) -> anyhow::Result<Self> {
+      let filepath = Self::filepath(sid);
+      match std::fs::read_to_string(&filepath) {
+         Ok(yaml) => Ok(serde_yaml::from_str::<Trash>(&yaml)?),
+         Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Trash::default()),
+         Err(e) => anyhow::bail!("Failed to read trash file {:?}: {}", filepath, e),
+      }
+   }
+
+   ///
+   pub fn save(&self, sid: &str) -> anyhow::Result<()> {
+      let yaml = serde_yaml::to_string(self)?;
+      std::fs::write(Self::filepath(sid), yaml)?;
+      Ok(())
+   }
+
+   ///
+   pub fn trashed_at(&self, ah: &ActionHash) -> Option<DateTime<Local>> {
+      self.mails.get(&ah.to_string()).cloned()
+   }
+
+   ///
+   pub fn contains(&self, ah: &ActionHash) -> bool {
+      self.mails.contains_key(&ah.to_string())
+   }
+}
+
+
+/// True if the mail was deleted in the zome
+pub fn is_purged(item: &MailItem) -> bool {
+   matches!(item.state, MailState::In(InMailState::Deleted) | MailState::Out(OutMailState::Deleted))
+}
+
+/// Mails of a network in the trash, with the time they were trashed, oldest first
+pub fn trashed_mails(sid: &str, api: &dyn SnapmailApi) -> anyhow::Result<Vec<(MailItem, DateTime<Local>)>> {
+   let trash = Trash::load(sid)?;
+   let mut mails: Vec<(MailItem, DateTime<Local>)> = api.get_all_mails()?.into_iter()
+      .filter(|item| !is_purged(item))
+      .filter_map(|item| trash.trashed_at(&item.address).map(|date| (item, date)))
+      .collect();
+   mails.sort_by_key(|(_, date)| *date);
+   Ok(mails)
+}
+
+/// Put a mail in the trash
+pub fn trash_mail(sid: &str, api: &dyn SnapmailApi, ah: &ActionHash) -> anyhow::Result<()> {
+   let item = api.get_all_mails()?.into_iter()
+      .find(|item| &item.address == ah)
+      .ok_or_else(|| anyhow::anyhow!("Unknown mail {}", ah))?;
+   if is_purged(&item) {
+      anyhow::bail!("Mail {} was deleted for good", ah);
+   }
+   let mut trash = Trash::load(sid)?;
+   trash.mails.entry(ah.to_string()).or_insert_with(Local::now);
+   trash.save(sid)
+}
+
+/// Take a mail out of the trash
+pub fn restore_mail(sid: &str, ah: &ActionHash) -> anyhow::Result<()> {
+   let mut trash = Trash::load(sid)?;
+   if trash.mails.remove(&ah.to_string()).is_none() {
+      anyhow::bail!("Mail {} is not in the trash", ah);
+   }
+   trash.save(sid)
+}
+
+/// Delete a mail in the zome. Can not be undone.
+pub fn purge_mail(sid: &str, api: &dyn SnapmailApi, ah: &ActionHash) -> anyhow::Result<()> {
+   api.delete_mail(ah.clone())?;
+   let mut trash = Trash::load(sid)?;
+   if trash.mails.remove(&ah.to_string()).is_some() {
+      trash.save(sid)?;
+   }
+   Ok(())
+}
+
+/// Purge the trashed mails of a network that match `predicate`. Returns the purged mails.
+fn purge_where(
+   sid: &str,
+   api: &dyn SnapmailApi,
+   predicate: impl Fn(&DateTime<Local>) -> bool,
+) -> anyhow::Result<Vec<ActionHash>> {
+   let mut purged = Vec::new();
+   for (item, trashed_at) in trashed_mails(sid, api)? {
+      if !predicate(&trashed_at) {
+         continue;
+      }
+      match purge_mail(sid, api, &item.address) {
+         Ok(()) => purged.push(item.address),
+         Err(e) => err_msg!("Failed to purge mail {}: {}", item.address, e),
+      }
+   }
+   Ok(purged)
+}
+
+/// Purge all trashed mails of a network
+pub fn empty_trash(sid: &str, api: &dyn SnapmailApi) -> anyhow::Result<Vec<ActionHash>> {
+   purge_where(sid, api, |_| true)
+}
+
+/// Purge the mails of a network trashed more than `retention_days` ago
+pub fn apply_retention(sid: &str, api: &dyn SnapmailApi, retention_days: u32) -> anyhow::Result<Vec<ActionHash>> {
+   let limit = Local::now() - Duration::days(retention_days as i64);
+   purge_where(sid, api, |trashed_at| *trashed_at < limit)
+}
+
+/// Purge the mails of a network trashed for longer than the session's `trash_retention_days`, if set
+pub fn apply_retention_setting(sid: &str, api: &dyn SnapmailApi) -> anyhow::Result<Vec<ActionHash>> {
+   match SessionSettings::load(sid)?.trash_retention_days {
+      Some(days) => apply_retention(sid, api, days),
+      None => Ok(Vec::new()),
+   }
+}
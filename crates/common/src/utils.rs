@@ -1,36 +1,27 @@
-use holochain_types::dna::*;
-use snapmail::handle::HandleItem;
-
-/// Print a msg with `snapmail: ` pre-pended
-/// and ansi colors.
+/// Log an info message for the user.
+/// Routed through `tracing` (see [`crate::logging`]).
 #[macro_export]
 macro_rules! msg {
     ($($arg:tt)*) => ({
-        use ansi_term::Color::*;
-        let now = chrono::Utc::now().format("%H:%M:%S");
-        let prepend = format!("[{}] snapmail: ", now);
-        print!("{}", Blue.bold().paint(prepend));
-        println!($($arg)*);
+        $crate::tracing::info!(target: "snapmail", $($arg)*);
     })
 }
 
+/// Log a debug message
 #[allow(unused_macros)]
 #[macro_export]
 macro_rules! dbg {
     ($($arg:tt)*) => ({
-        use ansi_term::Color::*;
-        print!("{} ", Yellow.bold().paint("snap-dbg:"));
-        println!($($arg)*);
+        $crate::tracing::debug!(target: "snapmail", $($arg)*);
     })
 }
 
+/// Log an error message
 #[allow(unused_macros)]
 #[macro_export]
 macro_rules! err_msg {
     ($($arg:tt)*) => ({
-        use ansi_term::Color::*;
-        print!("{} ", Red.bold().paint("snapmail error:"));
-        println!($($arg)*);
+        $crate::tracing::error!(target: "snapmail", $($arg)*);
     })
 }
 
@@ -0,0 +1,129 @@
+//! Network changes of a conductor config. No conductor needed.
+
+#![allow(unused_doc_comments)]
+
+use holochain_conductor_api::config::conductor::ConductorConfig;
+use holochain_p2p::kitsune_p2p::{NetworkType, ProxyConfig, TransportConfig};
+use snapmail_common::config::*;
+use url2::Url2;
+
+
+/// Bind address, override host and proxy URL of the config's only transport
+fn transport_of(config: &ConductorConfig) -> (Option<Url2>, Option<String>, Option<Url2>) {
+   let pool = &config.network.as_ref().unwrap().transport_pool;
+   assert_eq!(pool.len(), 1);
+   match &pool[0] {
+      TransportConfig::Quic { bind_to, override_host, .. } => (bind_to.clone(), override_host.clone(), None),
+      TransportConfig::Proxy { sub_transport, proxy_config } => {
+         let ProxyConfig::RemoteProxyClient { proxy_url } = proxy_config else {
+            panic!("Proxy should be a remote proxy client");
+         };
+         let TransportConfig::Quic { bind_to, override_host, .. } = sub_transport.as_ref() else {
+            panic!("Proxy should wrap a QUIC transport");
+         };
+         (bind_to.clone(), override_host.clone(), Some(proxy_url.clone()))
+      },
+      _ => panic!("Transport should be QUIC, wrapped in a proxy or not"),
+   }
+}
+
+
+#[test]
+fn proxy_and_bind_to_combine() {
+   let proxy = Url2::parse("kitsune-proxy://proxy.example.com/p1");
+   let bind_to = Url2::parse("kitsune-quic://0.0.0.0:0");
+   let mut config = ConductorConfig::default();
+
+   /// A missing network section is created, with the proxy wrapping a default QUIC transport
+   let change = NetworkChange { maybe_proxy: Some(proxy.clone()), ..Default::default() };
+   apply_network_change(&mut config, &change).unwrap();
+   assert_eq!(transport_of(&config), (None, None, Some(proxy.clone())));
+   assert_eq!(network_urls(&config).0, Some(proxy.clone()));
+
+   /// QUIC settings change inside the proxy
+   let change = NetworkChange {
+      maybe_bind_to: Some(bind_to.clone()),
+      maybe_override_host: Some("example.com".to_string()),
+      ..Default::default()
+   };
+   apply_network_change(&mut config, &change).unwrap();
+   assert_eq!(transport_of(&config), (Some(bind_to.clone()), Some("example.com".to_string()), Some(proxy)));
+
+   /// Removing the proxy keeps the QUIC settings
+   let change = NetworkChange { remove_proxy: true, ..Default::default() };
+   apply_network_change(&mut config, &change).unwrap();
+   assert_eq!(transport_of(&config), (Some(bind_to.clone()), Some("example.com".to_string()), None));
+   assert_eq!(network_urls(&config).0, None);
+
+   /// Setting another proxy wraps them again
+   let other_proxy = Url2::parse("kitsune-proxy://other.example.com/p2");
+   let change = NetworkChange { maybe_proxy: Some(other_proxy.clone()), ..Default::default() };
+   apply_network_change(&mut config, &change).unwrap();
+   assert_eq!(transport_of(&config), (Some(bind_to), Some("example.com".to_string()), Some(other_proxy)));
+}
+
+
+#[test]
+fn transport_and_bootstrap_leave_the_pool() {
+   let proxy = Url2::parse("kitsune-proxy://proxy.example.com/p1");
+   let bootstrap = Url2::parse("https://bootstrap.example.com");
+   let mut config = ConductorConfig::default();
+   apply_network_change(&mut config, &NetworkChange { maybe_proxy: Some(proxy.clone()), ..Default::default() }).unwrap();
+
+   let change = NetworkChange { maybe_transport: Some(NetworkTransport::Mdns), ..Default::default() };
+   apply_network_change(&mut config, &change).unwrap();
+   assert!(matches!(config.network.as_ref().unwrap().network_type, NetworkType::QuicMdns));
+   let change = NetworkChange {
+      maybe_transport: Some(NetworkTransport::Quic),
+      maybe_bootstrap: Some(bootstrap.clone()),
+      ..Default::default()
+   };
+   apply_network_change(&mut config, &change).unwrap();
+   assert!(matches!(config.network.as_ref().unwrap().network_type, NetworkType::QuicBootstrap));
+   assert_eq!(network_urls(&config), (Some(proxy.clone()), Some(bootstrap)));
+   assert_eq!(transport_of(&config), (None, None, Some(proxy)));
+
+   /// An empty change leaves the config as it was
+   let before = serde_yaml::to_string(&config).unwrap();
+   assert!(NetworkChange::default().is_empty());
+   apply_network_change(&mut config, &NetworkChange::default()).unwrap();
+   assert_eq!(serde_yaml::to_string(&config).unwrap(), before);
+}
+
+
+#[test]
+fn invalid_urls_are_rejected() {
+   let invalid_changes = [
+      NetworkChange { maybe_proxy: Some(Url2::parse("https://proxy.example.com")), ..Default::default() },
+      NetworkChange { maybe_bootstrap: Some(Url2::parse("kitsune-proxy://bootstrap.example.com")), ..Default::default() },
+      NetworkChange { maybe_bind_to: Some(Url2::parse("http://0.0.0.0:0")), ..Default::default() },
+      NetworkChange {
+         maybe_proxy: Some(Url2::parse("kitsune-proxy://proxy.example.com/p1")),
+         remove_proxy: true,
+         ..Default::default()
+      },
+   ];
+   for change in invalid_changes.iter() {
+      assert!(change.validate().is_err());
+      /// Config is not touched
+      let mut config = ConductorConfig::default();
+      assert!(apply_network_change(&mut config, change).is_err());
+      assert!(config.network.is_none());
+   }
+   let http_bootstrap = NetworkChange { maybe_bootstrap: Some(Url2::parse("http://localhost:8787")), ..Default::default() };
+   assert!(http_bootstrap.validate().is_ok());
+}
+
+
+#[test]
+fn diff_lines_on_insert_delete_replace() {
+   let before = "a\nb\nc";
+   assert!(diff_lines(before, before).is_empty());
+   assert_eq!(diff_lines(before, "a\nb\nx\nc"), vec!["+ x"]);
+   assert_eq!(diff_lines(before, "a\nc"), vec!["- b"]);
+   assert_eq!(diff_lines(before, "a\nx\nc"), vec!["- b", "+ x"]);
+   assert_eq!(diff_lines("", "a\nb"), vec!["+ a", "+ b"]);
+   assert_eq!(diff_lines("a\nb", ""), vec!["- a", "- b"]);
+   /// Changes at both ends
+   assert_eq!(diff_lines(before, "x\nb\ny"), vec!["- a", "+ x", "- c", "+ y"]);
+}
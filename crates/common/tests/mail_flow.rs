@@ -0,0 +1,117 @@
+//! End-to-end mail flows between in-process agents.
+//! Run with: cargo test -p snapmail_common --features test-support
+
+#![allow(unused_doc_comments)]
+
+use snapmail::{
+   mail::*,
+   mail::entries::*,
+   signal_protocol::SignalProtocol,
+};
+use snapmail_common::{
+   api::SnapmailApi,
+   attachment::*,
+   test_support::*,
+};
+
+
+/// Wait until a sent mail reaches `expected` state, pulling the sender's ack inbox meanwhile
+async fn wait_for_outmail_state(sender: &TestAgent, ah: &ActionHash, expected: OutMailState) {
+   let res = wait_until(DEFAULT_WAIT, || {
+      let _ = sender.cell.check_ack_inbox();
+      sender.cell.get_outmail_state(ah.clone()).map(|state| state == expected).unwrap_or(false)
+   }).await;
+   assert!(res.is_ok(), "Outmail {} never reached state {:?}", ah, expected);
+}
+
+
+#[tokio::test(flavor = "multi_thread")]
+async fn send_with_attachment_then_ack() {
+   let network = TestNetwork::new(&["alex", "billy"]).await.unwrap();
+   let alex = network.agent("alex");
+   let billy = network.agent("billy");
+
+   /// Alex sends a mail with an attachment
+   let file_content = b"snapmail attachment content".repeat(1000);
+   let file_path = network.root().join("attachment.txt");
+   std::fs::write(&file_path, &file_content).unwrap();
+   let manifest_ah = write_attachment(&alex.cell, file_path).unwrap();
+   let sent_ah = alex.cell.send_mail(new_mail("with attachment", vec![&billy.cell], vec![manifest_ah])).unwrap();
+
+   /// Billy receives it
+   billy.wait_for_signal(DEFAULT_WAIT, |signal| {
+      matches!(signal, SignalProtocol::ReceivedMail(item) if item.mail.subject == "with attachment")
+   }).await.unwrap();
+   let _ = billy.cell.check_mail_inbox().unwrap();
+   let mails = billy.cell.get_all_mails().unwrap();
+   let inmail = mails.iter()
+      .find(|item| item.mail.subject == "with attachment")
+      .expect("Billy should have the mail");
+   assert_eq!(inmail.author, *alex.cell.agent_pubkey());
+   assert_eq!(inmail.state, MailState::In(InMailState::Unacknowledged));
+
+   /// Billy downloads the attachment
+   assert_eq!(inmail.mail.attachments.len(), 1);
+   let download_folder = network.root().join("billy-downloads");
+   std::fs::create_dir_all(&download_folder).unwrap();
+   let path = get_attachment(&billy.cell, inmail.mail.attachments[0].manifest_eh.clone(), download_folder).unwrap();
+   assert_eq!(std::fs::read(path).unwrap(), file_content);
+
+   /// Billy acknowledges it and Alex sees it acknowledged
+   billy.cell.acknowledge_mail(inmail.address.clone()).unwrap();
+   alex.wait_for_signal(DEFAULT_WAIT, |signal| matches!(signal, SignalProtocol::ReceivedAck(_))).await.unwrap();
+   wait_for_outmail_state(alex, &sent_ah, OutMailState::AllAcknowledged).await;
+}
+
+
+#[tokio::test(flavor = "multi_thread")]
+async fn send_to_several_then_delete() {
+   let network = TestNetwork::new(&["alex", "billy", "camille"]).await.unwrap();
+   let alex = network.agent("alex");
+   let billy = network.agent("billy");
+   let camille = network.agent("camille");
+
+   let sent_ah = alex.cell.send_mail(new_mail("to all", vec![&billy.cell, &camille.cell], vec![])).unwrap();
+   for agent in [billy, camille] {
+      agent.wait_for_signal(DEFAULT_WAIT, |signal| {
+         matches!(signal, SignalProtocol::ReceivedMail(item) if item.mail.subject == "to all")
+      }).await.unwrap();
+   }
+   wait_for_outmail_state(alex, &sent_ah, OutMailState::AllReceived).await;
+
+   /// Alex deletes the sent mail
+   let output = alex.cell.delete_mail(sent_ah.clone()).unwrap();
+   assert!(output.0.is_some(), "delete_mail() should return the delete action");
+   assert_eq!(alex.cell.get_outmail_state(sent_ah).unwrap(), OutMailState::Deleted);
+}
+
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resend_delivers_pending_mail() {
+   let network = TestNetwork::new(&["alex", "billy"]).await.unwrap();
+   let alex = network.agent("alex");
+   let billy = network.agent("billy");
+
+   /// Billy is offline: the mail stays pending
+   billy.go_offline().await.unwrap();
+   let sent_ah = alex.cell.send_mail(new_mail("resent", vec![&billy.cell], vec![])).unwrap();
+   wait_for_outmail_state(alex, &sent_ah, OutMailState::AllSent).await;
+
+   /// Back online, billy only gets the mail through alex's resend, not by pulling its inbox
+   billy.go_online().await.unwrap();
+   let mut was_resent = false;
+   let res = wait_until(DEFAULT_WAIT, || {
+      was_resent |= alex.cell.resend_outmails().map(|resent| resent.contains(&sent_ah)).unwrap_or(false);
+      billy.cell.get_all_mails()
+         .map(|mails| mails.iter().any(|item| item.mail.subject == "resent"))
+         .unwrap_or(false)
+   }).await;
+   assert!(res.is_ok(), "Billy never received the resent mail");
+   assert!(was_resent, "Mail was not delivered by a resend");
+   /// Mail is only received once, even after alex learns of the delivery
+   wait_for_outmail_state(alex, &sent_ah, OutMailState::AllReceived).await;
+   let count = billy.cell.get_all_mails().unwrap().iter()
+      .filter(|item| item.mail.subject == "resent")
+      .count();
+   assert_eq!(count, 1);
+}
@@ -0,0 +1,496 @@
+//! Mail flows on the in-memory API. No conductor needed.
+
+#![allow(unused_doc_comments)]
+
+use holochain_types::dna::*;
+use snapmail::mail::*;
+use snapmail_common::{
+   api::SnapmailApi,
+   attachment::*,
+   mock::*,
+   networks::SessionNetworks,
+   presence::PresenceTracker,
+   read_flags::ReadFlags,
+   trash::*,
+   labels::*,
+   blocklist::*,
+   rules::*,
+   auto_download::mail_folder,
+   settings::*,
+   shutdown::*,
+   test_support::{new_mail, sessions_root},
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+
+/// Mail with this subject in the agent's mailbox
+fn find_mail(agent: &MockSnapmail, subject: &str) -> Option<MailItem> {
+   agent.get_all_mails().unwrap().into_iter().find(|item| item.mail.subject == subject)
+}
+
+
+#[test]
+fn handles_are_shared() {
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+
+   assert_eq!(alex.get_my_handle().unwrap(), "alex");
+   assert_eq!(billy.find_agent("alex".to_string()).unwrap(), vec![alex.my_agent_pubkey()]);
+   billy.set_handle("bill".to_string()).unwrap();
+   let names: Vec<String> = alex.get_all_handles().unwrap().into_iter().map(|item| item.name).collect();
+   assert_eq!(names, vec!["alex".to_string(), "bill".to_string()]);
+   assert_eq!(alex.peer_count().unwrap(), 2);
+}
+
+
+#[test]
+fn send_then_ack() {
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+
+   let sent_ah = alex.send_mail(new_mail("hello", vec![&billy], vec![])).unwrap();
+   assert_eq!(alex.get_outmail_state(sent_ah.clone()).unwrap(), OutMailState::AllReceived);
+   let inmail = find_mail(&billy, "hello").expect("Billy should have the mail");
+   assert_eq!(inmail.author, alex.my_agent_pubkey());
+   assert_eq!(inmail.state, MailState::In(InMailState::Unacknowledged));
+
+   billy.acknowledge_mail(inmail.address.clone()).unwrap();
+   assert_eq!(alex.get_outmail_state(sent_ah).unwrap(), OutMailState::AllAcknowledged);
+   assert_eq!(find_mail(&billy, "hello").unwrap().state, MailState::In(InMailState::AckDelivered));
+   /// Acknowledging twice fails
+   assert!(billy.acknowledge_mail(inmail.address).is_err());
+}
+
+
+#[test]
+fn offline_recipient_gets_mail_later() {
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+
+   billy.set_online(false);
+   let sent_ah = alex.send_mail(new_mail("later", vec![&billy], vec![])).unwrap();
+   assert_eq!(alex.get_outmail_state(sent_ah.clone()).unwrap(), OutMailState::AllSent);
+   assert!(find_mail(&billy, "later").is_none());
+   /// Resending while billy is offline does nothing
+   assert!(alex.resend_outmails().unwrap().is_empty());
+
+   billy.set_online(true);
+   assert_eq!(alex.resend_outmails().unwrap(), vec![sent_ah.clone()]);
+   assert!(billy.check_mail_inbox().unwrap().is_empty());
+   assert_eq!(alex.get_outmail_state(sent_ah.clone()).unwrap(), OutMailState::AllReceived);
+
+   /// Ack stays pending while alex is offline
+   alex.set_online(false);
+   let inmail = find_mail(&billy, "later").unwrap();
+   billy.acknowledge_mail(inmail.address).unwrap();
+   assert_eq!(find_mail(&billy, "later").unwrap().state, MailState::In(InMailState::AckPending));
+   alex.set_online(true);
+   assert_eq!(alex.check_ack_inbox().unwrap().len(), 1);
+   assert_eq!(alex.get_outmail_state(sent_ah).unwrap(), OutMailState::AllAcknowledged);
+}
+
+
+#[test]
+fn shutdown_waits_only_for_new_work() {
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+   let old_inmail = {
+      alex.send_mail(new_mail("to ack", vec![&billy], vec![])).unwrap();
+      find_mail(&billy, "to ack").unwrap()
+   };
+
+   /// Work left over from an earlier run
+   alex.hold_hand_offs(true);
+   billy.hold_hand_offs(true);
+   let old_ah = alex.send_mail(new_mail("old", vec![&billy], vec![])).unwrap();
+   billy.acknowledge_mail(old_inmail.address.clone()).unwrap();
+   assert_eq!(alex.get_outmail_state(old_ah.clone()).unwrap(), OutMailState::Unsent);
+   assert_eq!(find_mail(&billy, "to ack").unwrap().state, MailState::In(InMailState::AckUnsent));
+   let alex_baseline = pending_mails(&alex).unwrap();
+   let billy_baseline = pending_mails(&billy).unwrap();
+   assert_eq!(alex_baseline.unsent_outmails, vec![old_ah.clone()]);
+   assert_eq!(billy_baseline.unsent_acks, vec![old_inmail.address]);
+
+   /// Only the mail sent in this run is waited for
+   assert!(pending_mails(&billy).unwrap().since(&billy_baseline).is_empty());
+   let new_ah = alex.send_mail(new_mail("new", vec![&billy], vec![])).unwrap();
+   let pending = pending_mails(&alex).unwrap().since(&alex_baseline);
+   assert_eq!(pending.unsent_outmails, vec![new_ah.clone()]);
+   assert!(pending.unsent_acks.is_empty());
+
+   /// Handed off: nothing left to wait for
+   alex.hold_hand_offs(false);
+   billy.hold_hand_offs(false);
+   assert!(pending_mails(&alex).unwrap().since(&alex_baseline).is_empty());
+   assert!(pending_mails(&alex).unwrap().is_empty());
+   assert_eq!(alex.get_outmail_state(new_ah).unwrap(), OutMailState::AllReceived);
+   assert_eq!(alex.get_outmail_state(old_ah).unwrap(), OutMailState::AllReceived);
+   assert_eq!(find_mail(&billy, "to ack").unwrap().state, MailState::In(InMailState::AckDelivered));
+}
+
+#[test]
+fn session_files_default_only_when_missing() {
+   let (_guard, _root) = sessions_root(&["alex"]);
+   assert_eq!(SessionSettings::load("alex").unwrap(), SessionSettings::default());
+   /// Unreadable settings are an error, not defaults
+   std::fs::create_dir_all(SessionSettings::filepath("alex")).unwrap();
+   assert!(SessionSettings::load("alex").is_err());
+   std::fs::remove_dir(SessionSettings::filepath("alex")).unwrap();
+   std::fs::write(SessionSettings::filepath("alex"), "ack_policy: [").unwrap();
+   assert!(SessionSettings::load("alex").is_err());
+   /// Unreadable blocklist is an error, not an empty one
+   std::fs::create_dir_all(Blocklist::filepath("alex")).unwrap();
+   assert!(Blocklist::load("alex").is_err());
+   /// Unreadable trash is an error, not an empty one
+   std::fs::create_dir_all(Trash::filepath("alex")).unwrap();
+   assert!(Trash::load("alex").is_err());
+   /// Unreadable labels are an error, not empty ones
+   std::fs::create_dir_all(MailLabels::filepath("alex")).unwrap();
+   assert!(MailLabels::load("alex").is_err());
+   /// Unreadable read flags are an error, not empty ones
+   std::fs::create_dir_all(ReadFlags::filepath("alex")).unwrap();
+   assert!(ReadFlags::load("alex").is_err());
+   /// Unreadable networks are an error, not the default network only
+   std::fs::create_dir_all(SessionNetworks::filepath("alex")).unwrap();
+   assert!(SessionNetworks::load("alex").is_err());
+}
+
+#[test]
+fn attachment_round_trip_then_delete() {
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+   let folder = tempfile::tempdir().unwrap();
+
+   /// Alex sends a file big enough to be split in chunks
+   let file_content = b"snapmail attachment content".repeat(1000);
+   let file_path = folder.path().join("attachment.txt");
+   std::fs::write(&file_path, &file_content).unwrap();
+   let manifest_ah = write_attachment(&alex, file_path).unwrap();
+   let sent_ah = alex.send_mail(new_mail("with attachment", vec![&billy], vec![manifest_ah])).unwrap();
+
+   /// Billy downloads it
+   let inmail = find_mail(&billy, "with attachment").unwrap();
+   assert_eq!(inmail.mail.attachments.len(), 1);
+   let download_folder = folder.path().join("downloads");
+   std::fs::create_dir_all(&download_folder).unwrap();
+   let path = get_attachment(&billy, inmail.mail.attachments[0].manifest_eh.clone(), download_folder).unwrap();
+   assert_eq!(std::fs::read(path).unwrap(), file_content);
+
+   /// Both delete their copy
+   assert!(alex.delete_mail(sent_ah.clone()).unwrap().0.is_some());
+   assert_eq!(alex.get_outmail_state(sent_ah).unwrap(), OutMailState::Deleted);
+   assert!(billy.delete_mail(inmail.address).unwrap().0.is_some());
+   assert_eq!(find_mail(&billy, "with attachment").unwrap().state, MailState::In(InMailState::Deleted));
+}
+
+
+#[tokio::test(flavor = "multi_thread")]
+async fn presence_sweep() {
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+   let camille = network.add_agent("camille");
+   camille.set_online(false);
+
+   let tracker = PresenceTracker::in_memory(Arc::new(alex.clone())).with_max_concurrent(1);
+   assert_eq!(tracker.get(&billy.my_agent_pubkey()).is_online, None);
+   tracker.sweep_contacts().await.unwrap();
+   let billy_presence = tracker.get(&billy.my_agent_pubkey());
+   assert_eq!(billy_presence.is_online, Some(true));
+   assert!(billy_presence.last_seen.is_some());
+   assert_eq!(tracker.get(&camille.my_agent_pubkey()).label(), "offline");
+   /// Self is not pinged
+   assert_eq!(tracker.get(&alex.my_agent_pubkey()).is_online, None);
+
+   /// Last seen time is kept once offline
+   billy.set_online(false);
+   tracker.sweep_contacts().await.unwrap();
+   let billy_presence = tracker.get(&billy.my_agent_pubkey());
+   assert_eq!(billy_presence.is_online, Some(false));
+   assert!(billy_presence.last_seen.is_some());
+   assert!(billy_presence.label().starts_with("seen "));
+}
+
+
+#[test]
+fn trash_restore_then_purge() {
+   let (_guard, _root) = sessions_root(&["billy"]);
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+   alex.send_mail(new_mail("first", vec![&billy], vec![])).unwrap();
+   alex.send_mail(new_mail("second", vec![&billy], vec![])).unwrap();
+   let first = find_mail(&billy, "first").unwrap();
+   let second = find_mail(&billy, "second").unwrap();
+
+   /// Trashing does not delete
+   trash_mail("billy", &billy, &first.address).unwrap();
+   trash_mail("billy", &billy, &second.address).unwrap();
+   assert_eq!(trashed_mails("billy", &billy).unwrap().len(), 2);
+   assert_eq!(find_mail(&billy, "first").unwrap().state, MailState::In(InMailState::Unacknowledged));
+
+   restore_mail("billy", &first.address).unwrap();
+   assert!(restore_mail("billy", &first.address).is_err());
+   assert_eq!(trashed_mails("billy", &billy).unwrap().len(), 1);
+
+   /// Nothing is old enough for retention, then emptying purges for good
+   assert!(apply_retention("billy", &billy, 30).unwrap().is_empty());
+   assert_eq!(empty_trash("billy", &billy).unwrap(), vec![second.address.clone()]);
+   assert!(is_purged(&find_mail(&billy, "second").unwrap()));
+   assert!(trash_mail("billy", &billy, &second.address).is_err());
+   assert!(trashed_mails("billy", &billy).unwrap().is_empty());
+}
+
+
+#[test]
+fn labels_and_saved_folder_query() {
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+   let camille = network.add_agent("camille");
+   alex.send_mail(new_mail("from alex", vec![&billy], vec![])).unwrap();
+   camille.send_mail(new_mail("from camille", vec![&billy], vec![])).unwrap();
+   let from_alex = find_mail(&billy, "from alex").unwrap();
+   let from_camille = find_mail(&billy, "from camille").unwrap();
+
+   let mut labels = MailLabels::default();
+   assert!(labels.add(&from_alex.address, "work").unwrap());
+   assert!(!labels.add(&from_alex.address, " work ").unwrap());
+   assert!(labels.add(&from_camille.address, "work").unwrap());
+   assert!(labels.add(&from_camille.address, "family").unwrap());
+   assert!(labels.add(&from_camille.address, "").is_err());
+   assert_eq!(labels.label_counts().get("work"), Some(&2));
+
+   labels.save_folder("Work from alex", MailQuery {
+      from: Some("alex".to_string()),
+      label: Some("work".to_string()),
+      state: Some(StateFilter::Unacknowledged),
+      ..Default::default()
+   }).unwrap();
+   assert!(labels.save_folder("Inbox", MailQuery::default()).is_err());
+   let query = &labels.folder("Work from alex").unwrap().query;
+   let matching: Vec<String> = billy.get_all_mails().unwrap().iter()
+      .filter(|item| {
+         let sender = if item.author == alex.my_agent_pubkey() { "alex" } else { "camille" };
+         query.matches(item, Some(sender), labels.labels_of(&item.address))
+      })
+      .map(|item| item.mail.subject.clone())
+      .collect();
+   assert_eq!(matching, vec!["from alex".to_string()]);
+
+   /// Acknowledged mails leave the folder
+   billy.acknowledge_mail(from_alex.address.clone()).unwrap();
+   let acked = find_mail(&billy, "from alex").unwrap();
+   assert!(!query.matches(&acked, Some("alex"), labels.labels_of(&acked.address)));
+
+   assert!(labels.remove(&from_camille.address, "family"));
+   assert!(!labels.remove(&from_camille.address, "family"));
+   assert!(labels.remove_folder("Work from alex"));
+   assert!(labels.folders.is_empty());
+}
+
+
+#[test]
+fn blocked_and_spam_senders() {
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+   let camille = network.add_agent("camille");
+   let spammer = network.add_agent("spammer");
+   /// Billy wrote to alex, so alex is a known sender
+   billy.send_mail(new_mail("hi alex", vec![&alex], vec![])).unwrap();
+   for i in 0..3 {
+      alex.send_mail(new_mail(&format!("alex {}", i), vec![&billy], vec![])).unwrap();
+      spammer.send_mail(new_mail(&format!("spam {}", i), vec![&billy], vec![])).unwrap();
+   }
+   camille.send_mail(new_mail("from camille", vec![&billy], vec![])).unwrap();
+
+   let handle_map: HashMap<AgentPubKey, String> = billy.get_all_handles().unwrap().into_iter()
+      .map(|item| (item.agentId, item.name))
+      .collect();
+   let filter = SpamFilter { max_unknown_mails: 2, window_mins: 60 };
+   let mut blocklist = Blocklist::default();
+   assert!(blocklist.add_pattern("*").is_err());
+   assert!(blocklist.add_pattern("CAM*").unwrap());
+   let items = billy.get_all_mails().unwrap();
+   let classes = classify_mails(&items, &handle_map, &blocklist, &filter);
+   let class_of = |subject: &str| classes[&find_mail(&billy, subject).unwrap().address];
+   assert_eq!(class_of("alex 2"), MailClass::Normal);
+   assert_eq!(class_of("spam 0"), MailClass::Spam);
+   assert_eq!(class_of("from camille"), MailClass::Blocked);
+   assert!(!classes.contains_key(&find_mail(&billy, "hi alex").unwrap().address));
+
+   /// An old burst does not flag later mails
+   spammer.send_mail(new_mail("spam 3", vec![&billy], vec![])).unwrap();
+   let items: Vec<MailItem> = billy.get_all_mails().unwrap().into_iter()
+      .map(|mut item| {
+         if item.mail.subject.starts_with("spam ") && item.mail.subject != "spam 3" {
+            item.date -= 24 * 3600;
+         }
+         item
+      })
+      .collect();
+   let classes = classify_mails(&items, &handle_map, &blocklist, &filter);
+   assert_eq!(classes[&find_mail(&billy, "spam 0").unwrap().address], MailClass::Spam);
+   assert_eq!(classes[&find_mail(&billy, "spam 3").unwrap().address], MailClass::Normal);
+
+   /// Unblocking a spammer allows it, blocking overrides the pattern check
+   blocklist.unblock(&spammer.my_agent_pubkey());
+   assert!(blocklist.block(&alex.my_agent_pubkey(), "alex"));
+   assert!(!blocklist.block(&alex.my_agent_pubkey(), "alex"));
+   let classes = classify_mails(&items, &handle_map, &blocklist, &filter);
+   assert_eq!(classes[&find_mail(&billy, "spam 1").unwrap().address], MailClass::Normal);
+   assert_eq!(classes[&find_mail(&billy, "alex 0").unwrap().address], MailClass::Blocked);
+   assert!(blocklist.remove_pattern("cam*"));
+   assert!(!blocklist.is_blocked(&camille.my_agent_pubkey(), Some("camille")));
+}
+
+
+#[test]
+fn rules_match_in_order() {
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+   let folder = tempfile::tempdir().unwrap();
+   let file_path = folder.path().join("invoice.pdf");
+   std::fs::write(&file_path, b"%PDF").unwrap();
+   let manifest_ah = write_attachment(&alex, file_path).unwrap();
+   alex.send_mail(new_mail("Invoice 42", vec![&billy], vec![manifest_ah])).unwrap();
+   alex.send_mail(new_mail("Lunch?", vec![&billy], vec![])).unwrap();
+   let handle_map: HashMap<AgentPubKey, String> = billy.get_all_handles().unwrap().into_iter()
+      .map(|item| (item.agentId, item.name))
+      .collect();
+
+   let rules = vec![
+      Rule {
+         name: "invoices".to_string(),
+         from: Some("Al*".to_string()),
+         subject: Some("(?i)^invoice".to_string()),
+         attachment_type: Some(".PDF".to_string()),
+         attachment_max_size: Some(1000),
+         actions: vec![RuleAction::Label("invoices".to_string()), RuleAction::Acknowledge],
+         stop: true,
+         ..Default::default()
+      },
+      Rule {
+         name: "from alex".to_string(),
+         from: Some("alex".to_string()),
+         actions: vec![RuleAction::Label("alex".to_string())],
+         ..Default::default()
+      },
+      Rule {
+         name: "disabled".to_string(),
+         disabled: true,
+         actions: vec![RuleAction::Trash],
+         ..Default::default()
+      },
+   ];
+   let invoice = find_mail(&billy, "Invoice 42").unwrap();
+   let actions = matching_actions(&rules, &invoice, &handle_map).unwrap();
+   assert_eq!(actions, vec![
+      ("invoices".to_string(), RuleAction::Label("invoices".to_string())),
+      ("invoices".to_string(), RuleAction::Acknowledge),
+   ]);
+   let lunch = find_mail(&billy, "Lunch?").unwrap();
+   let explained = rules[0].explain(&lunch, &handle_map).unwrap();
+   assert_eq!(explained.iter().map(|(_, is_met)| *is_met).collect::<Vec<bool>>(), vec![true, false, false]);
+   assert_eq!(matching_actions(&rules, &lunch, &handle_map).unwrap(), vec![
+      ("from alex".to_string(), RuleAction::Label("alex".to_string())),
+   ]);
+
+   /// Sent mails are never matched, invalid regexes are errors
+   let sent = find_mail(&alex, "Lunch?").unwrap();
+   assert!(matching_actions(&rules, &sent, &handle_map).unwrap().is_empty());
+   let invalid = Rule { subject: Some("(".to_string()), ..Default::default() };
+   assert!(invalid.matches(&lunch, &handle_map).is_err());
+   assert!(compile_rules(std::slice::from_ref(&invalid)).is_err());
+   /// Disabled rules are not compiled
+   let disabled = Rule { disabled: true, ..invalid };
+   assert!(compile_rules(&[disabled]).unwrap().is_empty());
+}
+
+
+#[test]
+fn rules_run_on_new_mails() {
+   let (_guard, _root) = sessions_root(&["billy"]);
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+   let settings = SessionSettings {
+      rules: vec![
+         Rule {
+            name: "invoices".to_string(),
+            subject: Some("(?i)invoice".to_string()),
+            actions: vec![RuleAction::Label("invoices".to_string()), RuleAction::Acknowledge],
+            ..Default::default()
+         },
+         Rule {
+            name: "lunch".to_string(),
+            subject: Some("^Lunch".to_string()),
+            actions: vec![RuleAction::Trash],
+            ..Default::default()
+         },
+      ],
+      ..Default::default()
+   };
+   settings.save("billy").unwrap();
+   alex.send_mail(new_mail("Invoice 42", vec![&billy], vec![])).unwrap();
+   alex.send_mail(new_mail("Lunch?", vec![&billy], vec![])).unwrap();
+   alex.send_mail(new_mail("Invoice 43", vec![&billy], vec![])).unwrap();
+   let invoice = find_mail(&billy, "Invoice 42").unwrap();
+   let lunch = find_mail(&billy, "Lunch?").unwrap();
+   let old_invoice = find_mail(&billy, "Invoice 43").unwrap();
+
+   /// Only new mails are touched
+   let outcomes = apply_rules("billy", &billy, &[invoice.address.clone(), lunch.address.clone()]).unwrap();
+   assert_eq!(outcomes.len(), 3);
+   assert!(outcomes.iter().all(|outcome| matches!(outcome.result, Some(Ok(_)))));
+   let labels = MailLabels::load("billy").unwrap();
+   assert!(labels.labels_of(&invoice.address).unwrap().contains("invoices"));
+   assert!(labels.labels_of(&old_invoice.address).is_none());
+   assert_eq!(find_mail(&billy, "Invoice 42").unwrap().state, MailState::In(InMailState::AckDelivered));
+   assert_eq!(find_mail(&billy, "Invoice 43").unwrap().state, MailState::In(InMailState::Unacknowledged));
+   let trash = Trash::load("billy").unwrap();
+   assert!(trash.contains(&lunch.address));
+   assert!(!trash.contains(&invoice.address));
+
+   /// A failed action is reported
+   let outcomes = apply_rules("billy", &billy, &[invoice.address.clone()]).unwrap();
+   assert!(matches!(outcomes[1].result, Some(Err(_))));
+}
+
+
+#[test]
+fn attachments_never_overwrite() {
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+   let folder = tempfile::tempdir().unwrap();
+   let file_path = folder.path().join("notes.txt");
+   std::fs::write(&file_path, b"first").unwrap();
+   let first_ah = write_attachment(&alex, file_path.clone()).unwrap();
+   std::fs::write(&file_path, b"second").unwrap();
+   let second_ah = write_attachment(&alex, file_path).unwrap();
+   alex.send_mail(new_mail("two notes", vec![&billy], vec![first_ah, second_ah])).unwrap();
+   let inmail = find_mail(&billy, "two notes").unwrap();
+
+   /// Same name, different content: second file gets a number
+   let download_folder = mail_folder(&folder.path().join("downloads"), "{sender}/{date}", "al/ex", &inmail);
+   assert!(download_folder.ends_with(format!("al_ex/{}", chrono::Local::now().format("%Y-%m-%d"))));
+   let paths: Vec<_> = get_mail_attachments(&billy, &inmail.mail.attachments, &download_folder).unwrap()
+      .into_iter().map(|res| res.unwrap()).collect();
+   assert_eq!(paths, vec![download_folder.join("notes.txt"), download_folder.join("notes (2).txt")]);
+   assert_eq!(std::fs::read(&paths[1]).unwrap(), b"second");
+
+   /// Downloading again reuses the identical files
+   let again: Vec<_> = get_mail_attachments(&billy, &inmail.mail.attachments, &download_folder).unwrap()
+      .into_iter().map(|res| res.unwrap()).collect();
+   assert_eq!(again, paths);
+   assert_eq!(std::fs::read_dir(&download_folder).unwrap().count(), 2);
+}
@@ -0,0 +1,221 @@
+#![allow(non_upper_case_globals)]
+#![allow(unused_doc_comments)]
+#![allow(unused_attributes)]
+
+//! Generate a "wasm.rs" that embeds a whole snapmail DNA bundle.
+//!
+//! The DNA comes from a `.dna` bundle or is packed from integrity and coordinator zome wasms.
+//! The generated file records the DNA name, hashes, build time and source version,
+//! and is decoded back and compared before being written.
+//! Usable from the `rustify-wasm` binary or from a `build.rs` with [`rustify_blocking`].
+
+use holochain_types::dna::*;
+use holochain_types::prelude::ZomeName;
+use std::path::{Path, PathBuf};
+use yazi::*;
+
+/// Zome whose wasm hash is exposed as `SNAPMAIL_WASM_HASH` and checked at conductor startup
+pub const CHECKED_ZOME_NAME: &str = "snapmail";
+/// Origin time used when packing zome wasms, so the DNA hash only depends on the wasms
+pub const DEFAULT_ORIGIN_TIME: &str = "2022-01-01T00:00:00.000000Z";
+/// Environment variable read when no source version is given
+pub const SOURCE_VERSION_ENV_VAR: &str = "SNAPMAIL_SOURCE_VERSION";
+
+
+/// Where the DNA comes from
+#[derive(Debug, Clone)]
+pub enum DnaSource {
+   /// A `.dna` bundle
+   Bundle(PathBuf),
+   /// Zome wasms packed into a new bundle. Zomes are given as (name, path).
+   Zomes {
+      dna_name: String,
+      integrity: Vec<(String, PathBuf)>,
+      coordinator: Vec<(String, PathBuf)>,
+   },
+}
+
+/// Zome recorded in the generated file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZomeInfo {
+   pub name: String,
+   /// "integrity" or "coordinator"
+   pub kind: &'static str,
+   pub wasm_hash: String,
+}
+
+/// Metadata recorded in the generated file
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedDnaInfo {
+   pub dna_name: String,
+   /// DNA hash without network seed
+   pub dna_hash: String,
+   pub wasm_hash: String,
+   pub zomes: Vec<ZomeInfo>,
+   pub build_time: String,
+   pub source_version: String,
+   pub bundle_size: usize,
+   pub embedded_size: usize,
+}
+
+
+/// Read or pack the DNA bundle
+async fn load_bundle(source: &DnaSource) -> anyhow::Result<DnaBundle> {
+   match source {
+      DnaSource::Bundle(path) => {
+         println!("Loading DNA bundle: {:?}", path);
+         Ok(DnaBundle::read_from_file(path).await?)
+      },
+      DnaSource::Zomes { dna_name, integrity, coordinator } => {
+         if integrity.is_empty() || coordinator.is_empty() {
+            anyhow::bail!("At least one integrity zome and one coordinator zome are required");
+         }
+         /// Every coordinator zome depends on all integrity zomes
+         let mut yaml = format!("manifest_version: \"1\"\nname: {}\nintegrity:\n  origin_time: {}\n  zomes:\n", dna_name, DEFAULT_ORIGIN_TIME);
+         for (name, _) in integrity.iter() {
+            yaml += &format!("    - name: {}\n      bundled: {}.wasm\n", name, name);
+         }
+         yaml += "coordinator:\n  zomes:\n";
+         for (name, _) in coordinator.iter() {
+            yaml += &format!("    - name: {}\n      bundled: {}.wasm\n      dependencies:\n", name, name);
+            for (dep, _) in integrity.iter() {
+               yaml += &format!("        - name: {}\n", dep);
+            }
+         }
+         let manifest: DnaManifest = serde_yaml::from_str(&yaml)?;
+         let mut resources = Vec::new();
+         for (name, path) in integrity.iter().chain(coordinator.iter()) {
+            println!("Loading {} zome wasm: {:?}", name, path);
+            resources.push((PathBuf::from(format!("{}.wasm", name)), std::fs::read(path)?.into()));
+         }
+         Ok(DnaBundle::new(manifest.try_into()?, resources, std::env::current_dir()?)?)
+      },
+   }
+}
+
+/// Metadata of a bundle, computed from the DnaFile it produces
+async fn bundle_info(bundle: DnaBundle) -> anyhow::Result<(String, String, Vec<ZomeInfo>)> {
+   let (dna_file, dna_hash) = bundle.into_dna_file(None, None).await?;
+   let def = dna_file.dna_def();
+   let mut zomes = Vec::new();
+   for (name, _) in def.integrity_zomes.iter() {
+      zomes.push(zome_info(&dna_file, name, "integrity").await?);
+   }
+   for (name, _) in def.coordinator_zomes.iter() {
+      zomes.push(zome_info(&dna_file, name, "coordinator").await?);
+   }
+   Ok((def.name.clone(), dna_hash.to_string(), zomes))
+}
+
+///
+async fn zome_info(dna_file: &DnaFile, name: &ZomeName, kind: &'static str) -> anyhow::Result<ZomeInfo> {
+   let wasm = dna_file.get_wasm_for_zome(name)?;
+   let wasm_hash = holo_hash::WasmHash::with_data(wasm).await;
+   Ok(ZomeInfo { name: name.to_string(), kind, wasm_hash: wasm_hash.to_string() })
+}
+
+/// Source version given, or from the environment, or from git
+fn resolve_source_version(maybe_version: Option<String>) -> String {
+   if let Some(version) = maybe_version {
+      return version;
+   }
+   if let Ok(version) = std::env::var(SOURCE_VERSION_ENV_VAR) {
+      return version;
+   }
+   std::process::Command::new("git")
+      .args(["describe", "--always", "--dirty"])
+      .output()
+      .ok()
+      .filter(|output| output.status.success())
+      .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+      .unwrap_or("unknown".to_string())
+}
+
+
+/// Generate the Rust file embedding the DNA at `output`
+pub async fn rustify(source: &DnaSource, output: &Path, maybe_source_version: Option<String>) -> anyhow::Result<EmbeddedDnaInfo> {
+   /// Load and describe DNA
+   let bundle = load_bundle(source).await?;
+   let bundle_bytes = bundle.encode()?;
+   let (dna_name, dna_hash, zomes) = bundle_info(bundle).await?;
+   let wasm_hash = zomes.iter()
+      .find(|zome| zome.name == CHECKED_ZOME_NAME)
+      .map(|zome| zome.wasm_hash.clone())
+      .ok_or_else(|| anyhow::anyhow!("DNA has no \"{}\" zome", CHECKED_ZOME_NAME))?;
+   /// Compress
+   let compressed = compress(&bundle_bytes, Format::Zlib, CompressionLevel::BestSize)
+      .map_err(|e| anyhow::anyhow!("Compression failed: {:?}", e))?;
+   let dna_b64 = base64::encode_config(&compressed, base64::URL_SAFE_NO_PAD);
+   let info = EmbeddedDnaInfo {
+      dna_name,
+      dna_hash,
+      wasm_hash,
+      zomes,
+      build_time: chrono::Utc::now().to_rfc3339(),
+      source_version: resolve_source_version(maybe_source_version),
+      bundle_size: bundle_bytes.len(),
+      embedded_size: dna_b64.len(),
+   };
+
+   /// Verify round-trip before writing
+   let decoded = decode_embedded(&dna_b64)?;
+   if decoded != bundle_bytes {
+      anyhow::bail!("Round-trip failed: decoded bundle differs from source bundle");
+   }
+   let (_, decoded_hash, decoded_zomes) = bundle_info(DnaBundle::decode(&decoded)?).await?;
+   if decoded_hash != info.dna_hash || decoded_zomes != info.zomes {
+      anyhow::bail!("Round-trip failed: decoded DNA {} differs from source DNA {}", decoded_hash, info.dna_hash);
+   }
+
+   /// Write
+   std::fs::write(output, generate_rust(&info, &dna_b64).as_bytes())?;
+   Ok(info)
+}
+
+/// [`rustify`] for callers without an async runtime, like a `build.rs`
+pub fn rustify_blocking(source: &DnaSource, output: &Path, maybe_source_version: Option<String>) -> anyhow::Result<EmbeddedDnaInfo> {
+   tokio::runtime::Runtime::new()?.block_on(rustify(source, output, maybe_source_version))
+}
+
+/// Bundle bytes from the base64 string of a generated file
+pub fn decode_embedded(dna_b64: &str) -> anyhow::Result<Vec<u8>> {
+   let compressed = base64::decode_config(dna_b64, base64::URL_SAFE_NO_PAD)?;
+   let (decompressed, _checksum) = decompress(&compressed, Format::Zlib)
+      .map_err(|e| anyhow::anyhow!("Decompression failed: {:?}", e))?;
+   Ok(decompressed)
+}
+
+/// Content of the generated file
+fn generate_rust(info: &EmbeddedDnaInfo, dna_b64: &str) -> String {
+   let mut zomes_str = String::new();
+   for zome in info.zomes.iter() {
+      zomes_str += &format!("   EmbeddedZome {{ name: {:?}, kind: {:?}, wasm_hash: {:?} }},\n", zome.name, zome.kind, zome.wasm_hash);
+   }
+   format!(
+"// Generated by rustify-wasm. Do not edit.
+
+/// Zome of the embedded DNA
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedZome {{
+   pub name: &'static str,
+   /// \"integrity\" or \"coordinator\"
+   pub kind: &'static str,
+   pub wasm_hash: &'static str,
+}}
+
+pub const SNAPMAIL_DNA_NAME: &str = {:?};
+/// DNA hash without network seed
+pub const SNAPMAIL_DNA_HASH: &str = {:?};
+/// Wasm hash of the \"{}\" zome
+pub const SNAPMAIL_WASM_HASH: &str = {:?};
+pub const SNAPMAIL_DNA_ZOMES: &[EmbeddedZome] = &[
+{}];
+pub const SNAPMAIL_DNA_BUILD_TIME: &str = {:?};
+pub const SNAPMAIL_DNA_SOURCE_VERSION: &str = {:?};
+/// Zlib compressed DNA bundle, as URL safe base64
+pub const DNA_BUNDLE_B64: &str = {:?};
+",
+      info.dna_name, info.dna_hash, CHECKED_ZOME_NAME, info.wasm_hash, zomes_str,
+      info.build_time, info.source_version, dna_b64,
+   )
+}
@@ -2,49 +2,80 @@
 #![allow(unused_doc_comments)]
 #![allow(unused_attributes)]
 
-use yazi::*;
-use holochain_types::dna::wasm::DnaWasm;
-//use std::hash::Hash;
-
-pub const DEFAULT_WASM_PATH: &str = "/home/ddd/github/snapmail-rsm/target/wasm32-unknown-unknown/release/snapmail.wasm";
-//pub const DNA_PATH: &str        = "/home/ddd/github/snapmail-cli/dna/snapmail.dna";
-//pub const DNA_PATH: &str        = "~/github/snapmail-cli/dna/snapmail.dna";
+use rustify_wasm::*;
+use std::path::PathBuf;
 
 pub const RUST_OUTPUT: &str = "./crates/common/src/wasm.rs";
 
+static USAGE_TEXT: &str = "USAGE:
+    rustify-wasm <dna-file>
+    rustify-wasm [--name <dna-name>] --integrity <name>=<wasm> --coordinator <name>=<wasm>...
+
+OPTIONS:
+    --integrity <name>=<wasm>      Integrity zome wasm. Can be repeated.
+    --coordinator <name>=<wasm>    Coordinator zome wasm. Can be repeated.
+    --name <dna-name>              DNA name when packing zome wasms [default: snapmail]
+    --source-version <version>     Version of the zome sources. Defaults to $SNAPMAIL_SOURCE_VERSION or git describe.
+    --output <path>                Generated file [default: ./crates/common/src/wasm.rs]
+    ";
+
+/// Parse "<name>=<path>"
+fn parse_zome(arg: Option<String>) -> anyhow::Result<(String, PathBuf)> {
+   let arg = arg.ok_or_else(|| anyhow::anyhow!("Missing zome after flag"))?;
+   let (name, path) = arg.split_once('=')
+      .ok_or_else(|| anyhow::anyhow!("Zome must be given as <name>=<wasm>: \"{}\"", arg))?;
+   Ok((name.to_string(), PathBuf::from(path)))
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-
-   // Prints each argument on a separate line
-   // for argument in std::env::args() {
-   //    println!("{}", argument);
-   // }
+   /// Parse args
    let mut args = std::env::args();
    args.next(); // skip exe
-   let wasm_path = if let Some(arg) = args.next() {
-      arg
-   } else {
-      DEFAULT_WASM_PATH.to_string()
+   let mut maybe_bundle = None;
+   let mut dna_name = "snapmail".to_string();
+   let mut integrity = Vec::new();
+   let mut coordinator = Vec::new();
+   let mut maybe_source_version = None;
+   let mut output = PathBuf::from(RUST_OUTPUT);
+   while let Some(arg) = args.next() {
+      match arg.as_str() {
+         "--integrity" => integrity.push(parse_zome(args.next())?),
+         "--coordinator" => coordinator.push(parse_zome(args.next())?),
+         "--name" => dna_name = args.next().unwrap_or(dna_name),
+         "--source-version" => maybe_source_version = args.next(),
+         "--output" => output = args.next().map(PathBuf::from).unwrap_or(output),
+         "-h" | "--help" => {
+            println!("{}", USAGE_TEXT);
+            return Ok(());
+         },
+         _ if arg.starts_with('-') => anyhow::bail!("Unknown option \"{}\"\n\n{}", arg, USAGE_TEXT),
+         _ => {
+            if let Some(bundle) = &maybe_bundle {
+               anyhow::bail!("Only one DNA file can be given: {:?} and \"{}\"\n\n{}", bundle, arg, USAGE_TEXT);
+            }
+            maybe_bundle = Some(PathBuf::from(arg));
+         },
+      }
+   }
+   let source = match maybe_bundle {
+      Some(path) => DnaSource::Bundle(path),
+      None if !integrity.is_empty() => DnaSource::Zomes { dna_name, integrity, coordinator },
+      None => {
+         println!("No DNA given.\n\n{}", USAGE_TEXT);
+         return Ok(());
+      },
    };
 
-   /// Load DnaFile
-   println!("Loading DNA wasm file: {}", wasm_path);
-   let wasm = &std::fs::read(wasm_path)?;
-   let dna_wasm = DnaWasm::from(wasm.to_vec());
-   let (_, wasm_hash) = holochain_types::dna::wasm::DnaWasmHashed::from_content(dna_wasm.clone())
-      .await
-      .into_inner();
-   let compressed = compress(wasm, Format::Zlib, CompressionLevel::BestSize).unwrap();
-   let compressed_len = compressed.len();
-   let chunk_b64 = base64::encode_config(compressed, base64::URL_SAFE_NO_PAD);
-   //let wasm_str = format!("pub const DNA_WASM: [u8 ; {}] = {:?};\n", compressed.len(), compressed);
-   let wasm_str = format!("pub const SNAPMAIL_WASM_HASH: &str = \"{}\";\npub const DNA_WASM_B64: &str = {:?};\n", wasm_hash, chunk_b64);
-
+   /// Generate
+   let info = rustify(&source, &output, maybe_source_version).await?;
    let path = std::env::current_dir()?;
-   println!("writing Rust file at: {:?}", path.join(std::path::Path::new(RUST_OUTPUT)));
-   std::fs::write(RUST_OUTPUT, wasm_str.as_bytes())?;
-   println!("Wrote file wasm.rs ({} KiB)", wasm_str.len() / 1024);
-   println!("Size: {} KiB => {} KiB => {} KiB", wasm.len() / 1024, compressed_len / 1024, chunk_b64.len() / 1024);
-   println!("Wasm Hash = {}", wasm_hash);
+   println!("Wrote Rust file at: {:?}", path.join(&output));
+   println!("DNA \"{}\" = {} (source {}, built {})", info.dna_name, info.dna_hash, info.source_version, info.build_time);
+   for zome in info.zomes.iter() {
+      println!(" - {} zome \"{}\" = {}", zome.kind, zome.name, zome.wasm_hash);
+   }
+   println!("Size: {} KiB => {} KiB", info.bundle_size / 1024, info.embedded_size / 1024);
+   println!("Wasm Hash = {}", info.wasm_hash);
    Ok(())
 }
@@ -3,9 +3,25 @@
 
 use snapmail_common::{
    utils::*,
+   api::SnapmailApi,
    globals::*,
    conductor::*,
    attachment::*,
+   logging::*,
+   settings::SessionSettings,
+   doctor::*,
+   session::resolve_sid,
+   networks::network_cell,
+   peers::*,
+   presence::PresenceTracker,
+   shutdown::graceful_shutdown,
+   trash::*,
+   labels::{MailLabels, MailQuery},
+   read_flags::{ReadFlags, mark_unread},
+   blocklist::*,
+   rules::run_rules,
+   auto_download::*,
+   wasm,
 };
 
 use crate::{
@@ -13,13 +29,21 @@ use crate::{
    subcommands::*,
 };
 
-use snapmail::handle::*;
 use std::path::PathBuf;
+use std::sync::Arc;
 use structopt::StructOpt;
-use snapmail::mail::*;
 use holochain_types::dna::*;
-use snapmail::pub_enc_key::snapmail_get_enc_key;
 
+/// Commands of the CLI
+#[derive(StructOpt, Debug)]
+pub enum CliCommand {
+   /// List, rename, clone sessions or set the default session
+   Sessions(SessionsCommand),
+   #[structopt(flatten)]
+   Session(SnapSubcommand),
+}
+
+/// Commands on a session
 #[derive(StructOpt, Debug)]
 pub enum SnapSubcommand {
    /// Create agent and config
@@ -28,6 +52,8 @@ pub enum SnapSubcommand {
    Info,
    /// Modify the setup
    Change(ChangeCommand),
+   /// Get or change the session's user settings
+   Config(ConfigCommand),
    /// Change agent's handle
    SetHandle {
       /// New handle name to use for this agent
@@ -37,6 +63,8 @@ pub enum SnapSubcommand {
    GetHandle,
    /// Erase a session from disk
    Clear,
+   /// Check every part of a session and suggest fixes
+   Doctor,
    /// Check if a user is currently online
    Ping {
       #[structopt(name = "name", short, long)]
@@ -48,12 +76,18 @@ pub enum SnapSubcommand {
    },
    /// Query the DHT for all relevant data (handles, mailbox, ackbox)
    Pull,
-   /// Display the source chain in terminal
-   Chain,
+   /// Display the source chain in terminal, with filters, entry decoding, JSON export and diff
+   Chain(ChainCommand),
    /// Check outmails and resend non acknowledged ones
    Resend,
    /// Display all users part of the current network
-   Directory,
+   Directory {
+      #[structopt(long)]
+      /// Ping every user and show whether they are online, or when they were last seen
+      presence: bool,
+   },
+   /// List agents known on the network and check the bootstrap and proxy services
+   Peers,
    /// Get a users public encryption key
    Key {
       /// handle of agent to get the key of
@@ -62,13 +96,48 @@ pub enum SnapSubcommand {
    /// Send a mail to another agent
    Send(SendCommand),
    /// List all mails received by this agent
-   List,
-   /// List sessions that have been setup on this computer
-   ListSessions,
-   /// Read mail from mailbox (Will send an acknowledgement to mail author)
+   List {
+      #[structopt(long)]
+      /// Only mails with this label
+      label: Option<String>,
+      #[structopt(long)]
+      /// Only mails matching this saved folder's query
+      folder: Option<String>,
+      #[structopt(long)]
+      /// Only mails flagged as spam
+      spam: bool,
+   },
+   /// Attach local labels to mails and save folders as queries
+   Label(LabelCommand),
+   /// List, restore or purge trashed mails
+   Trash(TrashCommand),
+   /// Block a sender or a handle pattern: its mails are hidden and never acknowledged. Lists the blocklist without argument.
+   Block(BlockTarget),
+   /// Unblock a sender or a handle pattern. Unblocked senders are never flagged as spam.
+   Unblock(BlockTarget),
+   /// List the inbox rules of the settings file, or dry-run them on a mail
+   Rules(RulesCommand),
+   /// List, join or leave the snapmail networks of the session
+   Networks(NetworksCommand),
+   /// Move a network's mails to the DNA of this release, keeping the old cell as a readable archive
+   Migrate(MigrateCommand),
+   /// Read mail from mailbox. Sends an acknowledgement to the mail author if the ack policy allows it.
    Open {
       /// Hash of the mail to open
       hash: String,
+      #[structopt(long)]
+      /// Never acknowledge, whatever the ack policy
+      no_ack: bool,
+   },
+   /// Send an acknowledgement of a received mail to its author
+   Ack {
+      /// Hash of the mail to acknowledge
+      hash: String,
+   },
+   /// Flag a mail as unread. Does not affect its acknowledgement.
+   Unread {
+      /// Hash of the mail
+      hash: String,
    },
    Status {
       /// Hash of the mail to get state
@@ -79,18 +148,38 @@ pub enum SnapSubcommand {
       /// Hash of the attachment to extract
       hash: String,
    },
+   /// Extract the attachments of a mail
+   GetAttachments {
+      /// Hash of the mail
+      hash: String,
+      #[structopt(long, required_unless = "index")]
+      /// Extract all attachments
+      all: bool,
+      #[structopt(short, long)]
+      /// Number of an attachment to extract, starting at 1. Can be repeated.
+      index: Vec<usize>,
+      #[structopt(long)]
+      /// Extract into <download folder>/<sender>/<date>/, as the auto-download does
+      by_sender: bool,
+   },
    /// Launch an "always on" conductor that displays events & signals
    Listen {
       #[structopt(short, long)]
       /// Interval in seconds between each status print
       tick: Option<u32>,
+      #[structopt(name = "metrics", long)]
+      /// Expose Prometheus metrics at http://<address>/metrics (ex: 127.0.0.1:9100)
+      maybe_metrics_addr: Option<std::net::SocketAddr>,
    },
 }
 
 impl SnapSubcommand {
    /// Run this command
-   pub async fn run(self, sid: PathBuf) -> anyhow::Result<()> {
+   pub async fn run(self, sid: PathBuf, maybe_network: Option<String>) -> anyhow::Result<()> {
       let sid_str = sid.to_string_lossy().to_string();
+      /// Only the commands using settings load them, so a broken settings file does not block the others
+      let load_settings = || SessionSettings::load(&sid_str);
+      let network = maybe_network.as_deref();
 
       match self {
          Self::Setup(cmd)=> {
@@ -99,7 +188,7 @@ impl SnapSubcommand {
          },
          Self::Info => {
             msg!("{} Info:", sid_str);
-            let path = CONFIG_PATH.as_path().join(sid);
+            let path = config_path().join(sid);
             let config_filepath = path.join(CONDUCTOR_CONFIG_FILENAME);
             let app_filepath = path.join(APP_CONFIG_FILENAME);
             let uid = std::fs::read_to_string(app_filepath)
@@ -110,49 +199,95 @@ impl SnapSubcommand {
             let s = std::fs::read_to_string(config_filepath)
                .expect("Something went wrong reading CONDUCTOR CONFIG file");
             msg!("config:\n {}", s);
+            msg!("embedded DNA: \"{}\" {}", wasm::SNAPMAIL_DNA_NAME, wasm::SNAPMAIL_DNA_HASH);
+            msg!(" - source version: {}", wasm::SNAPMAIL_DNA_SOURCE_VERSION);
+            msg!(" - build time: {}", wasm::SNAPMAIL_DNA_BUILD_TIME);
+            for zome in wasm::SNAPMAIL_DNA_ZOMES.iter() {
+               msg!(" - {} zome \"{}\": {}", zome.kind, zome.name, zome.wasm_hash);
+            }
 
          },
          Self::Change(cmd) => {
             msg!("Change!");
-            cmd.run(sid);
+            cmd.run(sid)?;
          },
-         Self::ListSessions => {
-            msg!("ListSessions: ");
-            let root = CONFIG_PATH.as_path().to_path_buf();
-            let paths = std::fs::read_dir(root)
-               .expect("Missing config folder");
-            for path in paths {
-               msg!(" - {}", path.unwrap().path().display())
-            }
+         Self::Config(cmd) => {
+            cmd.run(sid)?;
+         },
+         Self::Networks(cmd) => {
+            let settings = load_settings()?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            cmd.run(conductor, &sid_str, settings.timeouts.shutdown_max_wait()).await?;
+         },
+         Self::Migrate(cmd) => {
+            let settings = load_settings()?;
+            /// Not `start_conductor_or_abort()` as it aborts on outdated DNAs
+            let conductor = start_conductor(sid_str.clone()).await;
+            cmd.run(conductor, &sid_str, network, settings.timeouts.shutdown_max_wait()).await?;
          },
          Self::Clear => { msg!("Clearing..."); clear(sid); },
-         Self::Listen {tick } => {
+         Self::Doctor => {
+            msg!("Diagnosing session \"{}\"...", sid_str);
+            let findings = diagnose(&sid_str).await;
+            let mut problem_count = 0;
+            for finding in findings.iter() {
+               let status = match finding.status {
+                  FindingStatus::Ok => "  OK ",
+                  FindingStatus::Warning => { problem_count += 1; "WARN " },
+                  FindingStatus::Error => { problem_count += 1; "ERROR" },
+                  FindingStatus::Skipped => "SKIP ",
+               };
+               msg!("[{}] {:<16} {}", status, finding.check, finding.detail);
+               if let Some(fix) = &finding.maybe_fix {
+                  msg!("        {:<16} -> {}", "", fix);
+               }
+            }
+            if problem_count > 0 {
+               return Err(anyhow::anyhow!("{} problem(s) found", problem_count));
+            }
+            msg!("No problem found");
+         },
+         Self::Listen {tick, maybe_metrics_addr } => {
+            let settings = load_settings()?;
             msg!("Listening forever:");
-            let conductor = start_conductor(sid_str).await;
-            listen(conductor, tick.unwrap_or(10) as u64).await?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            let tick = tick.map(|t| t as u64).unwrap_or(settings.timeouts.listen_tick_secs);
+            listen(&sid_str, cell, tick, maybe_metrics_addr).await?;
          },
          Self::Send(cmd) => {
+            let settings = load_settings()?;
             msg!("Send!");
-            let conductor = start_conductor(sid_str).await;
-            cmd.run(conductor).await?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            let sent_ah = cmd.run(&cell, &settings).await?;
+            let report = graceful_shutdown(cell.conductor.clone(), &sid_str, settings.timeouts.shutdown_max_wait()).await;
+            if report.pending.unsent_outmails.contains(&sent_ah) {
+               msg!("Mail not handed off yet. It will be with the next \"resend\".");
+            }
          },
          Self::SetHandle {handle } => {
+            let settings = load_settings()?;
             msg!("** Set handle: {}", handle);
-            let conductor = start_conductor(sid_str).await;
-            let hash = snapmail_set_handle(conductor, handle)?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            let hash = cell.set_handle(handle)?;
             msg!(" - {:?}", hash);
+            graceful_shutdown(cell.conductor.clone(), &sid_str, settings.timeouts.shutdown_max_wait()).await;
          },
          Self::GetHandle => {
             msg!("** Get handle: ");
             let conductor = start_conductor(sid_str.clone()).await;
-            let handle = snapmail_get_my_handle(conductor, ())?;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            let handle = cell.get_my_handle()?;
             msg!("** Active handle for session {} : \"{}\"", sid_str, handle);
          },
 
          Self::Ping { maybe_name, maybe_agent_id } => {
             msg!("Ping...");
-            let conductor = start_conductor(sid_str).await;
-            let handle_list = snapmail_get_all_handles(conductor.clone(), ())?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            let handle_list = cell.get_all_handles()?;
             let maybe_key = if let Some(name) = maybe_name {
                get_agent_id(&handle_list, &name)
             } else {
@@ -162,7 +297,7 @@ impl SnapSubcommand {
 
             };
             if let Some(key) = maybe_key {
-               let res = snapmail_ping_agent(conductor, key);
+               let res = cell.ping_agent(key);
                match res {
                   Ok(ponged) => msg!(" - {:?}", ponged),
                   Err(err) => err_msg!(" - Failed: {:?}", err),
@@ -171,82 +306,263 @@ impl SnapSubcommand {
                err_msg!(" - Unknown agent");
             }
          },
-         Self::Open { hash } => {
+         Self::Open { hash, no_ack } => {
+            let settings = load_settings()?;
             msg!("Open...");
             let ah: ActionHash = stoh(hash);
-            open(sid_str, ah).await?;
+            open(sid_str, network, ah, &settings, no_ack).await?;
+         },
+         Self::Ack { hash } => {
+            let settings = load_settings()?;
+            let ah: ActionHash = stoh(hash);
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            if !may_acknowledge(&sid_str, &cell, &ah)? {
+               anyhow::bail!("Mail {} is from a blocked sender. Unblock it first.", ah);
+            }
+            let res = acknowledge(&cell, ah);
+            graceful_shutdown(cell.conductor.clone(), &sid_str, settings.timeouts.shutdown_max_wait()).await;
+            res?;
+         },
+         Self::Unread { hash } => {
+            let ah: ActionHash = stoh(hash);
+            if mark_unread(&sid_str, &ah)? {
+               msg!("Mail {} flagged as unread", ah);
+            } else {
+               msg!("Mail {} is already unread", ah);
+            }
          },
          Self::Status { hash } => {
             msg!("Getting Mail Status...");
             let ah: ActionHash = stoh(hash);
-            get_status(sid_str, ah).await?;
+            get_status(sid_str, network, ah).await?;
          },
          Self::GetAttachment { hash } => {
+            let settings = load_settings()?;
             msg!("GetAttachment...");
             let eh: EntryHash = stoh(hash);
             //let uid_str = uid.to_string_lossy().to_string();
-            let conductor = start_conductor(sid_str).await;
-            let path = get_attachment(conductor, eh, std::env::current_dir().unwrap())?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            let path = get_attachment(&cell, eh, settings.download_folder())?;
             msg!("File writen at: {:?}", path);
          },
-         Self::Directory => {
+         Self::GetAttachments { hash, all, index, by_sender } => {
+            let settings = load_settings()?;
+            let ah: ActionHash = stoh(hash);
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            let item = cell.get_all_mails()?.into_iter()
+               .find(|item| item.address == ah)
+               .ok_or_else(|| anyhow::anyhow!("Unknown mail {}", ah))?;
+            let attachments: Vec<_> = if all {
+               item.mail.attachments.clone()
+            } else {
+               let mut selected = Vec::new();
+               for i in index.iter() {
+                  let attachment = i.checked_sub(1).and_then(|i| item.mail.attachments.get(i))
+                     .ok_or_else(|| anyhow::anyhow!("No attachment {}. Mail has {} attachment(s).", i, item.mail.attachments.len()))?;
+                  selected.push(attachment.clone());
+               }
+               selected
+            };
+            let folder = if by_sender {
+               let sender = get_name(&cell.get_all_handles()?, &item.author).unwrap_or_else(|| item.author.to_string());
+               mail_folder(&settings.download_folder(), &settings.auto_download.folder_template, &sender, &item)
+            } else {
+               settings.download_folder()
+            };
+            let mut failure_count = 0;
+            for (attachment, res) in attachments.iter().zip(get_mail_attachments(&cell, &attachments, &folder)?) {
+               match res {
+                  Ok(path) => msg!(" - {} -> {:?}", attachment.filename, path),
+                  Err(e) => {
+                     err_msg!(" - {} FAILED: {}", attachment.filename, e);
+                     failure_count += 1;
+                  },
+               }
+            }
+            if failure_count > 0 {
+               anyhow::bail!("{} of {} attachment(s) failed", failure_count, attachments.len());
+            }
+         },
+         Self::Directory { presence } => {
             msg!("Directory...");
-            let conductor = start_conductor(sid_str).await;
-            let handle_list = snapmail_get_all_handles(conductor, ())?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            let handle_list = cell.get_all_handles()?;
+            if !presence {
+               for handle_item in handle_list.iter() {
+                  msg!(" - {} - {}", handle_item.name, handle_item.agentId);
+               }
+               return Ok(());
+            }
+            let tracker = PresenceTracker::load(&sid_str, Arc::new(cell.clone()));
+            tracker.sweep_contacts().await?;
             for handle_item in handle_list.iter() {
-               msg!(" - {} - {}", handle_item.name, handle_item.agentId);
+               let label = if handle_item.agentId == *cell.agent_pubkey() {
+                  "me".to_string()
+               } else {
+                  tracker.get(&handle_item.agentId).label()
+               };
+               msg!(" - {:<12} {:<16} {}", label, handle_item.name, handle_item.agentId);
+            }
+         },
+         Self::Peers => {
+            msg!("Peers...");
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            let peers = list_peers(&cell).await?;
+            msg!(" {} agent(s) known:", peers.len());
+            for peer in peers.iter() {
+               let me_str = if peer.is_me { " (me)" } else { "" };
+               let expired_str = if peer.is_expired() { " EXPIRED" } else { "" };
+               msg!(" - {}{} - {}", peer.handle.as_deref().unwrap_or("<unknown>"), me_str, peer.agent);
+               msg!("   storage arc: {}", peer.storage_arc);
+               msg!("     signed at: {}", peer.signed_at.format("%Y-%m-%d %H:%M:%S"));
+               msg!("    expires at: {}{}", peer.expires_at.format("%Y-%m-%d %H:%M:%S"), expired_str);
+               for url in peer.urls.iter() {
+                  msg!("           url: {}", url);
+               }
+            }
+            msg!(" Network services:");
+            let checks = check_services(&sid_str).await?;
+            for check in checks.iter() {
+               let status = if check.is_ok { " OK " } else { "FAIL" };
+               msg!(" [{}] {:<9} {} {}", status, check.service, check.url.as_deref().unwrap_or("-"), check.message);
+            }
+            if peers.iter().all(|peer| peer.is_me) {
+               if checks.iter().any(|check| !check.is_ok) {
+                  msg!("No other agent known: this node is isolated. Fix the failing service(s) with \"change\".");
+               } else {
+                  msg!("No other agent known yet. Other agents may be offline, or on another network seed.");
+               }
             }
          },
          Self::Key {handle} => {
             msg!("** Getting key of: {}", handle);
-            let conductor = start_conductor(sid_str).await;
-            let agent_list = snapmail_find_agent(conductor.clone(), handle)?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            let agent_list = cell.find_agent(handle)?;
             if agent_list.len() == 0 {
                msg!("No agent found for that handle");
                return Ok(());
             }
             for agent_id in agent_list.iter() {
-               let enc_key = snapmail_get_enc_key(conductor.clone(), agent_id.clone())?;
+               let enc_key = cell.get_enc_key(agent_id.clone())?;
                msg!(" - {} : {:?}", agent_id, enc_key);
             }
          }
-         Self::List => {
+         Self::List { label, folder, spam } => {
+            let settings = load_settings()?;
             msg!("List inbox...");
-            let conductor = start_conductor(sid_str).await;
-            let all_mail_list = snapmail_get_all_mails(conductor.clone(), ())?;
-            let handle_list = snapmail_get_all_handles(conductor.clone(), ())?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            let purged = apply_retention_setting(&sid_str, &cell)?;
+            let deleted = apply_block_action(&sid_str, &cell)?;
+            let classes = classify_network_mails(&sid_str, &cell)?;
+            let trash = Trash::load(&sid_str)?;
+            let labels = MailLabels::load(&sid_str)?;
+            let read_flags = ReadFlags::load(&sid_str)?;
+            let mut query = match folder {
+               Some(name) => labels.folder(&name)?.query.clone(),
+               None => MailQuery::default(),
+            };
+            if label.is_some() {
+               query.label = label;
+            }
+            let handle_list = cell.get_all_handles()?;
+            let all_mail_list: Vec<_> = cell.get_all_mails()?.into_iter()
+               .filter(|item| !is_purged(item) && !trash.contains(&item.address))
+               .filter(|item| {
+                  let class = classes.get(&item.address).copied().unwrap_or(MailClass::Normal);
+                  if spam { class == MailClass::Spam } else { class == MailClass::Normal }
+               })
+               .filter(|item| {
+                  let sender = handle_list.iter().find(|handle_item| handle_item.agentId == item.author).map(|handle_item| handle_item.name.as_str());
+                  query.matches(item, sender, labels.labels_of(&item.address))
+               })
+               .collect();
             msg!(" {} mail(s) found:", all_mail_list.len());
             for item in all_mail_list.iter() {
                let username = get_name(&handle_list, &item.author)
                   .expect("DHT incoherent state as author's handle should publicly available");
-               msg!("- {:?} | {} | {} | {}", item.state, username, item.mail.subject, item.address);
+               let read_str = if read_flags.is_read(item) { " " } else { "*" };
+               msg!("-{} {:?} | {} | {} | {}", read_str, item.state, username, item.mail.subject, item.address);
+            }
+            let _ = cell.peer_count();
+            /// Publish the deletes of expired trashed mails and of blocked senders' mails
+            if !purged.is_empty() || !deleted.is_empty() {
+               graceful_shutdown(cell.conductor.clone(), &sid_str, settings.timeouts.shutdown_max_wait()).await;
             }
-            dump_state(conductor);
          },
          Self::Pull => {
+            let settings = load_settings()?;
             msg!("Pull...");
-            let conductor = start_conductor(sid_str).await;
-            let handle_list = snapmail_get_all_handles(conductor.clone(), ())?;
-            let new_ack_list = snapmail_check_ack_inbox(conductor.clone(), ())?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            let handle_list = cell.get_all_handles()?;
+            let new_ack_list = cell.check_ack_inbox()?;
             msg!(" -  New Acks: {}", new_ack_list.len());
-            let new_mail_list = snapmail_check_mail_inbox(conductor.clone(), ())?;
+            let new_mail_list = cell.check_mail_inbox()?;
             msg!(" - New Mails: {}", new_mail_list.len());
             for mail_item in new_mail_list.iter() {
                msg!(" - {:?}", mail_item);
             }
+            let outcomes = run_rules(&sid_str, &cell, &new_mail_list);
+            let _ = run_auto_download(&sid_str, &cell, &new_mail_list);
             msg!(" -   Handles: {}", handle_list.len());
-            let all_mail_list = snapmail_get_all_mails(conductor.clone(), ())?;
+            let all_mail_list = cell.get_all_mails()?;
             msg!(" - All Mails: {}", all_mail_list.len());
+            /// Rules may have acknowledged or forwarded mails
+            if !outcomes.is_empty() {
+               graceful_shutdown(cell.conductor.clone(), &sid_str, settings.timeouts.shutdown_max_wait()).await;
+            }
          },
-         Self::Chain => {
-            let conductor = start_conductor(sid_str).await;
-            print_chain(conductor).await;
+         Self::Label(cmd) => {
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            cmd.run(&sid_str, &cell)?;
+         },
+         Self::Trash(cmd) => {
+            let settings = load_settings()?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            cmd.run(&sid_str, &cell)?;
+            graceful_shutdown(cell.conductor.clone(), &sid_str, settings.timeouts.shutdown_max_wait()).await;
+         },
+         Self::Block(target) => {
+            let settings = load_settings()?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            target.block(&sid_str, &cell)?;
+            graceful_shutdown(cell.conductor.clone(), &sid_str, settings.timeouts.shutdown_max_wait()).await;
+         },
+         Self::Unblock(target) => {
+            let settings = load_settings()?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            target.unblock(&sid_str, &cell)?;
+            graceful_shutdown(cell.conductor.clone(), &sid_str, settings.timeouts.shutdown_max_wait()).await;
+         },
+         Self::Rules(cmd) => {
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            cmd.run(&sid_str, &cell)?;
+         },
+         Self::Chain(cmd) => {
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            cmd.run(&cell).await?;
          },
          Self::Resend => {
-            let conductor = start_conductor(sid_str).await;
-            let _ = snapmail_resend_outmails(conductor.clone(), ())?;
-            let _ = snapmail_resend_outacks(conductor.clone(), ())?;
-            let _ = snapmail_request_acks(conductor.clone(), ())?;
+            let settings = load_settings()?;
+            let conductor = start_conductor(sid_str.clone()).await;
+            let cell = network_cell(conductor, &sid_str, network)?;
+            let _ = cell.resend_outmails()?;
+            let _ = cell.resend_outacks()?;
+            let _ = cell.request_acks()?;
+            graceful_shutdown(cell.conductor.clone(), &sid_str, settings.timeouts.shutdown_max_wait()).await;
          }
       }
       Ok(())
@@ -259,16 +575,79 @@ impl SnapSubcommand {
 #[structopt(name = "snapmail-cli", about = "Command line interface for Snapmail DNA")]
 pub struct SnapCli {
    #[structopt(parse(from_os_str))]
-   /// Session ID (user defined). Corresponds to an unique config, network id and agent
-   sid: PathBuf,
+   /// Session ID (user defined). Corresponds to an unique config, network id and agent.
+   /// Defaults to $SNAPMAIL_SESSION or the default session.
+   sid: Option<PathBuf>,
+   #[structopt(long, parse(from_os_str))]
+   /// Folder holding all sessions. Defaults to $SNAPMAIL_HOME or the system's config folder.
+   root: Option<PathBuf>,
+   #[structopt(long)]
+   /// Alias of the network to use, for sessions that joined several networks. Defaults to "default".
+   network: Option<String>,
+   #[structopt(short, long, parse(from_occurrences))]
+   /// Increase log verbosity (-v, -vv, -vvv)
+   verbose: u8,
+   #[structopt(short, long)]
+   /// Only log errors
+   quiet: bool,
+   #[structopt(long)]
+   /// Log output format: "pretty" or "json". Defaults to the session's settings.
+   log_format: Option<LogFormat>,
+   #[structopt(long)]
+   /// Also write daily rotated log files in the session's logs folder
+   log_file: bool,
+   #[structopt(long)]
+   /// Capture logs emitted by the zome WASM
+   wasm_log: bool,
    #[structopt(subcommand)]
-   cmd: SnapSubcommand,
+   cmd: CliCommand,
 }
 
 impl SnapCli {
+   /// Apply the sessions root folder given on the command line, if any.
+   /// Must be called before anything accesses a session.
+   pub fn apply_root(&self) {
+      if let Some(root) = &self.root {
+         set_config_root(root.clone());
+      }
+   }
+
+   /// Logging setup requested by the command line flags
+   pub fn log_config(&self) -> LogConfig {
+      let verbosity = if self.quiet { -1 } else { self.verbose as i8 };
+      let maybe_sid = self.resolved_sid().ok();
+      let maybe_log_dir = match &maybe_sid {
+         Some(sid) if self.log_file => Some(config_path().join(sid).join(LOG_FOLDER_NAME)),
+         _ => None,
+      };
+      let format = self.log_format.unwrap_or_else(|| {
+         maybe_sid
+            .and_then(|sid| SessionSettings::load(&sid).ok())
+            .and_then(|settings| settings.log_format.parse().ok())
+            .unwrap_or(LogFormat::Pretty)
+      });
+      LogConfig {
+         verbosity,
+         format,
+         maybe_log_dir,
+         wasm_log: self.wasm_log,
+         stdout: true,
+      }
+   }
+
+   /// Session ID given on the command line or default session
+   fn resolved_sid(&self) -> anyhow::Result<String> {
+      resolve_sid(self.sid.as_ref().map(|sid| sid.to_string_lossy().to_string()))
+   }
+
    /// Run this command
    pub async fn run(self) -> anyhow::Result<()> {
-      self.cmd.run(self.sid).await
+      let maybe_sid = self.resolved_sid();
+      match self.cmd {
+         /// Sessions management does not need a session ID
+         CliCommand::Sessions(cmd) => cmd.run().await,
+         CliCommand::Session(cmd) => cmd.run(PathBuf::from(maybe_sid?), self.network).await,
+      }
    }
 }
 
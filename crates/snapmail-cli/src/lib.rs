@@ -0,0 +1,11 @@
+#![allow(non_upper_case_globals)]
+#![allow(unused_doc_comments)]
+#![allow(unused_attributes)]
+
+//! Subcommands of snapmail-cli, also used by its tests
+
+#[macro_use]
+extern crate snapmail_common;
+
+pub mod subcommands;
+pub mod cli;
@@ -3,6 +3,8 @@
 #![allow(unused_attributes)]
 
 use structopt::StructOpt;
+use snapmail_common::logging::{init_logging, set_wasm_log_env};
+use snapmail_cli::cli;
 
 // #[macro_use]
 // extern crate lazy_static;
@@ -10,21 +12,21 @@ use structopt::StructOpt;
 #[macro_use]
 extern crate snapmail_common;
 
-pub mod subcommands;
-pub mod cli;
-
 ///
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-   if std::env::var_os("RUST_LOG").is_some() {
-      observability::init_fmt(observability::Output::Log).ok();
-   }
+fn main() -> anyhow::Result<()> {
    let opts = cli::SnapCli::from_args();
+   opts.apply_root();
+   let log_config = opts.log_config();
+   /// Environment is set before the runtime starts its threads
+   set_wasm_log_env(log_config.wasm_log);
+   let _log_guard = init_logging(&log_config, None)?;
 
-   println!("{:?}", opts);
-   let res = opts.run().await;
-   if let Err(e) = res {
-      err_msg!("{}", e);
-   }
+   dbg!("{:?}", opts);
+   tokio::runtime::Runtime::new()?.block_on(async move {
+      let res = opts.run().await;
+      if let Err(e) = res {
+         err_msg!("{}", e);
+      }
+   });
    Ok(())
 }
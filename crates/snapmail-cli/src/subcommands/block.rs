@@ -0,0 +1,119 @@
+use holochain_types::dna::*;
+use snapmail_common::{
+   api::SnapmailApi,
+   blocklist::*,
+   utils::*,
+};
+use std::collections::BTreeMap;
+use structopt::StructOpt;
+
+/// Sender to block or unblock: a handle, an agent ID or a handle pattern.
+/// Lists the blocklist when none is given.
+#[derive(Debug, StructOpt, Clone)]
+pub struct BlockTarget {
+   /// Handle of the sender
+   handle: Option<String>,
+   #[structopt(name = "id", long, conflicts_with = "handle")]
+   /// Agent ID of the sender
+   maybe_agent_id: Option<String>,
+   #[structopt(long, conflicts_with_all = &["handle", "id"])]
+   /// Handle pattern, where '*' matches any characters. Ex: "*bot*"
+   pattern: Option<String>,
+}
+
+impl BlockTarget {
+   /// Agent and handle of the target sender, if one was given
+   fn agent(&self, api: &dyn SnapmailApi) -> anyhow::Result<Option<(AgentPubKey, String)>> {
+      let handle_list = api.get_all_handles()?;
+      if let Some(id) = &self.maybe_agent_id {
+         let agent: AgentPubKey = stoh(id.clone());
+         let handle = get_name(&handle_list, &agent).unwrap_or_else(|| "<unknown>".to_string());
+         return Ok(Some((agent, handle)));
+      }
+      let Some(handle) = &self.handle else { return Ok(None) };
+      let agent_list = api.find_agent(handle.clone())?;
+      match agent_list.as_slice() {
+         [agent] => Ok(Some((agent.clone(), handle.clone()))),
+         [] => anyhow::bail!("No agent found for handle \"{}\"", handle),
+         _ => anyhow::bail!("Several agents have handle \"{}\". Use --id.", handle),
+      }
+   }
+
+   ///
+   pub fn block(self, sid: &str, api: &dyn SnapmailApi) -> anyhow::Result<()> {
+      let mut blocklist = Blocklist::load(sid)?;
+      if let Some(pattern) = &self.pattern {
+         if blocklist.add_pattern(pattern)? {
+            msg!("Blocked handles matching \"{}\"", pattern);
+         } else {
+            msg!("Pattern \"{}\" is already blocked", pattern);
+         }
+      } else if let Some((agent, handle)) = self.agent(api)? {
+         if blocklist.block(&agent, &handle) {
+            msg!("Blocked {} - {}", handle, agent);
+         } else {
+            msg!("{} is already blocked", handle);
+         }
+      } else {
+         return print_blocklist(sid, api);
+      }
+      blocklist.save(sid)?;
+      let deleted = apply_block_action(sid, api)?;
+      if !deleted.is_empty() {
+         msg!("{} mail(s) from blocked senders deleted", deleted.len());
+      }
+      Ok(())
+   }
+
+   /// Unblocked senders are never flagged as spam
+   pub fn unblock(self, sid: &str, api: &dyn SnapmailApi) -> anyhow::Result<()> {
+      let mut blocklist = Blocklist::load(sid)?;
+      if let Some(pattern) = &self.pattern {
+         if !blocklist.remove_pattern(pattern) {
+            anyhow::bail!("Pattern \"{}\" is not blocked", pattern);
+         }
+         msg!("Unblocked handles matching \"{}\"", pattern);
+      } else if let Some((agent, handle)) = self.agent(api)? {
+         if blocklist.unblock(&agent) {
+            msg!("Unblocked {} - {}", handle, agent);
+         } else {
+            msg!("{} was not blocked. It will no longer be flagged as spam.", handle);
+         }
+         if blocklist.is_blocked(&agent, Some(&handle)) {
+            msg!("{} still matches a blocked handle pattern", handle);
+         }
+      } else {
+         return print_blocklist(sid, api);
+      }
+      blocklist.save(sid)
+   }
+}
+
+
+/// Print blocked agents and patterns, and senders flagged as spam
+pub fn print_blocklist(sid: &str, api: &dyn SnapmailApi) -> anyhow::Result<()> {
+   let blocklist = Blocklist::load(sid)?;
+   msg!("{} blocked agent(s):", blocklist.agents.len());
+   for (agent, handle) in blocklist.agents.iter() {
+      msg!(" - {} - {}", handle, agent);
+   }
+   msg!("{} blocked pattern(s):", blocklist.patterns.len());
+   for pattern in blocklist.patterns.iter() {
+      msg!(" - {}", pattern);
+   }
+   /// Spam senders, with their number of mails
+   let classes = classify_network_mails(sid, api)?;
+   let handle_list = api.get_all_handles()?;
+   let mut spammers: BTreeMap<String, usize> = BTreeMap::new();
+   for item in api.get_all_mails()?.iter() {
+      if classes.get(&item.address) == Some(&MailClass::Spam) {
+         let handle = get_name(&handle_list, &item.author).unwrap_or_else(|| item.author.to_string());
+         *spammers.entry(handle).or_insert(0) += 1;
+      }
+   }
+   msg!("{} sender(s) flagged as spam:", spammers.len());
+   for (handle, count) in spammers.iter() {
+      msg!(" - {} ({} mails)", handle, count);
+   }
+   Ok(())
+}
@@ -5,35 +5,180 @@ use colored::*;
 use holochain_zome_types::*;
 //use holo_hash::*;
 use strum::AsStaticRef;
+use snapmail_common::cell::SnapmailCell;
+use std::path::PathBuf;
+use structopt::StructOpt;
 
 use snapmail::{
    //handle::*,
    EntryKind,
+   file::FileManifest,
+   handle::Handle,
+   mail::entries::*,
 };
 
-///
-pub async fn print_chain(conductor: ConductorHandle) {
-   let cell_ids = conductor.list_cell_ids(None);
-
-   let vault = conductor.get_authored_env(cell_ids[0].dna_hash()).unwrap();
+/// Inspect the source chain of the session's agent
+#[derive(Debug, StructOpt, Clone)]
+pub struct ChainCommand {
+   #[structopt(name = "type", long = "type")]
+   /// Only show actions of these types (ex: Create, CreateLink). Can be repeated.
+   action_types: Vec<String>,
+   #[structopt(name = "kind", long = "kind")]
+   /// Only show entries of these kinds (ex: OutMail, Handle). Can be repeated.
+   entry_kinds: Vec<String>,
+   #[structopt(long)]
+   /// First action sequence number to show
+   from: Option<u32>,
+   #[structopt(long)]
+   /// Last action sequence number to show
+   to: Option<u32>,
+   #[structopt(short, long)]
+   /// Decode app entries (mails, handles, manifests, acks) into readable fields
+   decode: bool,
+   #[structopt(long, parse(from_os_str))]
+   /// Write the full dump as JSON to this file
+   json: Option<PathBuf>,
+   #[structopt(long, parse(from_os_str))]
+   /// Compare with a dump previously written with --json
+   diff: Option<PathBuf>,
+}
 
-   let json_dump = dump_state(vault.clone().into(), cell_ids[0].agent_pubkey().clone()).await.unwrap();
-   //let json = serde_json::to_string_pretty(&json_dump).unwrap();
+impl ChainCommand {
+   ///
+   pub async fn run(self, cell: &SnapmailCell) -> anyhow::Result<()> {
+      let vault = cell.conductor.get_authored_env(cell.dna_hash())?;
+      let json_dump = dump_state(vault.clone().into(), cell.agent_pubkey().clone()).await?;
+      /// Compare before exporting, so the same file can be diffed then replaced by the new dump
+      if let Some(path) = &self.diff {
+         let snapshot: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+         let current = serde_json::to_value(&json_dump)?;
+         print_diff(&snapshot, &current);
+      }
+      /// Export
+      if let Some(path) = &self.json {
+         std::fs::write(path, serde_json::to_string_pretty(&json_dump)?)?;
+         println!("Chain of {} actions written to {:?}", json_dump.elements.len(), path);
+      }
+      if self.diff.is_some() || self.json.is_some() {
+         return Ok(());
+      }
+      /// Print
+      println!(" ====== SOURCE-CHAIN START ===== {}", json_dump.elements.len());
+      let mut count = 0;
+      for element in json_dump.elements.iter().filter(|element| self.is_shown(element)) {
+         println!(" {:2}. {}", element.header.header_seq(), print_element(element));
+         if self.decode {
+            if let Some(decoded) = decode_entry(element) {
+               println!("       {}", decoded.dimmed());
+            }
+         }
+         count += 1;
+      }
+      println!(" ====== SOURCE-CHAIN END  ===== {} shown", count);
+      Ok(())
+   }
 
-   println!(" ====== SOURCE-CHAIN START ===== {}", json_dump.elements.len());
-   //println!("source_chain_dump({}) of {:?}", json_dump.elements.len(), agent);
+   /// True if the element passes all filters
+   fn is_shown(&self, element: &SourceChainJsonElement) -> bool {
+      let seq = element.header.header_seq();
+      if self.from.map(|from| seq < from).unwrap_or(false) || self.to.map(|to| seq > to).unwrap_or(false) {
+         return false;
+      }
+      if !self.action_types.is_empty() {
+         let action_type = format!("{:?}", element.header.header_type());
+         if !self.action_types.iter().any(|t| t.eq_ignore_ascii_case(&action_type)) {
+            return false;
+         }
+      }
+      if !self.entry_kinds.is_empty() {
+         let Some(kind) = entry_kind(element) else { return false };
+         if !self.entry_kinds.iter().any(|k| k.eq_ignore_ascii_case(kind)) {
+            return false;
+         }
+      }
+      true
+   }
+}
 
-   let mut count = 0;
-   for element in &json_dump.elements {
-      let str = print_element(&element);
-      println!(" {:2}. {}", count, str);
-      count += 1;
+/// Entry kind of an app entry creation or update
+fn entry_kind(element: &SourceChainJsonElement) -> Option<&'static str> {
+   let entry_type = match &element.header {
+      Header::Create(create_entry) => &create_entry.entry_type,
+      Header::Update(update_entry) => &update_entry.entry_type,
+      _ => return None,
+   };
+   match entry_type {
+      EntryType::App(app_entry_type) => Some(EntryKind::from_index(&app_entry_type.id()).as_static()),
+      _ => None,
    }
+}
 
-   println!(" ====== SOURCE-CHAIN END  ===== {}", json_dump.elements.len());
+/// Readable fields of the app entries we care about when debugging mail delivery
+fn decode_entry(element: &SourceChainJsonElement) -> Option<String> {
+   let kind = entry_kind(element)?;
+   let Some(Entry::App(AppEntryBytes(sb))) = &element.entry else {
+      return Some("<entry not stored>".to_string());
+   };
+   let sb = sb.clone();
+   let decoded = match kind {
+      "InMail" => InMail::try_from(sb).map(|inmail| format!(
+         "from: {} | subject: \"{}\" | received: {} | attachments: {}",
+         inmail.from, inmail.mail.subject, inmail.date_received, inmail.mail.attachments.len(),
+      )),
+      "OutMail" => OutMail::try_from(sb).map(|outmail| format!(
+         "subject: \"{}\" | to: {} | cc: {} | bcc: {} | sent: {} | attachments: {}",
+         outmail.mail.subject, outmail.mail.to.len(), outmail.mail.cc.len(), outmail.bcc.len(),
+         outmail.mail.date_sent, outmail.mail.attachments.len(),
+      )),
+      "FileManifest" => FileManifest::try_from(sb).map(|manifest| format!(
+         "file: \"{}\" | type: {} | size: {} | chunks: {}",
+         manifest.filename, manifest.filetype, manifest.orig_filesize, manifest.chunks.len(),
+      )),
+      "Handle" => Handle::try_from(sb).map(|handle| format!("{:?}", handle)),
+      "InAck" => InAck::try_from(sb).map(|ack| format!("{:?}", ack)),
+      "OutAck" => OutAck::try_from(sb).map(|ack| format!("{:?}", ack)),
+      "PendingMail" => PendingMail::try_from(sb).map(|pending| format!("{:?}", pending)),
+      "PendingAck" => PendingAck::try_from(sb).map(|pending| format!("{:?}", pending)),
+      _ => return None,
+   };
+   Some(decoded.unwrap_or_else(|e| format!("<failed to decode {}: {:?}>", kind, e)))
 }
 
 
+/// Print actions that are only in one of two dumps, by action hash
+fn print_diff(snapshot: &serde_json::Value, current: &serde_json::Value) {
+   let empty = Vec::new();
+   let snapshot_elements = snapshot["elements"].as_array().unwrap_or(&empty);
+   let current_elements = current["elements"].as_array().unwrap_or(&empty);
+   let is_in = |element: &serde_json::Value, list: &Vec<serde_json::Value>| {
+      list.iter().any(|other| other["header_address"] == element["header_address"])
+   };
+   let removed: Vec<&serde_json::Value> = snapshot_elements.iter().filter(|element| !is_in(element, current_elements)).collect();
+   let added: Vec<&serde_json::Value> = current_elements.iter().filter(|element| !is_in(element, snapshot_elements)).collect();
+   println!("Snapshot: {} actions | Current: {} actions", snapshot_elements.len(), current_elements.len());
+   for element in removed.iter() {
+      println!(" {} {}", "-".red(), describe_json_element(element).red());
+   }
+   for element in added.iter() {
+      println!(" {} {}", "+".green(), describe_json_element(element).green());
+   }
+   if removed.is_empty() && added.is_empty() {
+      println!("No difference");
+   }
+}
+
+/// One line description of an element of a JSON dump
+fn describe_json_element(element: &serde_json::Value) -> String {
+   let header = &element["header"];
+   let seq = header["header_seq"].as_u64().map(|seq| seq.to_string()).unwrap_or("?".to_string());
+   let action_type = header["type"].as_str().unwrap_or("?");
+   let entry_type = match &header["entry_type"] {
+      serde_json::Value::Null => String::new(),
+      entry_type => format!(" {}", entry_type),
+   };
+   format!("{:>3}. {}{}", seq, action_type, entry_type)
+}
+
 
 ///
 fn print_element(element: &SourceChainJsonElement) -> String {
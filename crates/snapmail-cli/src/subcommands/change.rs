@@ -2,70 +2,96 @@ use structopt::StructOpt;
 use std::path::PathBuf;
 use snapmail_common::{
    globals::*,
+   config::*,
 };
-use lazy_static::lazy_static;
-use regex::Regex;
-use std::path::Path;
+use url2::Url2;
 
 /// Change conductor config
 #[derive(Debug, StructOpt, Clone)]
 pub struct ChangeCommand {
-   #[structopt(name = "proxy", long, parse(from_os_str))]
-   /// Url of proxy server to use
-   maybe_proxy: Option<PathBuf>,
-   #[structopt(name = "bootstrap", long, parse(from_os_str))]
+   #[structopt(name = "proxy", long, parse(try_from_str = Url2::try_parse))]
+   /// Url of proxy server to use ("kitsune-proxy://...")
+   maybe_proxy: Option<Url2>,
+   #[structopt(long, conflicts_with = "proxy")]
+   /// Stop using a proxy server
+   no_proxy: bool,
+   #[structopt(name = "bootstrap", long, parse(try_from_str = Url2::try_parse))]
    /// Url of bootstrap server to use
-   maybe_bootstrap: Option<PathBuf>,
+   maybe_bootstrap: Option<Url2>,
+   #[structopt(name = "transport", long)]
+   /// Type of network: "quic" (with bootstrap) or "mdns"
+   maybe_transport: Option<NetworkTransport>,
+   #[structopt(name = "bind-to", long, parse(try_from_str = Url2::try_parse))]
+   /// Network interface / port to bind to ("kitsune-quic://0.0.0.0:0")
+   maybe_bind_to: Option<Url2>,
+   #[structopt(name = "override-host", long)]
+   /// Override the local NIC ip
+   maybe_override_host: Option<String>,
+   #[structopt(name = "override-port", long)]
+   /// Override the local NIC port
+   maybe_override_port: Option<u16>,
    #[structopt(name = "uid", long)]
    /// Network ID that this session will use (String)
    maybe_uid: Option<String>,
+   #[structopt(long)]
+   /// Print the changes without writing them
+   dry_run: bool,
 }
 
 impl ChangeCommand {
    ///
-   pub fn run(&self, sid: PathBuf) {
+   pub fn run(&self, sid: PathBuf) -> anyhow::Result<()> {
       if let Some(uid) = &self.maybe_uid {
-         self.update_uid(sid.clone(), uid);
+         if self.dry_run {
+            msg!("uid would be changed to: {}", uid);
+         } else {
+            self.update_uid(sid.clone(), uid);
+         }
       }
-      if self.maybe_bootstrap.is_some() || self.maybe_proxy.is_some() {
-         self.update_conductor_config(sid);
+      let change = self.network_change();
+      if !change.is_empty() {
+         self.update_conductor_config(sid, &change)?;
+      }
+      Ok(())
+   }
+
+   ///
+   fn network_change(&self) -> NetworkChange {
+      NetworkChange {
+         maybe_transport: self.maybe_transport,
+         maybe_proxy: self.maybe_proxy.clone(),
+         remove_proxy: self.no_proxy,
+         maybe_bootstrap: self.maybe_bootstrap.clone(),
+         maybe_bind_to: self.maybe_bind_to.clone(),
+         maybe_override_host: self.maybe_override_host.clone(),
+         maybe_override_port: self.maybe_override_port,
       }
    }
 
    ///
    fn update_uid(&self, sid: PathBuf, uid: &str) {
-      let config_path = Path::new(&*CONFIG_PATH).join(sid.clone());
-      let app_filepath = config_path.join(APP_CONFIG_FILENAME);
+      let session_path = config_path().join(sid.clone());
+      let app_filepath = session_path.join(APP_CONFIG_FILENAME);
       std::fs::write(app_filepath, uid.as_bytes())
          .expect("APP_CONFIG_FILENAME should be writable");
    }
 
    ///
-   fn update_conductor_config(&self, sid: PathBuf) {
-      lazy_static! {
-        //static ref PORT_RE: Regex = Regex::new(r"port: (.*)").unwrap();
-        static ref BOOTSTRAP_RE: Regex = Regex::new(r"bootstrap_service: (.*)").unwrap();
-        static ref PROXY_RE: Regex = Regex::new(r"proxy_url: (.*)").unwrap();
+   fn update_conductor_config(&self, sid: PathBuf, change: &NetworkChange) -> anyhow::Result<()> {
+      let path = config_path().join(sid);
+      let diff = change_network(path, change, self.dry_run)?;
+      if diff.is_empty() {
+         msg!("Conductor config unchanged");
+         return Ok(());
       }
-      /// Load config
-      let path = CONFIG_PATH.as_path().join(sid);
-      let config_filepath = path.join(CONDUCTOR_CONFIG_FILENAME);
-      let mut file_str = std::fs::read_to_string(config_filepath.clone())
-         .expect("Something went wrong reading CONDUCTOR CONFIG file");
-      /// Get proxy server URL
-      if let Some(proxy_url) = &self.maybe_proxy {
-         let new_entry = format!("proxy_url: {}", proxy_url.to_string_lossy());
-         file_str = PROXY_RE.replace_all(&file_str, new_entry).to_string();
-         msg!("New config:\n{}", file_str);
+      if self.dry_run {
+         msg!("Dry run. Changes that would be applied:");
+      } else {
+         msg!("Conductor config changed:");
       }
-      /// Get bootstrap server URL
-      if let Some(url) = &self.maybe_bootstrap {
-         let new_entry = format!("bootstrap_service: \"{}\"", url.to_string_lossy());
-         file_str = BOOTSTRAP_RE.replace_all(&file_str, new_entry).to_string();
+      for line in diff.iter() {
+         msg!("{}", line);
       }
-      /// Write config
-      msg!("New config:\n{}", file_str);
-      std::fs::write(config_filepath.clone(), file_str)
-         .expect("CONDUCTOR_CONFIG_FILENAME should be writable");
+      Ok(())
    }
 }
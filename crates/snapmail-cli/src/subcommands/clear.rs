@@ -1,11 +1,15 @@
 use snapmail_common::globals::*;
+use snapmail_common::lock::session_lock_holder;
 use std::fs;
-use std::path::Path;
 use std::path::PathBuf;
 
 ///
 pub fn clear(uid: PathBuf) {
-   let dir = Path::new(&*CONFIG_PATH).join(uid);
+   if let Some(holder) = session_lock_holder(&uid.to_string_lossy()) {
+      msg!("Clear failed: session is in use by {}", holder);
+      return;
+   }
+   let dir = config_path().join(uid);
    let result = fs::remove_dir_all(dir.as_path());
    if let Err(e) = result {
       msg!("Clear failed: {}", e);
@@ -0,0 +1,121 @@
+use chrono::NaiveDate;
+use holochain_types::dna::*;
+use snapmail_common::{
+   api::SnapmailApi,
+   labels::*,
+   utils::stoh,
+};
+use structopt::StructOpt;
+
+/// Mail query options of a saved folder
+#[derive(Debug, StructOpt, Clone)]
+pub struct QueryOptions {
+   #[structopt(long)]
+   /// Handle of the author
+   from: Option<String>,
+   #[structopt(long)]
+   label: Option<String>,
+   #[structopt(long)]
+   /// incoming, outgoing, unacknowledged or acknowledged
+   state: Option<StateFilter>,
+   #[structopt(long)]
+   /// First day included (YYYY-MM-DD)
+   since: Option<NaiveDate>,
+   #[structopt(long)]
+   /// Last day included (YYYY-MM-DD)
+   until: Option<NaiveDate>,
+}
+
+impl From<QueryOptions> for MailQuery {
+   fn from(options: QueryOptions) -> Self {
+      MailQuery {
+         from: options.from,
+         label: options.label,
+         state: options.state,
+         since: options.since,
+         until: options.until,
+      }
+   }
+}
+
+/// Manage the session's local mail labels and saved folders
+#[derive(Debug, StructOpt, Clone)]
+pub enum LabelCommand {
+   /// Attach a label to a mail
+   Add {
+      /// Hash of the mail
+      hash: String,
+      label: String,
+   },
+   /// Detach a label from a mail
+   Rm {
+      /// Hash of the mail
+      hash: String,
+      label: String,
+   },
+   /// List labels in use and saved folders
+   List,
+   /// Save a query as a folder, or change the query of an existing folder
+   SaveFolder {
+      name: String,
+      #[structopt(flatten)]
+      query: QueryOptions,
+   },
+   /// Remove a saved folder. Labels are kept.
+   RmFolder {
+      name: String,
+   },
+}
+
+impl LabelCommand {
+   ///
+   pub fn run(self, sid: &str, api: &dyn SnapmailApi) -> anyhow::Result<()> {
+      let mut labels = MailLabels::load(sid)?;
+      match self {
+         Self::Add { hash, label } => {
+            let ah: ActionHash = stoh(hash);
+            if !api.get_all_mails()?.iter().any(|item| item.address == ah) {
+               anyhow::bail!("Unknown mail {}", ah);
+            }
+            if !labels.add(&ah, &label)? {
+               msg!("Mail {} is already labelled \"{}\"", ah, label);
+               return Ok(());
+            }
+            labels.save(sid)?;
+            msg!("Labelled mail {} \"{}\"", ah, label);
+         },
+         Self::Rm { hash, label } => {
+            let ah: ActionHash = stoh(hash);
+            if !labels.remove(&ah, &label) {
+               anyhow::bail!("Mail {} is not labelled \"{}\"", ah, label);
+            }
+            labels.save(sid)?;
+            msg!("Removed label \"{}\" from mail {}", label, ah);
+         },
+         Self::List => {
+            let counts = labels.label_counts();
+            msg!("{} label(s):", counts.len());
+            for (label, count) in counts.iter() {
+               msg!(" - {} ({} mail(s))", label, count);
+            }
+            msg!("{} saved folder(s):", labels.folders.len());
+            for folder in labels.folders.iter() {
+               msg!(" - {}: {}", folder.name, folder.query);
+            }
+         },
+         Self::SaveFolder { name, query } => {
+            labels.save_folder(&name, query.into())?;
+            labels.save(sid)?;
+            msg!("Saved folder \"{}\": {}", name, labels.folder(&name)?.query);
+         },
+         Self::RmFolder { name } => {
+            if !labels.remove_folder(&name) {
+               anyhow::bail!("Unknown folder \"{}\"", name);
+            }
+            labels.save(sid)?;
+            msg!("Removed folder \"{}\"", name);
+         },
+      }
+      Ok(())
+   }
+}
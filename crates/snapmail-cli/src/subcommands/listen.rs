@@ -1,21 +1,39 @@
 use holochain_types::dna::*;
 use holochain_types::signal::*;
-use snapmail::mail::*;
 use snapmail::handle::*;
 use snapmail::signal_protocol::*;
-use holochain::conductor::{ConductorHandle};
 use snapmail_common::{
-   conductor::*,
+   api::SnapmailApi,
+   cell::SnapmailCell,
+   metrics::*,
+   rules::run_rules,
+   auto_download::run_auto_download,
    utils::*,
 };
 use tokio_stream::{StreamExt};
 use futures_util::{self, pin_mut};
 use tokio::time::{Duration};
+use std::net::SocketAddr;
 
 
 /// Launch an "always on" conductor that displays events & signals
-pub async fn listen(conductor: ConductorHandle, loop_interval_sec: u64) -> anyhow::Result<()> {
+pub async fn listen(
+   sid: &str,
+   cell: SnapmailCell,
+   loop_interval_sec: u64,
+   maybe_metrics_addr: Option<SocketAddr>,
+) -> anyhow::Result<()> {
 
+   /// Expose metrics endpoint
+   if let Some(addr) = maybe_metrics_addr {
+      tokio::spawn(async move {
+         if let Err(e) = serve_metrics(addr).await {
+            err_msg!("Metrics endpoint failed: {}", e);
+         }
+      });
+   }
+
+   let conductor = cell.conductor.clone();
    /// Add app interface so we can get signals
    let mut interfaces = conductor.list_app_interfaces().await?;
    if interfaces.is_empty() {
@@ -24,18 +42,20 @@ pub async fn listen(conductor: ConductorHandle, loop_interval_sec: u64) -> anyho
    }
    msg!("App Interfaces: {:?}", interfaces);
 
-   let handle_list = snapmail_get_all_handles(conductor.clone(), ())?;
+   let handle_list = cell.get_all_handles()?;
 
-   let eh_list = snapmail_check_ack_inbox(conductor.clone(), ())?;
-   let hh_list = snapmail_check_mail_inbox(conductor.clone(), ())?;
+   let eh_list = cell.check_ack_inbox()?;
+   let hh_list = cell.check_mail_inbox()?;
 
    msg!("Inbox checked:\n -  acks received: {}\n - mails received: {}", eh_list.len(), hh_list.len());
+   let _ = run_rules(sid, &cell, &hh_list);
+   let _ = run_auto_download(sid, &cell, &hh_list);
 
    let signal_stream = conductor.signal_broadcaster().await.subscribe_merged();
    pin_mut!(signal_stream);
 
    // while let Some(signal) = signal_stream.next().await {
-   //    print_signal(conductor.clone(), &handle_list, signal)
+   //    print_signal(&cell, &handle_list, signal)
    // }
 
    /// Infinite loop
@@ -46,12 +66,15 @@ pub async fn listen(conductor: ConductorHandle, loop_interval_sec: u64) -> anyho
       ).await;
       match res {
          Err(_e) => {
-               let peer_count = dump_state(conductor.clone());
-               let all_mail_list = snapmail_get_all_mails(conductor.clone(), ())?;
+               let peer_count = cell.peer_count()
+                  .map(|count| count.to_string())
+                  .unwrap_or_else(|e| { err_msg!("Failed to count peers: {}", e); "?".to_string() });
+               let all_mail_list = cell.get_all_mails()?;
+               METRICS.update_mails(&all_mail_list);
                msg!("Peers: {} | Mails: {}", peer_count, all_mail_list.len());
          },
          Ok(None) => msg!("No signal found"),
-         Ok(Some(signal)) => print_signal(conductor.clone(), &handle_list, signal),
+         Ok(Some(signal)) => print_signal(sid, &cell, &handle_list, signal),
       }
       tokio::time::sleep(Duration::from_millis(100)).await;
    }
@@ -59,30 +82,41 @@ pub async fn listen(conductor: ConductorHandle, loop_interval_sec: u64) -> anyho
 }
 
 ///
-fn print_signal(conductor: ConductorHandle, handle_list: &Vec<HandleItem>, signal: Signal) {
+fn print_signal(sid: &str, cell: &SnapmailCell, handle_list: &Vec<HandleItem>, signal: Signal) {
    match signal {
-      Signal::App(_cell_id, app_signal) => {
+      Signal::App(cell_id, app_signal) => {
+         /// Ignore signals from the session's other networks
+         if cell_id != cell.cell_id {
+            return;
+         }
          let snapmail_signal: SignalProtocol = app_signal.into_inner().decode().unwrap();
-         print_snapmail_signal(conductor, &handle_list, snapmail_signal);
+         print_snapmail_signal(sid, cell, &handle_list, snapmail_signal);
       },
       Signal::System(system_signal) => {
+         METRICS.inc_signal("System");
          msg!("{:?}", system_signal);
       },
    }
 }
 
 ///
-fn print_snapmail_signal(conductor: ConductorHandle, handle_list: &Vec<HandleItem>, signal: SignalProtocol) {
+fn print_snapmail_signal(sid: &str, api: &dyn SnapmailApi, handle_list: &Vec<HandleItem>, signal: SignalProtocol) {
    match signal {
       SignalProtocol::ReceivedMail(item) => {
+         METRICS.inc_signal("ReceivedMail");
          let name = get_name(handle_list, &item.author).unwrap_or("<unknown>".to_string());
          msg!("Received Mail from {}: \"{}\" ({})", name, item.mail.subject, item.address);
+         let _ = run_rules(sid, api, &[item.address.clone()]);
+         let _ = run_auto_download(sid, api, &[item.address]);
+         refresh_mail_gauges(api);
       }
       SignalProtocol::ReceivedAck(ack) => {
+         METRICS.inc_signal("ReceivedAck");
+         refresh_mail_gauges(api);
          let name = get_name(handle_list, &ack.from).unwrap_or("<unknown>".to_string());
-         let maybe_mail = snapmail_get_mail(conductor.clone(), ack.for_mail.clone());
+         let maybe_mail = api.get_mail(ack.for_mail.clone());
          if let Err(err) = maybe_mail {
-            msg!("snapmail_get_mail() failed during print_snapmail_signal(): {:?}", err);
+            msg!("get_mail() failed during print_snapmail_signal(): {:?}", err);
             return;
          }
          let maybe_mail = maybe_mail.unwrap();
@@ -95,18 +129,28 @@ fn print_snapmail_signal(conductor: ConductorHandle, handle_list: &Vec<HandleIte
          msg!("Received Acknowledgement from {} for mail \"{}\"", name, subject);
       }
       SignalProtocol::ReceivedFile(manifest) => {
+         METRICS.inc_signal("ReceivedFile");
          msg!("Received File {} ({} KiB)", manifest.filename, manifest.orig_filesize);
       }
    }
 }
 
 
+/// Update the mail gauges, so they don't wait for a quiet tick while signals keep coming
+fn refresh_mail_gauges(api: &dyn SnapmailApi) {
+   match api.get_all_mails() {
+      Ok(all_mail_list) => METRICS.update_mails(&all_mail_list),
+      Err(e) => err_msg!("get_all_mails() failed while updating metrics: {:?}", e),
+   }
+}
+
+
 /// Get username from AgentPubKey
 /// Update Handle list if necessary
-pub fn try_get_name(conductor: ConductorHandle, candidate: &AgentPubKey) -> Result<String, ()> {
-   let handle_list = snapmail_get_all_handles(conductor.clone(), ());
+pub fn try_get_name(api: &dyn SnapmailApi, candidate: &AgentPubKey) -> Result<String, ()> {
+   let handle_list = api.get_all_handles();
    if let Err(err) = handle_list {
-      msg!("snapmail_get_all_handles() failed during try_get_name(): {:?}", err);
+      msg!("get_all_handles() failed during try_get_name(): {:?}", err);
       return Err(());
    }
    for handle_item in handle_list.unwrap().iter() {
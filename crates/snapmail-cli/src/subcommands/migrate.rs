@@ -0,0 +1,38 @@
+use holochain::conductor::ConductorHandle;
+use snapmail_common::{
+   migrate::*,
+   shutdown::graceful_shutdown,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+use structopt::StructOpt;
+
+/// Move a network's mails to the DNA of this release, keeping the old cell as a readable archive
+#[derive(Debug, StructOpt, Clone)]
+pub struct MigrateCommand {
+   #[structopt(long, parse(from_os_str))]
+   /// DNA file to install. Defaults to the DNA bundled with snapmail.
+   dna_path: Option<PathBuf>,
+   #[structopt(long)]
+   /// Also re-send archived mails to self on the new DNA, with their attachments
+   resend: bool,
+}
+
+impl MigrateCommand {
+   /// Shuts the conductor down once done, waiting at most `max_wait` for re-sent mails to go out
+   pub async fn run(self, conductor: ConductorHandle, sid: &str, maybe_network: Option<&str>, max_wait: Duration) -> anyhow::Result<()> {
+      let report = migrate_network(conductor.clone(), sid, maybe_network, self.dna_path, self.resend).await?;
+      msg!("Migration of network \"{}\" done:", report.alias);
+      msg!("        new DNA: {}", report.new_dna_hash);
+      msg!("        old DNA: {} (readable with --network {})", report.old_dna_hash, report.archive_alias);
+      msg!("        archive: {:?}", report.archive_path);
+      msg!("          mails: {}", report.mail_count);
+      msg!("        handles: {}", report.handle_count);
+      msg!("    attachments: {} ({} failed)", report.attachment_count, report.attachment_failures);
+      if self.resend {
+         msg!("  re-sent to self: {}", report.resent_count);
+      }
+      graceful_shutdown(conductor, sid, max_wait).await;
+      Ok(())
+   }
+}
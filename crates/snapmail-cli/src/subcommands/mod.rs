@@ -5,6 +5,14 @@ mod open;
 mod listen;
 mod change;
 mod chain;
+mod settings;
+mod sessions;
+mod networks;
+mod migrate;
+mod trash;
+mod label;
+mod block;
+mod rules;
 
 pub use setup::*;
 pub use clear::*;
@@ -12,4 +20,12 @@ pub use send::*;
 pub use open::*;
 pub use listen::*;
 pub use change::*;
-pub use chain::*;
\ No newline at end of file
+pub use chain::*;
+pub use settings::*;
+pub use sessions::*;
+pub use networks::*;
+pub use migrate::*;
+pub use trash::*;
+pub use label::*;
+pub use block::*;
+pub use rules::*;
\ No newline at end of file
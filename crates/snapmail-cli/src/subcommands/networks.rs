@@ -0,0 +1,62 @@
+use holochain::conductor::ConductorHandle;
+use snapmail_common::{
+   api::SnapmailApi,
+   networks::*,
+   shutdown::graceful_shutdown,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+use structopt::StructOpt;
+
+/// Manage the snapmail networks joined by a session
+#[derive(Debug, StructOpt, Clone)]
+pub enum NetworksCommand {
+   /// List networks joined by the session
+   List,
+   /// Join another network with the session's agent
+   Join {
+      /// Alias used to address the network with --network
+      alias: String,
+      /// Network seed of the network to join
+      network_seed: String,
+      #[structopt(long, parse(from_os_str))]
+      /// DNA file to install. Defaults to the DNA bundled with snapmail.
+      dna_path: Option<PathBuf>,
+   },
+   /// Leave a network. Its mails are removed from the session.
+   Leave {
+      alias: String,
+   },
+}
+
+impl NetworksCommand {
+   /// Shuts the conductor down once done, waiting at most `max_wait` for pending work
+   pub async fn run(self, conductor: ConductorHandle, sid: &str, max_wait: Duration) -> anyhow::Result<()> {
+      match self {
+         Self::List => {
+            let cells = all_network_cells(conductor.clone(), sid)?;
+            let networks = SessionNetworks::load(sid)?;
+            msg!("{} network(s) joined by \"{}\":", networks.networks.len(), sid);
+            for (entry, cell) in networks.networks.iter().zip(cells.iter()) {
+               let handle = cell.get_my_handle().unwrap_or_default();
+               let archived_str = if entry.archived { " (archived)" } else { "" };
+               msg!(" - {}{}", entry.alias, archived_str);
+               msg!("   network seed: {}", entry.network_seed);
+               msg!("            DNA: {}", cell.dna_hash());
+               msg!("         handle: {}", handle);
+            }
+         },
+         Self::Join { alias, network_seed, dna_path } => {
+            let dna_hash = join_network(conductor.clone(), sid, &alias, network_seed, dna_path).await?;
+            msg!("Joined network \"{}\" ({})", alias, dna_hash);
+            msg!("Set a handle on it with: snapmail-cli {} --network {} set-handle <name>", sid, alias);
+         },
+         Self::Leave { alias } => {
+            leave_network(conductor.clone(), sid, &alias).await?;
+            msg!("Left network \"{}\"", alias);
+         },
+      }
+      graceful_shutdown(conductor, sid, max_wait).await;
+      Ok(())
+   }
+}
@@ -1,14 +1,18 @@
 use snapmail_common::{
    utils::*,
+   api::SnapmailApi,
    conductor::*,
+   settings::*,
+   read_flags::mark_read,
+   blocklist::may_acknowledge,
+   networks::network_cell,
+   shutdown::graceful_shutdown,
 };
 use holochain_types::dna::*;
+use snapmail::mail::*;
 use snapmail::mail::entries::Mail;
 use snapmail::handle::HandleItem;
-use snapmail::mail::*;
-use snapmail::handle::*;
 use chrono::{DateTime, TimeZone, Local};
-use tokio::time::{sleep, Duration};
 
 fn print_mail(handle_list: &Vec<HandleItem>, mail: Mail, from: String, bcc: Vec<AgentPubKey>) {
    /// Get all CCs
@@ -41,44 +45,75 @@ fn print_mail(handle_list: &Vec<HandleItem>, mail: Mail, from: String, bcc: Vec<
 }
 
 ///
-pub async fn open(uid: String, ah: ActionHash) -> anyhow::Result<()> {
-   let conductor = start_conductor(uid).await;
-   let maybe_mail = snapmail_get_mail(conductor.clone(), ah.clone())?;
-   if let Some(mail) = maybe_mail.0 {
-      let handle_list = snapmail_get_all_handles(conductor.clone(), ())?;
-      msg!(" - mail: {:?}", mail);
-      match mail {
-         Ok(inmail) => {
-            let from = get_name(&handle_list, &inmail.from)
-               .ok_or(anyhow::Error::msg("Handle not found"))?;
-            print_mail(&handle_list, inmail.mail, from, vec![]);
-            msg!("Acknowledging...");
-            let maybe_hash = snapmail_acknowledge_mail(conductor.clone(), ah);
-            match maybe_hash {
-               Ok(hash) => msg!("Acknowledged: {}", hash),
-               Err(e) => msg!("Done - {:?}", e),
-            }
-         },
-         Err(outmail) => {
-            print_mail(&handle_list, outmail.mail, "<myself>".to_string(), outmail.bcc);
-         },
-      }
-   } else {
+pub async fn open(uid: String, maybe_network: Option<&str>, ah: ActionHash, settings: &SessionSettings, no_ack: bool) -> anyhow::Result<()> {
+   let conductor = start_conductor(uid.clone()).await;
+   let cell = network_cell(conductor.clone(), &uid, maybe_network)?;
+   let res = open_mail(&cell, &uid, ah, settings, no_ack);
+   /// Let the ack get out
+   graceful_shutdown(conductor, &uid, settings.timeouts.shutdown_max_wait()).await;
+   res
+}
+
+/// Print a mail and flag it as read.
+/// Acknowledge it if the ack policy allows it for its sender, unless `no_ack` is set.
+pub fn open_mail(api: &dyn SnapmailApi, sid: &str, ah: ActionHash, settings: &SessionSettings, no_ack: bool) -> anyhow::Result<()> {
+   let maybe_mail = api.get_mail(ah.clone())?;
+   let Some(mail) = maybe_mail.0 else {
       msg!(" !! No mail found at this hash");
+      return Ok(());
+   };
+   let handle_list = api.get_all_handles()?;
+   msg!(" - mail: {:?}", mail);
+   match mail {
+      Ok(inmail) => {
+         let from = get_name(&handle_list, &inmail.from)
+            .ok_or(anyhow::Error::msg("Handle not found"))?;
+         print_mail(&handle_list, inmail.mail, from.clone(), vec![]);
+         mark_read(sid, &ah)?;
+         let is_acknowledged = api.get_all_mails()?.iter()
+            .any(|item| item.address == ah && item.state != MailState::In(InMailState::Unacknowledged));
+         if no_ack {
+            msg!("Not acknowledging: --no-ack");
+         } else if is_acknowledged {
+            msg!("Already acknowledged");
+         } else if !may_acknowledge(sid, api, &ah)? {
+            msg!("Not acknowledging: sender is blocked");
+         } else if settings.acks_on_open(Some(&from)) {
+            acknowledge(api, ah)?;
+         } else {
+            msg!("Not acknowledging: ack policy is {:?}. Acknowledge with \"ack\".", settings.ack_policy);
+         }
+      },
+      Err(outmail) => {
+         print_mail(&handle_list, outmail.mail, "<myself>".to_string(), outmail.bcc);
+      },
    }
-   sleep(Duration::from_millis(20 * 1000)).await; // conductor.shutdown() is broken
-   conductor.shutdown();
+   Ok(())
+}
+
+/// Send the read receipt of a received mail
+pub fn acknowledge(api: &dyn SnapmailApi, ah: ActionHash) -> anyhow::Result<()> {
+   msg!("Acknowledging...");
+   let hash = api.acknowledge_mail(ah.clone())
+      .map_err(|e| anyhow::anyhow!("Failed to acknowledge mail {}: {:?}", ah, e))?;
+   msg!("Acknowledged: {}", hash);
    Ok(())
 }
 
 ///
-pub async fn get_status(uid: String, ah: ActionHash) -> anyhow::Result<()> {
-   let conductor = start_conductor(uid).await;
-   let state = snapmail_get_outmail_state(conductor.clone(), ah.clone())?;
+pub async fn get_status(uid: String, maybe_network: Option<&str>, ah: ActionHash) -> anyhow::Result<()> {
+   let conductor = start_conductor(uid.clone()).await;
+   let cell = network_cell(conductor, &uid, maybe_network)?;
+   print_outmail_status(&cell, ah)
+}
+
+/// Print the state of a sent mail and its delivery state per recipient
+pub fn print_outmail_status(api: &dyn SnapmailApi, ah: ActionHash) -> anyhow::Result<()> {
+   let state = api.get_outmail_state(ah.clone())?;
    msg!(" Outmail state: {:?}", state);
-   let map = snapmail_get_outmail_delivery_state(conductor.clone(), ah.clone())?;
+   let map = api.get_outmail_delivery_state(ah)?;
    for pair in map.iter() {
       msg!(" - {:?}", pair);
    }
    Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,63 @@
+use holochain_types::dna::*;
+use snapmail_common::{
+   api::SnapmailApi,
+   rules::*,
+   settings::SessionSettings,
+   utils::stoh,
+};
+use std::collections::HashMap;
+use structopt::StructOpt;
+
+/// Inspect the inbox rules of the session's settings file
+#[derive(Debug, StructOpt, Clone)]
+pub enum RulesCommand {
+   /// List rules in the order they run
+   List,
+   /// Dry run: show which conditions a mail meets and which actions would run. Nothing is done.
+   Test {
+      /// Hash of a received mail
+      hash: String,
+   },
+}
+
+impl RulesCommand {
+   ///
+   pub fn run(self, sid: &str, api: &dyn SnapmailApi) -> anyhow::Result<()> {
+      let settings = SessionSettings::load(sid)?;
+      match self {
+         Self::List => {
+            msg!("{} rule(s) in {:?}:", settings.rules.len(), SessionSettings::filepath(sid));
+            for rule in settings.rules.iter() {
+               let disabled_str = if rule.disabled { " (disabled)" } else { "" };
+               let actions: Vec<String> = rule.actions.iter().map(|action| action.to_string()).collect();
+               msg!("- {}{}: {}", rule.name, disabled_str, actions.join(", "));
+            }
+         },
+         Self::Test { hash } => {
+            let ah: ActionHash = stoh(hash);
+            let item = api.get_all_mails()?.into_iter()
+               .find(|item| item.address == ah)
+               .ok_or_else(|| anyhow::anyhow!("Unknown mail {}", ah))?;
+            let handle_map: HashMap<AgentPubKey, String> = api.get_all_handles()?.into_iter()
+               .map(|item| (item.agentId, item.name))
+               .collect();
+            msg!("Mail \"{}\"", item.mail.subject);
+            for rule in settings.rules.iter() {
+               let is_match = rule.matches(&item, &handle_map)?;
+               let disabled_str = if rule.disabled { " (disabled)" } else { "" };
+               msg!("- {}{}: {}", rule.name, disabled_str, if is_match { "MATCH" } else { "no match" });
+               for (condition, is_met) in rule.explain(&item, &handle_map)? {
+                  msg!("   [{}] {}", if is_met { "x" } else { " " }, condition);
+               }
+            }
+            let actions = matching_actions(&settings.rules, &item, &handle_map)?;
+            msg!("{} action(s) would run:", actions.len());
+            for (rule, action) in actions.into_iter() {
+               msg!("- {}", RuleOutcome { mail: ah.clone(), rule, action, result: None });
+            }
+         },
+      }
+      Ok(())
+   }
+}
+
@@ -1,21 +1,18 @@
 use snapmail_common::{
    utils::*,
    attachment::*,
+   settings::SessionSettings,
 };
 use structopt::StructOpt;
-use holochain::conductor::ConductorHandle;
-use snapmail::{
-   mail::*,
-   handle::*,
-};
+use snapmail_common::api::SnapmailApi;
+use snapmail::mail::*;
 use std::path::PathBuf;
 use holochain_types::dna::*;
-use tokio::time::{sleep, Duration};
 
 #[derive(Debug, StructOpt, Clone)]
 pub struct SendCommand {
    #[structopt(long)]
-   /// Recepients
+   /// Recepients. Defaults to the session's default recipients.
    to: Vec<String>,
    // #[structopt(long)]
    // cc: Option<Vec<String>>,
@@ -32,12 +29,16 @@ pub struct SendCommand {
 
 
 impl SendCommand {
-   ///
-   pub async fn run(self, conductor: ConductorHandle) -> anyhow::Result<()> {
+   /// Send the mail. Returns its action hash.
+   pub async fn run(self, api: &dyn SnapmailApi, settings: &SessionSettings) -> anyhow::Result<ActionHash> {
       // Form "to" list
-      let handle_list = snapmail_get_all_handles(conductor.clone(), ())?;
+      let handle_list = api.get_all_handles()?;
+      let names = if self.to.is_empty() { &settings.default_recipients } else { &self.to };
+      if names.is_empty() {
+         return Err(anyhow::Error::msg("No recipient given and no default recipients set"));
+      }
       let mut to_list: Vec<AgentPubKey> = Vec::new();
-      for name in self.to.iter() {
+      for name in names.iter() {
          let agent_id = get_agent_id(&handle_list, name)
             .ok_or(anyhow::Error::msg("username not found"))?;
          to_list.push(agent_id);
@@ -46,13 +47,18 @@ impl SendCommand {
       let mut manifest_address_list: Vec<ActionHash> = Vec::new();
       if let Some(attachment) = self.maybe_attachment {
          msg!("Reading attachment file: {:?}", attachment);
-         let hh = write_attachment(conductor.clone(), attachment)?;
+         let hh = write_attachment(api, attachment)?;
          manifest_address_list.push(hh);
       }
       // Form MailInput
+      let payload = if settings.signature.is_empty() {
+         self.message
+      } else {
+         format!("{}\n\n{}", self.message, settings.signature)
+      };
       let mail = SendMailInput {
          subject: self.subject,
-         payload: self.message,
+         payload,
          to: to_list,
          cc: vec![],
          bcc: vec![],
@@ -60,20 +66,14 @@ impl SendCommand {
       };
       //let send_count = mail.to.len() + mail.cc.len() + mail.bcc.len();
       // Send
-      let sent_hh = snapmail_send_mail(conductor.clone(), mail)?;
-
-      sleep(Duration::from_millis(10 * 1000)).await; // conductor.shutdown() is broken
+      let sent_hh = api.send_mail(mail)?;
 
       // Get State
-      let mail_state = snapmail_get_outmail_state(conductor.clone(), sent_hh.clone())?;
+      let mail_state = api.get_outmail_state(sent_hh.clone())?;
       // Show results
       //let pending_count = output.to_pendings.len() + output.cc_pendings.len() + output.bcc_pendings.len();
       msg!("Send done: {:?}", sent_hh);
       msg!("   - mail_state: {:?}", mail_state);
-
-      // Wait for post-commit to finish
-      sleep(Duration::from_millis(20 * 1000)).await; // conductor.shutdown() is broken
-      conductor.shutdown();
-      Ok(())
+      Ok(sent_hh)
    }
 }
@@ -0,0 +1,93 @@
+use structopt::StructOpt;
+use snapmail_common::session::*;
+
+/// Manage the sessions set up on this computer
+#[derive(Debug, StructOpt, Clone)]
+pub enum SessionsCommand {
+   /// List sessions with their agent, network and mail counts
+   List {
+      #[structopt(long)]
+      /// Output as JSON
+      json: bool,
+      #[structopt(long)]
+      /// Only read session files (do not start conductors)
+      fast: bool,
+   },
+   /// Rename a session
+   Rename {
+      sid: String,
+      new_sid: String,
+   },
+   /// Create a new session with a new agent on the same network as an existing session
+   Clone {
+      sid: String,
+      new_sid: String,
+   },
+   /// Set the session used when no session ID is given. Prints it if no session ID is given.
+   Default {
+      sid: Option<String>,
+   },
+}
+
+impl SessionsCommand {
+   ///
+   pub async fn run(self) -> anyhow::Result<()> {
+      match self {
+         Self::List { json, fast } => {
+            let mut infos = Vec::new();
+            for sid in list_sids()? {
+               infos.push(session_info(&sid, !fast).await);
+            }
+            if json {
+               println!("{}", serde_json::to_string_pretty(&infos)?);
+               return Ok(());
+            }
+            msg!("{} session(s) found:", infos.len());
+            for info in infos.iter() {
+               let default_str = if info.is_default { " (default)" } else { "" };
+               msg!(" - {}{}", info.sid, default_str);
+               msg!("           path: {}", info.path.display());
+               msg!("   network seed: {}", info.network_seed.clone().unwrap_or_default());
+               msg!("      last used: {}", info.last_used.map(|d| d.to_string()).unwrap_or("never".to_string()));
+               if fast {
+                  continue;
+               }
+               if let Some(error) = &info.error {
+                  msg!("          error: {}", error);
+                  continue;
+               }
+               msg!("         handle: {}", info.handle.clone().unwrap_or_default());
+               msg!("          agent: {}", info.agent_pubkey.clone().unwrap_or_default());
+               msg!("            DNA: {}", info.dna_hash.clone().unwrap_or_default());
+               if let Some(counts) = &info.mail_counts {
+                  msg!("          mails: {} total | {} inbox ({} unacknowledged) | {} sent",
+                     counts.total, counts.inbox, counts.unacknowledged, counts.sent);
+               }
+            }
+         },
+         Self::Rename { sid, new_sid } => {
+            rename_session(&sid, &new_sid)?;
+            msg!("Session \"{}\" renamed to \"{}\"", sid, new_sid);
+         },
+         Self::Clone { sid, new_sid } => {
+            let dna_hash = clone_session(&sid, &new_sid).await?;
+            msg!("Session \"{}\" created on the network of \"{}\" ({})", new_sid, sid, dna_hash);
+         },
+         Self::Default { sid } => {
+            match sid {
+               Some(sid) => {
+                  set_default_sid(&sid)?;
+                  msg!("Default session set to \"{}\"", sid);
+               },
+               None => {
+                  match default_sid() {
+                     Some(sid) => msg!("Default session: \"{}\"", sid),
+                     None => msg!("No default session set"),
+                  }
+               },
+            }
+         },
+      }
+      Ok(())
+   }
+}
@@ -0,0 +1,45 @@
+use structopt::StructOpt;
+use std::path::PathBuf;
+use snapmail_common::settings::*;
+
+/// Read or modify the session's user settings
+#[derive(Debug, StructOpt, Clone)]
+pub enum ConfigCommand {
+   /// Print the value of a setting (ex: "timeouts.listen_tick_secs")
+   Get {
+      key: String,
+   },
+   /// Change the value of a setting
+   Set {
+      key: String,
+      /// New value, parsed as YAML (ex: "[alex, billy]" for a list)
+      value: String,
+   },
+   /// Print all settings
+   List,
+}
+
+impl ConfigCommand {
+   ///
+   pub fn run(&self, sid: PathBuf) -> anyhow::Result<()> {
+      let sid_str = sid.to_string_lossy().to_string();
+      let mut settings = SessionSettings::load(&sid_str)?;
+      match self {
+         Self::Get { key } => {
+            msg!("{} = {}", key, settings.get(key)?);
+         },
+         Self::Set { key, value } => {
+            settings.set(key, value)?;
+            settings.save(&sid_str)?;
+            msg!("{} = {}", key, settings.get(key)?);
+         },
+         Self::List => {
+            msg!("Settings of session \"{}\" ({:?}):", sid_str, SessionSettings::filepath(&sid_str));
+            for (key, value) in settings.list()? {
+               msg!(" - {} = {}", key, value);
+            }
+         },
+      }
+      Ok(())
+   }
+}
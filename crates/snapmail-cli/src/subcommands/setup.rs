@@ -3,11 +3,8 @@ use std::path::PathBuf;
 use holochain_p2p::kitsune_p2p::{KitsuneP2pConfig, TransportConfig, NetworkType};
 use url2::Url2;
 use snapmail_common::{
-   globals::*,
-   conductor::*,
-   config::*,
+   session::create_session,
 };
-use snapmail::handle::*;
 
 /// This creates a new holochain sandbox
 /// which is a
@@ -25,32 +22,18 @@ pub struct SetupCommand {
 
    #[structopt(long, parse(from_os_str))]
    pub dna_path: Option<PathBuf>,
-
-   // Set a root directory for the app's storage data to be placed into.
-   // Defaults to the system's temp directory.
-   // This directory must already exist.
-   // #[structopt(name = "root", parse(from_os_str))]
-   // pub maybe_root: Option<PathBuf>,
 }
 
 impl SetupCommand {
    ///
    pub async fn run(&self, sid: PathBuf) -> anyhow::Result<()> {
       let sid_str = sid.to_string_lossy().to_string();
-      //let root = self.maybe_root.clone().unwrap_or(CONFIG_PATH.as_path().to_path_buf());
-      let root = CONFIG_PATH.as_path().to_path_buf();
-      let _ = generate(
-         root,
-         Some(sid.clone()),
+      let _dna_hash = create_session(
+         &sid_str,
+         self.uid.clone(),
          self.maybe_network.clone().map(|n| n.into_inner().into()),
-      ).expect("Generate config failed. Maybe Invalid params.");
-
-
-      let dna_hash = install_app(sid.to_string_lossy().to_string(), self.uid.clone(), self.dna_path.clone()).await?;
-       msg!("    Using DNA: {}", dna_hash);
-      let conductor = start_conductor(sid_str.clone()).await;
-      let hash = snapmail_set_handle(conductor, sid_str.clone())?;
-      msg!(" handle set: {} - {:?}", sid_str, hash);
+         self.dna_path.clone(),
+      ).await?;
       Ok(())
    }
 }
@@ -0,0 +1,83 @@
+use holochain_types::dna::*;
+use snapmail_common::{
+   api::SnapmailApi,
+   trash::*,
+   utils::stoh,
+};
+use structopt::StructOpt;
+
+/// Manage the trash of a network. Mails trashed longer than `trash_retention_days` are purged first.
+#[derive(Debug, StructOpt, Clone)]
+pub enum TrashCommand {
+   /// List trashed mails, oldest first
+   List,
+   /// Put a mail in the trash
+   Add {
+      /// Hash of the mail to trash
+      hash: String,
+   },
+   /// Take mails out of the trash
+   Restore {
+      #[structopt(required_unless = "all")]
+      /// Hash of the mail to restore
+      hash: Option<String>,
+      #[structopt(long)]
+      /// Restore all trashed mails
+      all: bool,
+   },
+   /// Delete a trashed mail for good
+   Purge {
+      /// Hash of the mail to purge
+      hash: String,
+   },
+   /// Delete all trashed mails for good
+   Empty,
+}
+
+impl TrashCommand {
+   ///
+   pub fn run(self, sid: &str, api: &dyn SnapmailApi) -> anyhow::Result<()> {
+      let retained = apply_retention_setting(sid, api)?;
+      if !retained.is_empty() {
+         msg!("{} mail(s) purged after retention period", retained.len());
+      }
+      match self {
+         Self::List => {
+            let mails = trashed_mails(sid, api)?;
+            msg!("{} mail(s) in trash:", mails.len());
+            for (item, trashed_at) in mails.iter() {
+               msg!("- {} | {} | {}", trashed_at.format("%Y-%m-%d %H:%M"), item.mail.subject, item.address);
+            }
+         },
+         Self::Add { hash } => {
+            let ah: ActionHash = stoh(hash);
+            trash_mail(sid, api, &ah)?;
+            msg!("Trashed mail {}", ah);
+         },
+         Self::Restore { hash, all } => {
+            let ah_list: Vec<ActionHash> = if all {
+               trashed_mails(sid, api)?.into_iter().map(|(item, _)| item.address).collect()
+            } else {
+               vec![stoh(hash.unwrap())]
+            };
+            for ah in ah_list.iter() {
+               restore_mail(sid, ah)?;
+            }
+            msg!("{} mail(s) restored", ah_list.len());
+         },
+         Self::Purge { hash } => {
+            let ah: ActionHash = stoh(hash);
+            if !Trash::load(sid)?.contains(&ah) {
+               anyhow::bail!("Mail {} is not in the trash. Trash it first with \"trash add\".", ah);
+            }
+            purge_mail(sid, api, &ah)?;
+            msg!("Purged mail {}", ah);
+         },
+         Self::Empty => {
+            let purged = empty_trash(sid, api)?;
+            msg!("{} mail(s) purged", purged.len());
+         },
+      }
+      Ok(())
+   }
+}
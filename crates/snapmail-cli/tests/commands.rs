@@ -0,0 +1,141 @@
+//! Subcommands on the in-memory API. No conductor needed.
+
+#![allow(unused_doc_comments)]
+
+use holochain_types::dna::*;
+use snapmail::mail::*;
+use snapmail_cli::subcommands::*;
+use snapmail_common::{
+   api::SnapmailApi,
+   mock::*,
+   read_flags::*,
+   settings::*,
+   test_support::{new_mail, sessions_root},
+};
+use structopt::StructOpt;
+
+
+/// Send a mail and return its address in the recipient's mailbox
+fn receive(from: &MockSnapmail, to: &MockSnapmail, subject: &str) -> ActionHash {
+   from.send_mail(new_mail(subject, vec![to], vec![])).unwrap();
+   to.get_all_mails().unwrap().into_iter()
+      .find(|item| item.mail.subject == subject)
+      .unwrap()
+      .address
+}
+
+///
+fn state_of(agent: &MockSnapmail, ah: &ActionHash) -> MailState {
+   agent.get_all_mails().unwrap().into_iter().find(|item| item.address == *ah).unwrap().state
+}
+
+
+#[test]
+fn open_acks_per_policy() {
+   let (_guard, _root) = sessions_root(&["billy"]);
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+   let camille = network.add_agent("camille");
+   let mut settings = SessionSettings::default();
+
+   /// Manual: opening never acknowledges
+   settings.ack_policy = AckPolicy::Manual;
+   let ah = receive(&alex, &billy, "manual");
+   open_mail(&billy, "billy", ah.clone(), &settings, false).unwrap();
+   assert_eq!(state_of(&billy, &ah), MailState::In(InMailState::Unacknowledged));
+
+   /// Always: opening acknowledges, opening again does nothing more
+   settings.ack_policy = AckPolicy::Always;
+   let ah = receive(&alex, &billy, "always");
+   open_mail(&billy, "billy", ah.clone(), &settings, false).unwrap();
+   assert_eq!(state_of(&billy, &ah), MailState::In(InMailState::AckDelivered));
+   open_mail(&billy, "billy", ah.clone(), &settings, false).unwrap();
+
+   /// PerContact: only mails of listed contacts are acknowledged
+   settings.ack_policy = AckPolicy::PerContact;
+   settings.ack_contacts = vec!["alex".to_string()];
+   let from_alex = receive(&alex, &billy, "per contact alex");
+   let from_camille = receive(&camille, &billy, "per contact camille");
+   open_mail(&billy, "billy", from_alex.clone(), &settings, false).unwrap();
+   open_mail(&billy, "billy", from_camille.clone(), &settings, false).unwrap();
+   assert_eq!(state_of(&billy, &from_alex), MailState::In(InMailState::AckDelivered));
+   assert_eq!(state_of(&billy, &from_camille), MailState::In(InMailState::Unacknowledged));
+}
+
+
+#[test]
+fn open_with_no_ack_only_flags_read() {
+   let (_guard, _root) = sessions_root(&["billy"]);
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+   let settings = SessionSettings { ack_policy: AckPolicy::Always, ..Default::default() };
+
+   let ah = receive(&alex, &billy, "quiet");
+   let item = billy.get_all_mails().unwrap().into_iter().find(|item| item.address == ah).unwrap();
+   assert!(!ReadFlags::load("billy").unwrap().is_read(&item));
+   open_mail(&billy, "billy", ah.clone(), &settings, true).unwrap();
+   assert_eq!(state_of(&billy, &ah), MailState::In(InMailState::Unacknowledged));
+   assert!(ReadFlags::load("billy").unwrap().is_read(&item));
+
+   /// Read flags are local: unread does not touch the ack
+   assert!(mark_unread("billy", &ah).unwrap());
+   assert!(!mark_unread("billy", &ah).unwrap());
+   assert!(!ReadFlags::load("billy").unwrap().is_read(&item));
+   assert_eq!(state_of(&billy, &ah), MailState::In(InMailState::Unacknowledged));
+
+   /// Sent mails are always read
+   let sent = alex.get_all_mails().unwrap().into_iter().find(|item| item.mail.subject == "quiet").unwrap();
+   assert!(ReadFlags::default().is_read(&sent));
+}
+
+
+#[test]
+fn failed_ack_is_an_error() {
+   let (_guard, _root) = sessions_root(&["billy"]);
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+
+   let ah = receive(&alex, &billy, "twice");
+   acknowledge(&billy, ah.clone()).unwrap();
+   assert!(acknowledge(&billy, ah).is_err());
+   /// Outmails can not be acknowledged
+   let sent_ah = alex.get_all_mails().unwrap()[0].address.clone();
+   assert!(acknowledge(&alex, sent_ah).is_err());
+}
+
+
+#[tokio::test]
+async fn send_to_default_or_given_recipients() {
+   let network = MockNetwork::new();
+   let alex = network.add_agent("alex");
+   let billy = network.add_agent("billy");
+   let camille = network.add_agent("camille");
+   let settings = SessionSettings {
+      default_recipients: vec!["billy".to_string()],
+      signature: "-- alex".to_string(),
+      ..Default::default()
+   };
+
+   /// No recipient given: default recipients get it, with the signature
+   let command = SendCommand::from_iter(&["send", "-s", "hello", "-m", "Hi"]);
+   let sent_ah = command.run(&alex, &settings).await.unwrap();
+   assert_eq!(alex.get_outmail_state(sent_ah).unwrap(), OutMailState::AllReceived);
+   let item = billy.get_all_mails().unwrap().into_iter().find(|item| item.mail.subject == "hello").unwrap();
+   assert_eq!(item.mail.payload, "Hi\n\n-- alex");
+   assert!(camille.get_all_mails().unwrap().is_empty());
+
+   /// Given recipients replace the default ones
+   let command = SendCommand::from_iter(&["send", "--to", "camille", "-s", "direct", "-m", "Hi"]);
+   command.run(&alex, &settings).await.unwrap();
+   assert_eq!(camille.get_all_mails().unwrap().len(), 1);
+   assert_eq!(billy.get_all_mails().unwrap().len(), 1);
+
+   /// Unknown handles and missing recipients fail
+   let command = SendCommand::from_iter(&["send", "--to", "nobody", "-s", "lost", "-m", "Hi"]);
+   assert!(command.run(&alex, &settings).await.is_err());
+   let command = SendCommand::from_iter(&["send", "-s", "lost", "-m", "Hi"]);
+   assert!(command.run(&alex, &SessionSettings::default()).await.is_err());
+}
@@ -0,0 +1,174 @@
+use holochain::conductor::ConductorHandle;
+use holochain_types::dna::*;
+use snapmail_common::{
+   api::SnapmailApi,
+   conductor::*,
+   cell::SnapmailCell,
+   globals::DEFAULT_NETWORK_ALIAS,
+   networks::network_cell,
+   presence::PresenceTracker,
+   trash::apply_retention_setting,
+   blocklist::apply_block_action,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use crate::{
+   snapmail_chain::SnapmailChain,
+   listen_signal::spawn_inbox_actions,
+};
+
+
+/// A network of a session opened by the TUI.
+/// Its name is the session ID, followed by "@alias" when it is not the session's default network.
+pub struct Account {
+   pub name: String,
+   pub sid: String,
+   /// Network alias in the session
+   pub alias: String,
+   pub api: Arc<dyn SnapmailApi>,
+   /// Cell behind `api` when it is backed by a conductor. Needed for signals.
+   pub maybe_cell: Option<SnapmailCell>,
+   pub chain: SnapmailChain,
+   /// Presence of the contacts on this network
+   pub presence: PresenceTracker,
+}
+
+impl Account {
+   /// Apply the session's retention and block settings, then load the chain.
+   /// Returns the account and its new mails.
+   async fn open(name: String, alias: String, api: Arc<dyn SnapmailApi>, maybe_cell: Option<SnapmailCell>) -> anyhow::Result<(Account, Vec<ActionHash>)> {
+      let (sid, _) = parse_account_name(&name);
+      apply_retention_setting(&sid, api.as_ref())?;
+      apply_block_action(&sid, api.as_ref())?;
+      let (chain, new_mails) = SnapmailChain::from_latest(&name, api.as_ref()).await;
+      let presence = PresenceTracker::load(&sid, api.clone());
+      Ok((Account { name, sid, alias, api, maybe_cell, chain, presence }, new_mails))
+   }
+}
+
+/// All accounts opened by the TUI
+pub struct Accounts {
+   pub list: Vec<Account>,
+   /// Feedback of the tasks running in the background
+   signal_tx: Sender<String>,
+}
+
+/// Split an account name into session ID and network alias (ex: "alex@work")
+pub fn parse_account_name(name: &str) -> (String, Option<String>) {
+   match name.split_once('@') {
+      Some((sid, alias)) => (sid.to_string(), Some(alias.to_string())),
+      None => (name.to_string(), None),
+   }
+}
+
+impl Accounts {
+   /// Start the conductor of each session. Accounts of the same session share its conductor.
+   /// Fails if a network alias is unknown.
+   pub async fn start(names: Vec<String>, signal_tx: Sender<String>) -> anyhow::Result<Accounts> {
+      let mut conductors: HashMap<String, ConductorHandle> = HashMap::new();
+      let mut list = Vec::new();
+      let mut new_mail_lists = Vec::new();
+      for name in names {
+         let (sid, maybe_alias) = parse_account_name(&name);
+         let conductor = match conductors.get(&sid) {
+            Some(conductor) => conductor.clone(),
+            None => {
+               let (conductor, _dna_hash) = start_conductor_or_abort(sid.clone()).await;
+               conductors.insert(sid.clone(), conductor.clone());
+               conductor
+            },
+         };
+         let cell = network_cell(conductor, &sid, maybe_alias.as_deref())?;
+         let api: Arc<dyn SnapmailApi> = Arc::new(cell.clone());
+         let (account, new_mails) = Account::open(name, cell.alias.clone(), api, Some(cell)).await?;
+         list.push(account);
+         new_mail_lists.push(new_mails);
+      }
+      let accounts = Accounts { list, signal_tx };
+      accounts.spawn_inbox_actions(new_mail_lists);
+      Ok(accounts)
+   }
+
+   /// Accounts on APIs not backed by a conductor, ex: `MockSnapmail` of the `test-support` feature.
+   /// Each API comes with its account name. Accounts have no signals.
+   pub async fn from_apis(apis: Vec<(String, Arc<dyn SnapmailApi>)>, signal_tx: Sender<String>) -> anyhow::Result<Accounts> {
+      let mut list = Vec::new();
+      let mut new_mail_lists = Vec::new();
+      for (name, api) in apis {
+         let alias = parse_account_name(&name).1.unwrap_or(DEFAULT_NETWORK_ALIAS.to_string());
+         let (account, new_mails) = Account::open(name, alias, api, None).await?;
+         list.push(account);
+         new_mail_lists.push(new_mails);
+      }
+      let accounts = Accounts { list, signal_tx };
+      accounts.spawn_inbox_actions(new_mail_lists);
+      Ok(accounts)
+   }
+
+   /// Pull latest data of every account
+   pub async fn refresh(&mut self) {
+      let mut new_mail_lists = Vec::new();
+      for account in self.list.iter_mut() {
+         let (chain, new_mails) = SnapmailChain::from_latest(&account.name, account.api.as_ref()).await;
+         account.chain = chain;
+         new_mail_lists.push(new_mails);
+      }
+      self.spawn_inbox_actions(new_mail_lists);
+   }
+
+   /// Run the inbox rules of each account on its new mails, in the background
+   fn spawn_inbox_actions(&self, new_mail_lists: Vec<Vec<ActionHash>>) {
+      for (account, new_mails) in self.list.iter().zip(new_mail_lists) {
+         let prefix = self.signal_prefix(&account.name);
+         spawn_inbox_actions(account.sid.clone(), account.api.clone(), new_mails, prefix, self.signal_tx.clone());
+      }
+   }
+
+   /// Prefix of an account's feedback messages: its name, when several accounts are opened
+   pub fn signal_prefix(&self, name: &str) -> String {
+      if self.list.len() > 1 { format!("[{}] ", name) } else { String::new() }
+   }
+
+   ///
+   pub fn len(&self) -> usize {
+      self.list.len()
+   }
+
+   ///
+   pub fn get(&self, name: &str) -> &Account {
+      self.list.iter()
+         .find(|account| account.name == name)
+         .expect("Account should have been opened")
+   }
+
+   /// Account after `name`, wrapping around
+   pub fn next_name(&self, name: &str) -> String {
+      let index = self.list.iter().position(|account| account.name == name).unwrap_or(0);
+      self.list[(index + 1) % self.list.len()].name.clone()
+   }
+
+   /// Account a mail belongs to
+   pub fn mail_account(&self, chain: &SnapmailChain, hh: &ActionHash) -> Option<&Account> {
+      let name = chain.mail_sid_map.get(hh)?;
+      Some(self.get(name))
+   }
+
+   /// API of the account a mail belongs to
+   pub fn mail_api(&self, chain: &SnapmailChain, hh: &ActionHash) -> Option<Arc<dyn SnapmailApi>> {
+      Some(self.mail_account(chain, hh)?.api.clone())
+   }
+
+   /// Chain to display: the account's chain, or all accounts merged ("All accounts" view)
+   pub fn view_chain(&self, name: &str, all_accounts: bool) -> SnapmailChain {
+      let current = &self.get(name).chain;
+      if !all_accounts {
+         return current.clone();
+      }
+      let mut chains = vec![current];
+      for account in self.list.iter().filter(|account| account.name != name) {
+         chains.push(&account.chain);
+      }
+      SnapmailChain::merge(&chains)
+   }
+}
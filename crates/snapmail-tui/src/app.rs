@@ -2,22 +2,35 @@ use std::string::ToString;
 use snapmail_common::{
    attachment::*,
    globals::*,
+   logging::LogBuffer,
+   settings::*,
+   config::*,
+   networks::SessionNetworks,
+   api::SnapmailApi,
+   peers::*,
+   trash::*,
+   labels::*,
+   read_flags::*,
+   blocklist::*,
+   auto_download::mail_folder,
 };
 use crate::{
       menu::*,
       tables::{MailTable, ContactsTable, AttachmentsTable},
       snapmail_chain::SnapmailChain,
+      accounts::Accounts,
 };
 use tui::style::Color;
+use crossterm::event::KeyCode;
 use holochain_types::dna::*;
 use snapmail::{
    api_error::*,
    mail::*,
    mail::entries::*,
-   handle::*,
 };
-use holochain::conductor::ConductorHandle;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
 
 
 #[derive(AsStaticStr, ToString, Copy, Clone, Debug, PartialEq)]
@@ -37,6 +50,8 @@ pub enum InputVariable {
    Attachment,
    Subject,
    DownloadFolder,
+   Signature,
+   DefaultRecipients,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -44,10 +59,51 @@ pub enum AppCommand {
    None,
    SendMail,
    AcknowledgeMail(ActionHash),
+   MarkRead(ActionHash),
+   MarkUnread(ActionHash),
    DeleteMail,
+   RestoreMail,
+   EmptyTrash,
+   BlockSender(ActionHash),
+   UnblockSender(ActionHash),
    UpdateHandle,
 }
 
+/// Default key of each action that can be rebound in the settings' keybindings
+pub const DEFAULT_KEYBINDINGS: [(&str, char); 15] = [
+   ("quit", 'q'),
+   ("view", 'v'),
+   ("write", 'w'),
+   ("settings", 'e'),
+   ("logs", 'l'),
+   ("peers", 'o'),
+   ("inbox", 'i'),
+   ("sent", 's'),
+   ("trash", 't'),
+   ("junk", 'j'),
+   ("all", 'a'),
+   ("empty_trash", 'x'),
+   ("next_folder", 'f'),
+   ("next_identity", 'n'),
+   ("all_accounts", 'c'),
+];
+
+/// Default key of each View screen action that can be rebound.
+/// Only resolved on the View screen, as other screens use the same keys.
+pub const VIEW_KEYBINDINGS: [(&str, char); 3] = [
+   ("acknowledge", 'k'),
+   ("unread", 'u'),
+   ("block", 'b'),
+];
+
+/// Result of a background refresh of the peers panel
+pub struct PeersRefresh {
+   /// Account the refresh was started for
+   pub account: String,
+   pub peers: anyhow::Result<Vec<PeerInfo>>,
+   pub service_checks: anyhow::Result<Vec<ServiceCheck>>,
+}
+
 /// App holds the state of the application
 pub struct App {
    /// Current value of the input box
@@ -57,13 +113,21 @@ pub struct App {
    /// Current settings to change
    pub input_variable: InputVariable,
 
+   /// Current identity: account used for sending
+   pub account: String,
+   /// Session of the current identity, used for settings
    pub sid: String,
    pub uid: String,
+   /// View mails of all opened sessions
+   pub all_accounts: bool,
 
    pub content_width: usize,
    pub scroll_y: u16,
 
    pub download_folder: PathBuf,
+   pub settings: SessionSettings,
+   pub proxy_url: String,
+   pub bootstrap_url: String,
 
    pub command: AppCommand,
 
@@ -71,6 +135,8 @@ pub struct App {
 
    pub active_menu_item: TopMenuItem,
    pub active_folder_item: FolderItem,
+   /// Saved folders of the current identity, shown after the built-in folders
+   pub saved_folders: Vec<SavedFolder>,
 
    pub mail_table: MailTable,
 
@@ -82,6 +148,13 @@ pub struct App {
    pub active_write_block: WriteBlock,
 
    pub peer_count: usize,
+   /// Agents known on the network of the current identity, shown in the peers panel
+   pub peers: Vec<PeerInfo>,
+   /// Reachability of the bootstrap and proxy services of the current identity
+   pub service_checks: Vec<ServiceCheck>,
+   /// Peers panel refreshes run in the background, as probing services can take seconds
+   peers_tx: Sender<PeersRefresh>,
+   peers_rx: Receiver<PeersRefresh>,
 
    /// - Debug
    pub frame_count: u32,
@@ -89,31 +162,28 @@ pub struct App {
    /// History of recorded messages
    pub feedback_index: u32,
    pub feedbacks: Vec<(String, Color, Color)>,
+
+   /// Captured logs, shown in the logs panel
+   pub log_buffer: LogBuffer,
+   /// Number of lines scrolled up from the bottom of the logs panel
+   pub logs_scroll: usize,
+   /// Empty trash key was pressed once and awaits confirmation
+   pub confirm_empty_trash: bool,
 }
 
 impl App {
    ///
-   pub fn new(sid: String, chain: &SnapmailChain) -> App {
+   pub fn new(account: String, all_accounts: bool, accounts: &Accounts, log_buffer: LogBuffer) -> App {
       /// Setup Tables
-      let mail_list = filter_chain(&chain, FolderItem::Inbox);
-      let mail_table = MailTable::new(mail_list, &chain.handle_map, 12);
-      let contacts_table = ContactsTable::new(&chain.handle_map);
+      let chain = accounts.view_chain(&account, all_accounts);
+      let mail_list = filter_chain(&chain, FolderItem::Inbox, &[]);
+      let maybe_sid_map = if all_accounts { Some(&chain.mail_sid_map) } else { None };
+      let mail_table = MailTable::new(mail_list, &chain.handle_map, 12, maybe_sid_map, &chain.read);
 
-      /// Get UID
-      let path = CONFIG_PATH.as_path().join(sid.clone());
-      let app_filepath = path.join(APP_CONFIG_FILENAME);
-      let uid = std::fs::read_to_string(app_filepath)
-         .unwrap_or("test-network".to_string());
-
-      /// Get Download folder
-      let dl_filepath = path.join(APP_DL_CONFIG_FILENAME);
-      let mut download_folder = std::env::current_dir().unwrap();
-       if let Ok(s) = std::fs::read_to_string(dl_filepath) {
-          download_folder = PathBuf::from(s);
-       }
+      let (peers_tx, peers_rx) = mpsc::channel();
 
       /// Done
-      App {
+      let mut app = App {
          input: String::new(),
          input_mode: InputMode::Navigation,
          input_variable: InputVariable::Content,
@@ -124,20 +194,83 @@ impl App {
          frame_count: 0,
          active_menu_item: TopMenuItem::View,
          active_folder_item: FolderItem::Inbox,
-         sid,
-         uid,
+         saved_folders: Vec::new(),
+         account: String::new(),
+         sid: String::new(),
+         uid: String::new(),
+         all_accounts,
          peer_count: 0,
+         peers: Vec::new(),
+         service_checks: Vec::new(),
+         peers_tx,
+         peers_rx,
          content_width: 12,
-         download_folder,
+         download_folder: PathBuf::new(),
+         settings: SessionSettings::default(),
+         proxy_url: String::new(),
+         bootstrap_url: String::new(),
          scroll_y: 0,
          mail_table,
-         contacts_table,
+         contacts_table: ContactsTable::new(&HashMap::new()),
          active_write_block: WriteBlock::None,
          write_subject: String::new(),
          write_content: String::new(),
          write_attachment: String::new(),
          //write_attachments: Vec::new(), // TODO multi attachment support
+         log_buffer,
+         logs_scroll: 0,
+         confirm_empty_trash: false,
+      };
+      app.switch_identity(account, accounts);
+      app
+   }
+
+   /// Make another opened account the current identity and load its settings and contacts
+   pub fn switch_identity(&mut self, account: String, accounts: &Accounts) {
+      self.sid = accounts.get(&account).sid.clone();
+      self.account = account;
+
+      /// Get settings
+      self.settings = SessionSettings::load(&self.sid).unwrap_or_default();
+      self.download_folder = self.settings.download_folder();
+
+      /// Get UID
+      let path = config_path().join(self.sid.clone());
+      self.uid = SessionNetworks::load(&self.sid).ok()
+         .and_then(|networks| networks.get(&accounts.get(&self.account).alias).ok().map(|entry| entry.network_seed.clone()))
+         .unwrap_or("test-network".to_string());
+
+      /// Get network URLs
+      let (maybe_proxy, maybe_bootstrap) = match read_config(path.clone()) {
+         Ok(Some(config)) => network_urls(&config),
+         _ => (None, None),
+      };
+      self.proxy_url = maybe_proxy.map(|url| url.to_string()).unwrap_or_default();
+      self.bootstrap_url = maybe_bootstrap.map(|url| url.to_string()).unwrap_or_default();
+
+      /// Get saved folders
+      self.saved_folders = MailLabels::load(&self.sid).map(|labels| labels.folders).unwrap_or_default();
+      if let FolderItem::Saved(index) = self.active_folder_item {
+         if index >= self.saved_folders.len() {
+            self.active_folder_item = FolderItem::Inbox;
+         }
       }
+
+      /// Contacts are the agents of this account's network
+      self.reset_contacts(accounts);
+   }
+
+   /// Rebuild the contacts table from the current identity's network and select default recipients
+   fn reset_contacts(&mut self, accounts: &Accounts) {
+      self.contacts_table = ContactsTable::new(&accounts.get(&self.account).chain.handle_map);
+      self.contacts_table.select_recipients(&self.settings.default_recipients);
+   }
+
+   /// Mail table of the active folder
+   fn new_mail_table(&self, chain: &SnapmailChain) -> MailTable {
+      let mail_list = filter_chain(&chain, self.active_folder_item, &self.saved_folders);
+      let maybe_sid_map = if self.all_accounts { Some(&chain.mail_sid_map) } else { None };
+      MailTable::new(mail_list, &chain.handle_map, self.content_width, maybe_sid_map, &chain.read)
    }
 
    ///
@@ -157,12 +290,11 @@ impl App {
    ///
    pub fn resize_width(&mut self, new_width: u16, chain: &SnapmailChain) {
       self.content_width = new_width as usize;
-      let mail_list = filter_chain(&chain, self.active_folder_item);
-      self.mail_table = MailTable::new(mail_list, &chain.handle_map, self.content_width);
+      self.mail_table = self.new_mail_table(chain);
    }
 
    ///
-   pub fn try_download(&mut self, conductor: ConductorHandle, index: usize) {
+   pub fn try_download(&mut self, accounts: &Accounts, chain: &SnapmailChain, index: usize) {
       let maybe_info = self.attachments_table.manifest_index_map.get(&index);
       if let None = maybe_info {
          let msg = format!("No attachment at index {}", index);
@@ -170,8 +302,18 @@ impl App {
          return;
       }
       let info = maybe_info.unwrap();
+      let maybe_api = self.mail_table.state.selected()
+         .and_then(|i| self.mail_table.mail_index_map.get(&i))
+         .and_then(|hh| accounts.mail_api(chain, hh));
+      let api = match maybe_api {
+         Some(api) => api,
+         None => {
+            self.feedback_ext("No mail selected", Color::Yellow, Color::Black);
+            return;
+         },
+      };
       let maybe_path = get_attachment(
-         conductor.clone(),
+         api.as_ref(),
          info.manifest_eh.clone(),
          self.download_folder.clone(),
       );
@@ -184,20 +326,129 @@ impl App {
       }
    }
 
-   ///
-   fn delete_mail(&mut self, conductor: ConductorHandle) {
-      if let Some(index) = self.mail_table.state.selected() {
-         let hh = self.mail_table.mail_index_map.get(&index).unwrap().clone();
-         let res = snapmail_delete_mail(conductor, hh);
-         if let Ok(output) = res {
-            if let Some(hh2) = output.0 {
-               let msg = &format!("Deleted mail {}", hh2);
-               self.feedback_ext(&msg, Color::Green, Color::Black);
-               return;
-            }
+   /// Download all attachments of the selected mail.
+   /// Uses the auto-download folder tree when auto-download is enabled.
+   pub fn download_all(&mut self, accounts: &Accounts, chain: &SnapmailChain) {
+      let Some(hh) = self.selected_mail() else {
+         return self.feedback_ext("No mail selected", Color::Yellow, Color::Black);
+      };
+      let (Some(item), Some(api)) = (chain.mail_map.get(&hh), accounts.mail_api(chain, &hh)) else {
+         return self.feedback_ext("Could not find account of selected mail", Color::Yellow, Color::Black);
+      };
+      if item.mail.attachments.is_empty() {
+         return self.feedback_ext("Selected mail has no attachment", Color::Yellow, Color::Black);
+      }
+      let folder = if self.settings.auto_download.enabled {
+         let sender = chain.handle_map.get(&item.author).cloned().unwrap_or_else(|| item.author.to_string());
+         mail_folder(&self.download_folder, &self.settings.auto_download.folder_template, &sender, item)
+      } else {
+         self.download_folder.clone()
+      };
+      let results = match get_mail_attachments(api.as_ref(), &item.mail.attachments, &folder) {
+         Ok(results) => results,
+         Err(e) => return self.feedback_ext(&format!("Failed to create {:?}: {}", folder, e), Color::Red, Color::Black),
+      };
+      let failure_count = results.iter().filter(|res| res.is_err()).count();
+      if failure_count > 0 {
+         let msg = format!("{} of {} attachment(s) failed to download", failure_count, results.len());
+         self.feedback_ext(&msg, Color::Red, Color::Black);
+      } else {
+         let msg = format!("{} attachment(s) written in {:?}", results.len(), folder);
+         self.feedback_ext(&msg, Color::Green, Color::Black);
+      }
+   }
+
+   /// Move the selected mail to the trash, or delete it for good if it is already trashed
+   fn delete_mail(&mut self, accounts: &Accounts, chain: &SnapmailChain) {
+      let Some(hh) = self.selected_mail() else {
+         return self.feedback_ext("No mail selected", Color::Yellow, Color::Black);
+      };
+      let Some(account) = accounts.mail_account(chain, &hh) else {
+         return self.feedback_ext("Could not find account of selected mail", Color::Yellow, Color::Black);
+      };
+      let (res, done) = if chain.trashed.contains(&hh) {
+         (purge_mail(&account.sid, account.api.as_ref(), &hh), "Deleted for good")
+      } else {
+         (trash_mail(&account.sid, account.api.as_ref(), &hh), "Trashed")
+      };
+      match res {
+         Ok(()) => self.feedback_ext(&format!("{} mail {}", done, hh), Color::Green, Color::Black),
+         Err(e) => self.feedback_ext(&format!("Could not delete selected mail: {}", e), Color::Yellow, Color::Black),
+      }
+   }
+
+   /// Take the selected mail out of the trash
+   fn restore_mail(&mut self, accounts: &Accounts, chain: &SnapmailChain) {
+      let Some(hh) = self.selected_mail() else {
+         return self.feedback_ext("No mail selected", Color::Yellow, Color::Black);
+      };
+      let Some(account) = accounts.mail_account(chain, &hh) else {
+         return self.feedback_ext("Could not find account of selected mail", Color::Yellow, Color::Black);
+      };
+      match restore_mail(&account.sid, &hh) {
+         Ok(()) => self.feedback_ext(&format!("Restored mail {}", hh), Color::Green, Color::Black),
+         Err(e) => self.feedback_ext(&format!("Could not restore selected mail: {}", e), Color::Yellow, Color::Black),
+      }
+   }
+
+   /// Delete for good the trashed mails of every account shown
+   fn empty_trash(&mut self, accounts: &Accounts) {
+      let names: Vec<String> = if self.all_accounts {
+         accounts.list.iter().map(|account| account.name.clone()).collect()
+      } else {
+         vec![self.account.clone()]
+      };
+      let mut count = 0;
+      for name in names.iter() {
+         let account = accounts.get(name);
+         match empty_trash(&account.sid, account.api.as_ref()) {
+            Ok(purged) => count += purged.len(),
+            Err(e) => self.feedback_ext(&format!("Failed to empty trash of {}: {}", name, e), Color::Red, Color::Black),
          }
       }
-      self.feedback_ext("Could not delete selected mail", Color::Yellow, Color::Black);
+      self.feedback_ext(&format!("{} mail(s) deleted for good", count), Color::Green, Color::Black);
+   }
+
+   /// Block or unblock the sender of a mail. Unblocked senders are never flagged as spam.
+   fn set_sender_blocked(&mut self, accounts: &Accounts, chain: &SnapmailChain, hh: &ActionHash, blocked: bool) {
+      let Some(item) = chain.mail_map.get(hh) else { return };
+      if !matches!(item.state, MailState::In(_)) {
+         return self.feedback_ext("Only senders of received mails can be blocked", Color::Yellow, Color::Black);
+      }
+      let Some(account) = accounts.mail_account(chain, hh) else {
+         return self.feedback_ext("Could not find account of selected mail", Color::Yellow, Color::Black);
+      };
+      let sender = chain.handle_map.get(&item.author).cloned().unwrap_or_else(|| item.author.to_string());
+      let res = Blocklist::load(&account.sid).and_then(|mut blocklist| {
+         let changed = if blocked { blocklist.block(&item.author, &sender) } else { blocklist.unblock(&item.author) };
+         blocklist.save(&account.sid)?;
+         Ok((changed, blocklist.is_blocked(&item.author, Some(&sender))))
+      });
+      let msg = match res {
+         Ok((_, true)) if !blocked => format!("{} still matches a blocked handle pattern", sender),
+         Ok((true, _)) if blocked => format!("Blocked {}", sender),
+         Ok((false, _)) if blocked => format!("{} is already blocked", sender),
+         Ok(_) => format!("Unblocked {}", sender),
+         Err(e) => return self.feedback_ext(&format!("Failed to update blocklist: {}", e), Color::Red, Color::Black),
+      };
+      self.feedback_ext(&msg, Color::Green, Color::Black);
+   }
+
+   /// Block the sender of the selected mail, or unblock it if its mail is blocked or spam
+   pub fn toggle_block_selected_sender(&mut self, chain: &SnapmailChain) {
+      let Some(hh) = self.selected_mail() else {
+         return self.feedback_ext("No mail selected", Color::Yellow, Color::Black);
+      };
+      self.command = match chain.mail_class(&hh) {
+         MailClass::Normal => AppCommand::BlockSender(hh),
+         MailClass::Blocked | MailClass::Spam => AppCommand::UnblockSender(hh),
+      };
+   }
+
+   ///
+   fn selected_mail(&self) -> Option<ActionHash> {
+      let index = self.mail_table.state.selected()?;
+      self.mail_table.mail_index_map.get(&index).cloned()
    }
 
    ///
@@ -205,7 +456,9 @@ impl App {
       self.mail_table.next();
       if let Some(index) = self.mail_table.state.selected() {
          let hh = self.mail_table.mail_index_map.get(&index).unwrap().clone();
-         self.command = AppCommand::AcknowledgeMail(hh.clone());
+         if !chain.read.contains(&hh) {
+            self.command = AppCommand::MarkRead(hh.clone());
+         }
          self.feedback(&format!("Reading mail: {}", hh));
          /// Attachment
          let item = chain.mail_map.get(&hh).unwrap();
@@ -225,7 +478,9 @@ impl App {
             Some(h) => h.clone(),
             None => return,
          };
-         self.command = AppCommand::AcknowledgeMail(hh.clone());
+         if !chain.read.contains(&hh) {
+            self.command = AppCommand::MarkRead(hh.clone());
+         }
          self.feedback(&format!("Reading mail: {}", hh));
          /// Attachment
          let item = chain.mail_map.get(&hh).unwrap();
@@ -238,13 +493,12 @@ impl App {
    }
 
    ///
-   pub fn update_data(&mut self, chain: &SnapmailChain) {
+   pub fn update_data(&mut self, chain: &SnapmailChain, accounts: &Accounts) {
       /// Update mail table && keep current selection
-      let mail_list = filter_chain(&chain, self.active_folder_item);
       let maybe_hh = if let Some(i) = self.mail_table.state.selected() {
-         Some(self.mail_table.mail_index_map.get(&i).unwrap().clone())
+         self.mail_table.mail_index_map.get(&i).cloned()
       } else { None };
-      self.mail_table = MailTable::new(mail_list, &chain.handle_map, self.content_width);
+      self.mail_table = self.new_mail_table(chain);
       if let Some(hh) = maybe_hh {
          for (index, current) in &self.mail_table.mail_index_map {
             if *current == hh {
@@ -253,7 +507,100 @@ impl App {
          }
       }
       // Update contacts table
-      self.contacts_table = ContactsTable::new(&chain.handle_map);
+      self.contacts_table = ContactsTable::new(&accounts.get(&self.account).chain.handle_map);
+   }
+
+   /// Start reloading the peers panel in the background: known agents and network services of the current identity.
+   /// The panel is updated by [`App::update_peers`] once done.
+   pub fn refresh_peers(&mut self, accounts: &Accounts) {
+      let Some(cell) = accounts.get(&self.account).maybe_cell.clone() else {
+         self.peers = Vec::new();
+         self.service_checks = Vec::new();
+         self.feedback("No conductor for this account");
+         return;
+      };
+      let account = self.account.clone();
+      let sid = self.sid.clone();
+      let peers_tx = self.peers_tx.clone();
+      tokio::spawn(async move {
+         let peers = list_peers(&cell).await;
+         let service_checks = check_services(&sid).await;
+         let _ = peers_tx.send(PeersRefresh { account, peers, service_checks });
+      });
+      self.feedback("Refreshing peers...");
+   }
+
+   /// Show the results of finished peers panel refreshes of the current identity
+   pub fn update_peers(&mut self) {
+      while let Ok(refresh) = self.peers_rx.try_recv() {
+         if refresh.account != self.account {
+            continue;
+         }
+         match refresh.peers {
+            Ok(peers) => self.peers = peers,
+            Err(e) => self.feedback_ext(&format!("Failed to list peers: {}", e), Color::Red, Color::Black),
+         }
+         match refresh.service_checks {
+            Ok(checks) => self.service_checks = checks,
+            Err(e) => self.feedback_ext(&format!("Failed to check network services: {}", e), Color::Red, Color::Black),
+         }
+      }
+   }
+
+   /// Write settings to disk and notify failure
+   pub fn save_settings(&mut self) {
+      if let Err(e) = self.settings.save(&self.sid) {
+         self.feedback_ext(&format!("Failed to save settings: {}", e), Color::Black, Color::Red);
+      }
+   }
+
+   /// Change the proxy or bootstrap URL of the conductor config.
+   /// Conductor must be restarted for the change to take effect.
+   pub fn change_network(&mut self, change: NetworkChange) {
+      let path = config_path().join(self.sid.clone());
+      match change_network(path, &change, false) {
+         Err(e) => self.feedback_ext(&format!("Network change failed: {}", e), Color::Black, Color::Red),
+         Ok(diff) => {
+            if let Some(proxy) = change.maybe_proxy {
+               self.proxy_url = proxy.to_string();
+            }
+            if change.remove_proxy {
+               self.proxy_url = String::new();
+            }
+            if let Some(bootstrap) = change.maybe_bootstrap {
+               self.bootstrap_url = bootstrap.to_string();
+            }
+            let msg = format!("Conductor config changed ({} lines). Restart to apply.", diff.len());
+            self.feedback_ext(&msg, Color::Green, Color::Black);
+         },
+      }
+   }
+
+   /// Translate a key rebound in the settings to the default key of its action.
+   /// The default key of a rebound action does nothing.
+   pub fn resolve_key(&self, key_code: KeyCode) -> KeyCode {
+      let KeyCode::Char(c) = key_code else { return key_code };
+      let view_keybindings: &[(&str, char)] = if self.active_menu_item == TopMenuItem::View { &VIEW_KEYBINDINGS } else { &[] };
+      let keybindings = || DEFAULT_KEYBINDINGS.iter().chain(view_keybindings.iter());
+      for (action, default_key) in keybindings() {
+         if self.settings.keybindings.get(*action) == Some(&c) {
+            return KeyCode::Char(*default_key);
+         }
+      }
+      for (action, default_key) in keybindings() {
+         if *default_key == c && self.settings.keybindings.contains_key(*action) {
+            return KeyCode::Null;
+         }
+      }
+      key_code
+   }
+
+   /// Highlight color of the current theme
+   pub fn accent_color(&self) -> Color {
+      match self.settings.theme {
+         Theme::Dark => Color::Yellow,
+         Theme::Light => Color::Blue,
+      }
    }
 
    pub fn feedback(&mut self, msg: &str) {
@@ -270,11 +617,12 @@ impl App {
 
    /// Process the AppCommand stored in self.command
    /// Returns true if chain should be updated
-   pub fn process_command(&mut self, conductor: ConductorHandle, chain: &SnapmailChain) -> bool {
+   pub fn process_command(&mut self, accounts: &Accounts, chain: &SnapmailChain) -> bool {
       let mut can_update_chain = false;
+      let api = accounts.get(&self.account).api.clone();
       match &self.command {
          AppCommand::SendMail => {
-            let res = self.send_mail(conductor.clone(), chain);
+            let res = self.send_mail(api.as_ref(), accounts);
             self.input_mode = InputMode::Navigation;
             match res {
                Err(e) => self.feedback_ext(&format!("Send mail failed: {}", e), Color::Black, Color::Red),
@@ -282,26 +630,59 @@ impl App {
             }
          },
          AppCommand::AcknowledgeMail(hh) => {
-            if let Some(mail_item) = chain.mail_map.get(hh) {
+            if chain.mail_class(hh) == MailClass::Blocked {
+               self.feedback_ext("Mails from blocked senders are never acknowledged", Color::Yellow, Color::Black);
+            } else if let Some(mail_item) = chain.mail_map.get(hh) {
                match mail_item.state {
                   MailState::In(InMailState::Unacknowledged) => {
-                     let res = snapmail_acknowledge_mail(conductor, hh.clone());
-                     if let Ok(_entry_hash) = res {
-                        let msg = format!("Mail acknowledged: {}", hh);
-                        self.feedback_ext(&msg, Color::Green, Color::Black);
-                        can_update_chain = true;
+                     let api = accounts.mail_api(chain, hh).unwrap_or(api);
+                     match api.acknowledge_mail(hh.clone()) {
+                        Ok(_entry_hash) => {
+                           let msg = format!("Mail acknowledged: {}", hh);
+                           self.feedback_ext(&msg, Color::Green, Color::Black);
+                           can_update_chain = true;
+                        },
+                        Err(e) => self.feedback_ext(&format!("Failed to acknowledge mail: {:?}", e), Color::Red, Color::Black),
                      }
                   }
-                  _ => {},
+                  MailState::In(_) => {
+                     let msg = format!("Mail already acknowledged: {}", hh);
+                     self.feedback(&msg);
+                  },
+                  MailState::Out(_) => self.feedback("Only received mails can be acknowledged"),
+               }
+            }
+         },
+         AppCommand::MarkRead(hh) | AppCommand::MarkUnread(hh) => {
+            let is_read = matches!(self.command, AppCommand::MarkRead(_));
+            if let Some(account) = accounts.mail_account(chain, hh) {
+               let res = if is_read { mark_read(&account.sid, hh) } else { mark_unread(&account.sid, hh) };
+               match res {
+                  Ok(changed) => can_update_chain = changed,
+                  Err(e) => self.feedback_ext(&format!("Failed to flag mail: {}", e), Color::Red, Color::Black),
                }
             }
          },
          AppCommand::DeleteMail => {
-            self.delete_mail(conductor);
+            self.delete_mail(accounts, chain);
+            can_update_chain = true;
+         },
+         AppCommand::RestoreMail => {
+            self.restore_mail(accounts, chain);
+            can_update_chain = true;
+         },
+         AppCommand::EmptyTrash => {
+            self.empty_trash(accounts);
+            can_update_chain = true;
+         },
+         AppCommand::BlockSender(hh) | AppCommand::UnblockSender(hh) => {
+            let hh = hh.clone();
+            let blocked = matches!(self.command, AppCommand::BlockSender(_));
+            self.set_sender_blocked(accounts, chain, &hh, blocked);
             can_update_chain = true;
          },
          AppCommand::UpdateHandle => {
-            let res = snapmail_set_handle(conductor.clone(), self.input.clone());
+            let res = api.set_handle(self.input.clone());
             match res {
                Err(e) => self.feedback_ext(&format!("Set handle failed: {}", e), Color::Black, Color::Red),
                Ok(hash) => {
@@ -320,11 +701,52 @@ impl App {
    pub fn update_active_folder(&mut self, chain: &SnapmailChain, folder_item: FolderItem) {
       if self.active_menu_item == TopMenuItem::View {
          self.active_folder_item = folder_item;
-         let mail_list = filter_chain(&chain, self.active_folder_item);
-         self.mail_table = MailTable::new(mail_list, &chain.handle_map, self.content_width);
+         self.mail_table = self.new_mail_table(chain);
+      }
+   }
+
+   /// Acknowledge the selected mail if its sender's ack policy allows it when opened
+   pub fn open_selected_mail(&mut self, chain: &SnapmailChain) {
+      let Some(hh) = self.selected_mail() else { return };
+      let Some(item) = chain.mail_map.get(&hh) else { return };
+      if item.state != MailState::In(InMailState::Unacknowledged) || chain.mail_class(&hh) != MailClass::Normal {
+         return;
+      }
+      let maybe_sender = chain.handle_map.get(&item.author).map(|name| name.as_str());
+      if self.settings.acks_on_open(maybe_sender) {
+         self.command = AppCommand::AcknowledgeMail(hh);
+      }
+   }
+
+   /// Acknowledge the selected mail, whatever the ack policy
+   pub fn acknowledge_selected_mail(&mut self) {
+      match self.selected_mail() {
+         Some(hh) => self.command = AppCommand::AcknowledgeMail(hh),
+         None => self.feedback_ext("No mail selected", Color::Yellow, Color::Black),
+      }
+   }
+
+   /// Flag the selected mail as unread
+   pub fn unread_selected_mail(&mut self) {
+      match self.selected_mail() {
+         Some(hh) => self.command = AppCommand::MarkUnread(hh),
+         None => self.feedback_ext("No mail selected", Color::Yellow, Color::Black),
       }
    }
 
+   /// Show the saved folder after the current one, wrapping around
+   pub fn next_saved_folder(&mut self, chain: &SnapmailChain) {
+      if self.saved_folders.is_empty() {
+         self.feedback("No saved folder. Create one with: snapmail-cli <sid> label save-folder");
+         return;
+      }
+      let index = match self.active_folder_item {
+         FolderItem::Saved(index) => (index + 1) % self.saved_folders.len(),
+         _ => 0,
+      };
+      self.update_active_folder(chain, FolderItem::Saved(index));
+   }
+
    ///
    pub fn set_write_block(&mut self, block: WriteBlock) {
       if block == WriteBlock::None {
@@ -388,7 +810,7 @@ impl App {
    }
 
    ///
-   pub fn send_mail(&mut self, conductor: ConductorHandle, chain: &SnapmailChain) -> SnapmailApiResult<()> {
+   pub fn send_mail(&mut self, api: &dyn SnapmailApi, accounts: &Accounts) -> SnapmailApiResult<()> {
       /// Form recepient lists from ContactsTable
       let mut to_list: Vec<AgentPubKey> = Vec::new();
       let mut cc_list: Vec<AgentPubKey> = Vec::new();
@@ -426,7 +848,7 @@ impl App {
 
       if !self.write_attachment.is_empty() {
          let path = PathBuf::from(self.write_attachment.clone());
-         let maybe_hh = write_attachment(conductor.clone(), path);
+         let maybe_hh = write_attachment(api, path);
          match maybe_hh {
             Ok(hh) => manifest_address_list.push(hh),
             Err(e) => {
@@ -437,9 +859,14 @@ impl App {
       }
 
       /// Form MailInput
+      let payload = if self.settings.signature.is_empty() {
+         self.write_content.clone()
+      } else {
+         format!("{}\n\n{}", self.write_content, self.settings.signature)
+      };
       let mail = SendMailInput {
          subject: self.write_subject.clone(),
-         payload: self.write_content.clone(),
+         payload,
          to: to_list,
          cc: cc_list,
          bcc: bcc_list,
@@ -447,13 +874,13 @@ impl App {
       };
       //let send_count = mail.to.len() + mail.cc.len() + mail.bcc.len();
       /// Send
-      let sent_hh = snapmail_send_mail(conductor.clone(), mail)?;
+      let sent_hh = api.send_mail(mail)?;
       /// Get State
-      let mail_state = snapmail_get_outmail_state(conductor, sent_hh.clone())?;
+      let mail_state = api.get_outmail_state(sent_hh.clone())?;
       /// Show result
       // let pending_count = output.to_pendings.len() + output.cc_pendings.len() + output.bcc_pendings.len();
       // let message = format!("Mail sent. Pendings:  {} / {} ({})", pending_count, send_count, output.outmail);
-      let message = format!("Mail sent: {:?} ({})", mail_state, sent_hh);
+      let message = format!("Mail sent from {}: {:?} ({})", self.account, mail_state, sent_hh);
       let fg_color =  match mail_state {
          OutMailState::Unsent => Color::Yellow,
          OutMailState::AllSent => Color::Magenta,
@@ -469,54 +896,37 @@ impl App {
       self.write_attachment = String::new();
       // self.write_attachments = Vec::new(); // TODO multi attachments support
       self.write_subject = String::new();
-      self.contacts_table = ContactsTable::new(&chain.handle_map);
+      self.reset_contacts(accounts);
       Ok(())
    }
 }
 
 ///
-pub fn filter_chain(chain: &SnapmailChain, folder: FolderItem) -> Vec<MailItem> {
+pub fn filter_chain(chain: &SnapmailChain, folder: FolderItem, saved_folders: &[SavedFolder]) -> Vec<MailItem> {
    let mut res = Vec::new();
-   match folder {
-      FolderItem::Inbox => {
-         for item in chain.mail_map.values() {
-            if let MailState::In(state) = &item.state {
-               if state != &InMailState::Deleted {
-                  res.push(item.clone());
-               }
-            }
-         }
-      }
-      FolderItem::Sent => {
-         for item in chain.mail_map.values() {
-            if let MailState::Out(state) = &item.state {
-               if state != &OutMailState::Deleted {
-                  res.push(item.clone());
-               }
-            }
-         }
-      }
-      FolderItem::All => {
-         for item in chain.mail_map.values() {
-            res.push(item.clone());
-         }
-      }
-      FolderItem::Trash => {
-         for item in chain.mail_map.values() {
-            match &item.state {
-               MailState::Out(state) => {
-                  if let OutMailState::Deleted = state {
-                     res.push(item.clone());
-                  }
-               }
-               MailState::In(state) => {
-                  if let InMailState::Deleted = state {
-                     res.push(item.clone());
-                  }
-               }
-            }
-         }
+   for item in chain.mail_map.values() {
+      let is_trashed = chain.trashed.contains(&item.address);
+      /// Mails from blocked senders are only shown in "All"
+      let class = chain.mail_class(&item.address);
+      let is_normal = class == MailClass::Normal;
+      let is_shown = match folder {
+         FolderItem::Inbox => matches!(item.state, MailState::In(_)) && is_normal && !is_trashed && !is_purged(item),
+         FolderItem::Sent => matches!(item.state, MailState::Out(_)) && !is_trashed && !is_purged(item),
+         FolderItem::All => true,
+         FolderItem::Trash => is_trashed && class != MailClass::Blocked && !is_purged(item),
+         FolderItem::Junk => class == MailClass::Spam && !is_trashed && !is_purged(item),
+         FolderItem::Saved(index) => match saved_folders.get(index) {
+            Some(saved) => is_normal && !is_trashed && !is_purged(item) && saved.query.matches(
+               item,
+               chain.handle_map.get(&item.author).map(|name| name.as_str()),
+               chain.labels.get(&item.address),
+            ),
+            None => false,
+         },
+      };
+      if is_shown {
+         res.push(item.clone());
       }
    }
    res
-}
\ No newline at end of file
+}
@@ -0,0 +1,17 @@
+#![allow(non_upper_case_globals)]
+#![allow(unused_doc_comments)]
+#![allow(unused_attributes)]
+
+//! Screens and state of snapmail-tui, also used by its tests
+
+#[macro_use]
+extern crate strum_macros;
+
+pub mod accounts;
+pub mod render;
+pub mod tables;
+pub mod app;
+pub mod listen_signal;
+pub mod menu;
+pub mod run;
+pub mod snapmail_chain;
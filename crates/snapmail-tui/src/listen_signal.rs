@@ -1,19 +1,54 @@
 use holochain_types::dna::*;
 use holochain_types::signal::*;
-use snapmail::mail::*;
 use snapmail::handle::*;
 use snapmail::signal_protocol::*;
-use holochain::conductor::ConductorHandle;
 use snapmail_common::{
    utils::*,
+   api::SnapmailApi,
+   cell::SnapmailCell,
+   rules::run_rules,
+   auto_download::run_auto_download,
 };
 use tokio_stream::{StreamExt};
 use futures_util::{self, pin_mut};
 use tokio::time::{Duration};
+use std::sync::Arc;
 use std::sync::mpsc::Sender;
 
-/// Listen to signals and display them in the feedback box
-pub async fn listen_signal(conductor: ConductorHandle, signal_tx: Sender<String>) -> anyhow::Result<()> {
+
+/// Run the inbox rules of session `sid` on new mails, then download their attachments if enabled.
+/// Hooks, forwards and downloads can take a while, so this runs in a blocking task
+/// and reports what it did in the feedback box.
+pub fn spawn_inbox_actions(
+   sid: String,
+   api: Arc<dyn SnapmailApi>,
+   new_mails: Vec<ActionHash>,
+   prefix: String,
+   signal_tx: Sender<String>,
+) {
+   if new_mails.is_empty() {
+      return;
+   }
+   tokio::task::spawn_blocking(move || {
+      let outcomes = run_rules(&sid, api.as_ref(), &new_mails);
+      if !outcomes.is_empty() {
+         let _res = signal_tx.send(format!("{}{} rule action(s) ran on new mails", prefix, outcomes.len()));
+      }
+      let paths = run_auto_download(&sid, api.as_ref(), &new_mails);
+      if !paths.is_empty() {
+         let _res = signal_tx.send(format!("{}{} attachment(s) downloaded", prefix, paths.len()));
+      }
+   });
+}
+
+
+/// Listen to signals and display them in the feedback box.
+/// Only signals of the account's cell are displayed.
+/// `prefix` is prepended to each message (ex: account name when several accounts are opened).
+/// Inbox rules and auto-download of session `sid` run on each received mail.
+pub async fn listen_signal(sid: String, cell: SnapmailCell, prefix: String, signal_tx: Sender<String>) -> anyhow::Result<()> {
+   let api: Arc<dyn SnapmailApi> = Arc::new(cell.clone());
+   let conductor = cell.conductor.clone();
    /// Add app interface so we can get signals
    let mut interfaces = conductor.list_app_interfaces().await?;
    if interfaces.is_empty() {
@@ -24,7 +59,7 @@ pub async fn listen_signal(conductor: ConductorHandle, signal_tx: Sender<String>
    // msg!("App Interfaces: {:?}", interfaces);
 
    /// Get handles from DHT
-   let mut handle_list = snapmail_get_all_handles(conductor.clone(), ())?;
+   let mut handle_list = cell.get_all_handles()?;
 
    /// Setup signal stream
    let signal_stream = conductor.signal_broadcaster().await.subscribe_merged();
@@ -44,8 +79,11 @@ pub async fn listen_signal(conductor: ConductorHandle, signal_tx: Sender<String>
             let _res = signal_tx.send(msg);
          },
          Ok(Some(signal)) => {
-            let msg = print_signal(conductor.clone(), &mut handle_list, signal);
-            let _res = signal_tx.send(msg);
+            let mut new_mails = Vec::new();
+            if let Some(msg) = print_signal(&cell, &mut handle_list, signal, &mut new_mails) {
+               let _res = signal_tx.send(format!("{}{}", prefix, msg));
+            }
+            spawn_inbox_actions(sid.clone(), api.clone(), new_mails, prefix.clone(), signal_tx.clone());
          },
       };
       tokio::time::sleep(Duration::from_millis(10)).await;
@@ -53,33 +91,45 @@ pub async fn listen_signal(conductor: ConductorHandle, signal_tx: Sender<String>
 }
 
 
-///
-fn print_signal(conductor: ConductorHandle, handle_list: &mut Vec<HandleItem>, signal: Signal) -> String {
+/// Received mails are added to `new_mails`
+fn print_signal(
+   cell: &SnapmailCell,
+   handle_list: &mut Vec<HandleItem>,
+   signal: Signal,
+   new_mails: &mut Vec<ActionHash>,
+) -> Option<String> {
    match signal {
-      Signal::App(_cell_id, app_signal) => {
+      Signal::App(cell_id, app_signal) => {
+         /// Signals of other accounts sharing the conductor
+         if cell_id != cell.cell_id {
+            return None;
+         }
          let snapmail_signal: SignalProtocol = app_signal.into_inner().decode().unwrap();
-         return print_snapmail_signal(conductor, handle_list, snapmail_signal);
+         if let SignalProtocol::ReceivedMail(item) = &snapmail_signal {
+            new_mails.push(item.address.clone());
+         }
+         return Some(print_snapmail_signal(cell, handle_list, snapmail_signal));
       },
       Signal::System(system_signal) => {
-         return format!("{:?}", system_signal);
+         return Some(format!("{:?}", system_signal));
       },
    }
 }
 
 ///
 fn print_snapmail_signal(
-   conductor: ConductorHandle,
+   api: &dyn SnapmailApi,
    handle_list: &mut Vec<HandleItem>,
    signal: SignalProtocol,
 ) -> String {
    match signal {
       SignalProtocol::ReceivedMail(item) => {
-         let name = get_handle(conductor.clone(), handle_list, &item.author);
+         let name = get_handle(api, handle_list, &item.author);
          return format!("Received Mail from {}: \"{}\" ({})", name, item.mail.subject, item.address);
       }
       SignalProtocol::ReceivedAck(ack) => {
-         let name = get_handle(conductor.clone(), handle_list, &ack.from);
-         let maybe_mail = snapmail_get_mail(conductor.clone(), ack.for_mail.clone());
+         let name = get_handle(api, handle_list, &ack.from);
+         let maybe_mail = api.get_mail(ack.for_mail.clone());
          if let Err(_err) = maybe_mail {
             return format!("Received Acknowledgement for unknown mail {}", ack.for_mail);
          }
@@ -98,10 +148,10 @@ fn print_snapmail_signal(
    }
 }
 
-fn get_handle(conductor: ConductorHandle, handle_list: &mut Vec<HandleItem>, pubkey: &AgentPubKey) -> String {
+fn get_handle(api: &dyn SnapmailApi, handle_list: &mut Vec<HandleItem>, pubkey: &AgentPubKey) -> String {
    let maybe_name = get_name(handle_list, pubkey);
    if maybe_name.is_none() {
-      if let Ok(list) = snapmail_get_all_handles(conductor.clone(), ()) {
+      if let Ok(list) = api.get_all_handles() {
          *handle_list = list;
       }
       let maybe_name = get_name(handle_list, pubkey);
@@ -2,22 +2,15 @@
 #![allow(unused_doc_comments)]
 #![allow(unused_attributes)]
 
-#[macro_use]
-extern crate strum_macros;
-
-pub mod render;
-pub mod tables;
-pub mod app;
-pub mod listen_signal;
-pub mod menu;
-pub mod run;
-pub mod snapmail_chain;
-
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use std::io;
 use snapmail_common::{
    globals::*,
+   logging::*,
+   session::*,
+   settings::SessionSettings,
 };
+use snapmail_tui::accounts::parse_account_name;
 use tui::{
    Terminal,
    backend::CrosstermBackend,
@@ -25,47 +18,147 @@ use tui::{
 
 
 static USAGE_TEXT: &str = "USAGE:
-    snapmail-tui <sid>
+    snapmail-tui [FLAGS] [--root <dir>] [--log-format <format>] [sid[@network]...]
 
 FLAGS:
     -l, List available Session IDs
     -h, Prints help information
     -V, Prints version information
+    -v, --verbose    Increase log verbosity (-v, -vv, -vvv)
+    -q, --quiet      Only log errors
+        --log-file   Also write daily rotated log files in the first session's logs folder
+        --wasm-log   Capture logs emitted by the zome WASM
+
+OPTIONS:
+    --root <dir>             Folder holding all sessions. Defaults to $SNAPMAIL_HOME or the system's config folder.
+    --log-format <format>    Log output format: \"pretty\" or \"json\". Defaults to the first session's settings.
 
 ARGS:
-    <sid>    Session ID. Corresponds to an unique config, network id and agent
+    <sid>...    Session IDs. Each corresponds to an unique config, network id and agent.
+                Defaults to $SNAPMAIL_SESSION or the default session set with snapmail-cli.
+                Append @<alias> to open another network joined by the session (ex: alex@work).
+                With several sessions, the first one is the starting identity.
     ";
 
+
+/// Logging options of the command line
+#[derive(Debug, Default)]
+struct LogArgs {
+   verbosity: i8,
+   quiet: bool,
+   maybe_format: Option<LogFormat>,
+   log_file: bool,
+   wasm_log: bool,
+}
+
+impl LogArgs {
+   /// Take the logging options out of the command line args
+   fn take(args: &mut Vec<String>) -> Result<Self, String> {
+      let mut log_args = LogArgs::default();
+      let mut rest = Vec::new();
+      let mut iter = std::mem::take(args).into_iter();
+      while let Some(arg) = iter.next() {
+         match arg.as_str() {
+            "-q" | "--quiet" => log_args.quiet = true,
+            "--verbose" => log_args.verbosity += 1,
+            "--log-file" => log_args.log_file = true,
+            "--wasm-log" => log_args.wasm_log = true,
+            "--log-format" => {
+               let format = iter.next().ok_or("Missing format after --log-format")?;
+               log_args.maybe_format = Some(format.parse()?);
+            },
+            /// -v, -vv, -vvv
+            _ if arg.len() > 1 && arg.starts_with('-') && arg[1..].chars().all(|c| c == 'v') => {
+               log_args.verbosity += (arg.len() - 1) as i8;
+            },
+            _ => rest.push(arg),
+         }
+      }
+      *args = rest;
+      Ok(log_args)
+   }
+
+   /// Logging setup for a session. Logs go to the logs panel instead of stdout.
+   fn log_config(&self, sid: &str) -> LogConfig {
+      let format = self.maybe_format.unwrap_or_else(|| {
+         SessionSettings::load(sid).ok()
+            .and_then(|settings| settings.log_format.parse().ok())
+            .unwrap_or(LogFormat::Pretty)
+      });
+      LogConfig {
+         verbosity: if self.quiet { -1 } else { self.verbosity },
+         format,
+         maybe_log_dir: if self.log_file { Some(config_path().join(sid).join(LOG_FOLDER_NAME)) } else { None },
+         wasm_log: self.wasm_log,
+         stdout: false,
+      }
+   }
+}
+
+
 ///
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
 
    /// Parse args
-   let args: Vec<String> = std::env::args().collect();
+   let mut args: Vec<String> = std::env::args().collect();
    //println!("{:?}", args);
-   if args.len() != 2 {
-      println!("Wrong number of arguments. Expected:\n");
-      println!("{}", USAGE_TEXT);
-      return Ok(());
+   if let Some(index) = args.iter().position(|arg| arg == "--root") {
+      if index + 1 >= args.len() {
+         println!("Missing folder after --root. Expected:\n");
+         println!("{}", USAGE_TEXT);
+         return Ok(());
+      }
+      let root = args.remove(index + 1);
+      args.remove(index);
+      set_config_root(std::path::PathBuf::from(root));
    }
-   if args[1] == "-h" {
+   let log_args = match LogArgs::take(&mut args) {
+      Ok(log_args) => log_args,
+      Err(e) => {
+         println!("{}\n\n{}", e, USAGE_TEXT);
+         return Ok(());
+      }
+   };
+   let maybe_arg = args.get(1).cloned();
+   if maybe_arg.as_deref() == Some("-h") {
       println!("\n{}", USAGE_TEXT);
       return Ok(());
    }
-   if args[1] == "-v" {
+   if maybe_arg.as_deref() == Some("-V") {
       println!("\n{}", SNAPMAIL_VERSION);
       return Ok(());
    }
-   if args[1] == "-l" {
+
+   /// Environment is set before the runtime starts its threads
+   set_wasm_log_env(log_args.wasm_log);
+   let runtime = tokio::runtime::Runtime::new()?;
+
+   if maybe_arg.as_deref() == Some("-l") {
       println!("Available Session IDs: ");
-      let root = CONFIG_PATH.as_path().to_path_buf();
-      let paths = std::fs::read_dir(root).expect("Should have config dir set up during setup");
-      for path in paths {
-         println!(" - {}", path.unwrap().path().display());
+      for sid in list_sids()? {
+         let info = runtime.block_on(session_info(&sid, false));
+         let default_str = if info.is_default { " (default)" } else { "" };
+         let last_used = info.last_used.map(|d| d.format("%Y-%m-%d %H:%M").to_string()).unwrap_or("never".to_string());
+         println!(" - {}{} | network: {} | last used: {}",
+            sid, default_str, info.network_seed.unwrap_or_default(), last_used);
       }
       return Ok(());
    }
-   let sid = args[1].to_string();
+   let mut sids: Vec<String> = Vec::new();
+   for sid in args.iter().skip(1) {
+      if !sids.contains(sid) {
+         sids.push(sid.clone());
+      }
+   }
+   if sids.is_empty() {
+      match resolve_sid(None) {
+         Ok(sid) => sids.push(sid),
+         Err(e) => {
+            println!("{}\n\n{}", e, USAGE_TEXT);
+            return Ok(());
+         }
+      }
+   }
 
    /// Set raw mode ('Enter' not required)
    enable_raw_mode().expect("can run in raw mode");
@@ -76,14 +169,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
    let mut terminal = Terminal::new(backend)?;
    terminal.clear()?;
 
+   /// Capture logs for the logs panel instead of printing them
+   let log_buffer = LogBuffer::new();
+   let (first_sid, _) = parse_account_name(&sids[0]);
+   let log_config = log_args.log_config(&first_sid);
+   let _log_guard = init_logging(&log_config, Some(log_buffer.clone()))?;
+
    /// Run TUI app
-   std::env::set_var("WASM_LOG", "NONE");
-   std::env::set_var("RUST_LOG", "NONE");
-   let _res = crate::run::run(&mut terminal, sid).await;
+   let res = runtime.block_on(snapmail_tui::run::run(&mut terminal, sids, log_buffer));
 
    /// Clean up & Shutdown
    terminal.clear()?;
    disable_raw_mode()?;
    terminal.show_cursor()?;
+   if let Err(e) = res {
+      println!("{}", e);
+   }
    Ok(())
 }
@@ -4,6 +4,8 @@ pub enum TopMenuItem {
    View,
    Write,
    Settings,
+   Logs,
+   Peers,
 }
 
 impl From<TopMenuItem> for usize {
@@ -12,6 +14,8 @@ impl From<TopMenuItem> for usize {
          TopMenuItem::View => 0,
          TopMenuItem::Write => 1,
          TopMenuItem::Settings => 2,
+         TopMenuItem::Logs => 3,
+         TopMenuItem::Peers => 4,
       }
    }
 }
@@ -21,7 +25,11 @@ pub enum FolderItem {
    Inbox,
    Sent,
    Trash,
+   /// Mails flagged as spam
+   Junk,
    All,
+   /// Saved folder of the current identity, by index
+   Saved(usize),
 }
 
 impl From<FolderItem> for usize {
@@ -30,7 +38,9 @@ impl From<FolderItem> for usize {
          FolderItem::Inbox => 0,
          FolderItem::Sent => 1,
          FolderItem::Trash => 2,
-         FolderItem::All => 3,
+         FolderItem::Junk => 3,
+         FolderItem::All => 4,
+         FolderItem::Saved(index) => 5 + index,
       }
    }
 }
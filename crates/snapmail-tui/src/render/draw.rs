@@ -24,7 +24,7 @@ pub fn draw(
    chain: &SnapmailChain,
    app: &mut App,
 ) {
-   let menu_titles = vec!["View", "Write", "Edit Settings", "Quit"];
+   let menu_titles = vec!["View", "Write", "Edit Settings", "Logs", "Online peers", "Quit"];
    /// Set vertical layout
    let size = main_rect.size();
    let chunks = Layout::default()
@@ -47,19 +47,22 @@ pub fn draw(
             Span::styled(
                first,
                Style::default()
-                  .fg(Color::Yellow)
+                  .fg(app.accent_color())
                   .add_modifier(Modifier::UNDERLINED),
             ),
             Span::styled(rest, Style::default()),
          ])
       })
       .collect();
-   let title = format!("Snapmail {} - {} - {} - {} - {}", SNAPMAIL_VERSION,
-                       app.sid, app.uid, chain.my_handle.clone(), app.peer_count);
+   let mut title = format!("Snapmail {} - {} - {} - {} - {}", SNAPMAIL_VERSION,
+                       app.account, app.uid, chain.my_handle.clone(), app.peer_count);
+   if app.all_accounts {
+      title += " - All accounts";
+   }
    let tabs = Tabs::new(top_menu)
       .select(app.active_menu_item.to_owned().into())
       .block(Block::default().title(title).borders(Borders::ALL))
-      .highlight_style(Style::default().fg(Color::Yellow))
+      .highlight_style(Style::default().fg(app.accent_color()))
       .divider(Span::raw("|"));
    main_rect.render_widget(tabs, chunks[0]);
 
@@ -87,6 +90,8 @@ pub fn draw(
    match app.active_menu_item {
       TopMenuItem::View => render_view(chain, main_rect, chunks[1], app),
       TopMenuItem::Write => render_write(chain, main_rect, chunks[1], app),
-      TopMenuItem::Settings => render_settings(app, main_rect, chunks[1]),
+      TopMenuItem::Settings => render_settings(chain, app, main_rect, chunks[1]),
+      TopMenuItem::Logs => render_logs(app, main_rect, chunks[1]),
+      TopMenuItem::Peers => render_peers(app, main_rect, chunks[1]),
    }
 }
@@ -1,9 +1,13 @@
 mod render_view;
 mod render_settings;
 mod render_write;
+mod render_logs;
+mod render_peers;
 mod draw;
 
 pub use draw::*;
 pub use render_view::*;
 pub use render_settings::*;
 pub use render_write::*;
+pub use render_logs::*;
+pub use render_peers::*;
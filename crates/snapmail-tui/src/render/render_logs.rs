@@ -0,0 +1,57 @@
+use std::io;
+use tui::{
+   Frame,
+   backend::CrosstermBackend,
+   layout::{Alignment, Rect},
+   style::{Color, Style},
+   text::{Span, Spans},
+   widgets::{
+      Block, BorderType, Borders, Paragraph, Wrap,
+   },
+};
+use crate::app::App;
+
+///
+pub fn render_logs(
+   app: &mut App,
+   main_rect: &mut Frame<CrosstermBackend<io::Stdout>>,
+   area: Rect,
+) {
+   /// Clamp scrolling to available lines
+   let visible_count = area.height.saturating_sub(2) as usize;
+   let total = app.log_buffer.len();
+   app.logs_scroll = std::cmp::min(app.logs_scroll, total.saturating_sub(visible_count));
+
+   /// Get lines to display, last line at the bottom
+   let lines = app.log_buffer.last_lines(visible_count + app.logs_scroll);
+   let lines: Vec<Spans> = lines
+      .iter()
+      .take(visible_count)
+      .map(|line| {
+         let style = if line.contains("ERROR") {
+            Style::default().fg(Color::Red)
+         } else if line.contains("WARN") {
+            Style::default().fg(Color::Yellow)
+         } else if line.contains("DEBUG") || line.contains("TRACE") {
+            Style::default().fg(Color::DarkGray)
+         } else {
+            Style::default()
+         };
+         Spans::from(Span::styled(line.clone(), style))
+      })
+      .collect();
+
+   let title = format!("Logs: {} lines (Up/Down to scroll)", total);
+   let logs_block = Paragraph::new(lines)
+      .wrap(Wrap {trim: false})
+      .alignment(Alignment::Left)
+      .block(
+         Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_type(BorderType::Plain),
+      );
+
+   /// Render
+   main_rect.render_widget(logs_block, area);
+}
@@ -0,0 +1,94 @@
+use std::io;
+use tui::{
+   Frame,
+   backend::CrosstermBackend,
+   layout::{Alignment, Constraint, Direction, Layout, Rect},
+   style::{Color, Modifier, Style},
+   text::{Span, Spans},
+   widgets::{
+      Block, BorderType, Borders, Paragraph, Wrap,
+   },
+};
+use crate::app::App;
+
+/// Date format of agent info times
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+///
+pub fn render_peers(
+   app: &App,
+   main_rect: &mut Frame<CrosstermBackend<io::Stdout>>,
+   area: Rect,
+) {
+   let chunks = Layout::default()
+      .direction(Direction::Vertical)
+      .constraints([
+         Constraint::Length(app.service_checks.len() as u16 + 2),
+         Constraint::Min(4),
+      ].as_ref())
+      .split(area);
+
+   /// Network services
+   let service_lines: Vec<Spans> = app.service_checks
+      .iter()
+      .map(|check| {
+         let (status, color) = if check.is_ok { (" OK ", Color::Green) } else { ("FAIL", Color::Red) };
+         Spans::from(vec![
+            Span::styled(format!("[{}] ", status), Style::default().fg(color)),
+            Span::styled(format!("{:<9} ", check.service), Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!("{} - {}", check.url.as_deref().unwrap_or("-"), check.message)),
+         ])
+      })
+      .collect();
+   let services_block = Paragraph::new(service_lines)
+      .alignment(Alignment::Left)
+      .block(
+         Block::default()
+            .borders(Borders::ALL)
+            .title("Network services")
+            .border_type(BorderType::Plain),
+      );
+   main_rect.render_widget(services_block, chunks[0]);
+
+   /// Known agents
+   let mut peer_lines: Vec<Spans> = Vec::new();
+   for peer in app.peers.iter() {
+      let name = peer.handle.clone().unwrap_or("<unknown>".to_string());
+      let me_str = if peer.is_me { " (me)" } else { "" };
+      let style = if peer.is_expired() {
+         Style::default().fg(Color::DarkGray)
+      } else {
+         Style::default().fg(app.accent_color())
+      };
+      peer_lines.push(Spans::from(vec![
+         Span::styled(format!("{}{}", name, me_str), style.add_modifier(Modifier::BOLD)),
+         Span::raw(format!(" - {}", peer.agent)),
+      ]));
+      peer_lines.push(Spans::from(format!("   storage arc: {}", peer.storage_arc)));
+      peer_lines.push(Spans::from(format!("   signed at: {}  expires at: {}{}",
+         peer.signed_at.format(TIME_FORMAT),
+         peer.expires_at.format(TIME_FORMAT),
+         if peer.is_expired() { " (expired)" } else { "" },
+      )));
+      for url in peer.urls.iter() {
+         peer_lines.push(Spans::from(format!("   url: {}", url)));
+      }
+   }
+   if app.peers.iter().all(|peer| peer.is_me) {
+      peer_lines.push(Spans::from(Span::styled(
+         "No other agent known: check the network services above, or wait for other agents to come online.",
+         Style::default().fg(Color::Yellow),
+      )));
+   }
+   let title = format!("Known agents: {} ('o' to refresh)", app.peers.len());
+   let peers_block = Paragraph::new(peer_lines)
+      .wrap(Wrap {trim: false})
+      .alignment(Alignment::Left)
+      .block(
+         Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_type(BorderType::Plain),
+      );
+   main_rect.render_widget(peers_block, chunks[1]);
+}
@@ -12,9 +12,12 @@ use tui::{
 use crate::app::{
    InputMode, App,
 };
+use crate::snapmail_chain::SnapmailChain;
+use snapmail_common::settings::AckPolicy;
 
 ///
 pub fn render_settings(
+   chain: &SnapmailChain,
    app: &App,
    main_rect: &mut Frame<CrosstermBackend<io::Stdout>>,
    area: Rect,
@@ -26,28 +29,42 @@ pub fn render_settings(
       )
       .split(area);
 
-   let items = vec!["Handle", "UID", "Proxy URL", "Bootstrap URL", "Download Folder"];
+   /// (key, label, current value)
+   let items = vec![
+      ('h', "Handle", chain.my_handle.clone()),
+      ('u', "UID", app.uid.clone()),
+      ('p', "Proxy URL", app.proxy_url.clone()),
+      ('b', "Bootstrap URL", app.bootstrap_url.clone()),
+      ('d', "Download Folder", app.download_folder.to_string_lossy().to_string()),
+      ('g', "Signature", app.settings.signature.clone()),
+      ('r', "Default Recipients", app.settings.default_recipients.join(", ")),
+      ('k', "Ack Policy", match app.settings.ack_policy {
+         AckPolicy::PerContact => format!("PerContact ({})", app.settings.ack_contacts.join(", ")),
+         policy => format!("{:?}", policy),
+      }),
+      ('m', "Theme", format!("{:?}", app.settings.theme)),
+   ];
 
    let items: Vec<Spans> = items
       .iter()
-      .map(|&item| {
-         let (first, rest) = item.split_at(1);
+      .map(|(key, label, value)| {
          let span =
             Spans::from(vec![
                Span::styled(
-                  first,
+                  key.to_string(),
                   Style::default()
-                     .fg(Color::Yellow)
+                     .fg(app.accent_color())
                      .add_modifier(Modifier::UNDERLINED),
                ),
-               Span::styled(rest, Style::default()),
+               Span::styled(format!(" - {:<20}", label), Style::default()),
+               Span::styled(value.clone(), Style::default().fg(Color::Gray)),
             ]);
          span
       })
       .collect();
 
    let top = Paragraph::new(items)
-      .alignment(Alignment::Center)
+      .alignment(Alignment::Left)
       .block(
          Block::default()
             .borders(Borders::ALL)
@@ -60,7 +77,7 @@ pub fn render_settings(
          Block::default()
             .borders(Borders::ALL)
             .style(match app.input_mode {
-               InputMode::Editing => Style::default().fg(Color::Yellow),
+               InputMode::Editing => Style::default().fg(app.accent_color()),
                _ => Style::default(),
             })
             .title(app.input_variable.to_string())
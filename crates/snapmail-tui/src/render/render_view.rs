@@ -42,8 +42,8 @@ pub fn render_view(
       .split(vert_chunks[2]);
 
    /// -- Set top menu
-   let menu_titles = vec!["Inbox", "Sent", "Trash", "All"];
-   let top_menu = menu_titles
+   let menu_titles = vec!["Inbox", "Sent", "Trash", "Junk", "All"];
+   let mut top_menu: Vec<Spans> = menu_titles
       .iter()
       .map(|t| {
          let (first, rest) = t.split_at(1);
@@ -58,6 +58,10 @@ pub fn render_view(
          ])
       })
       .collect();
+   /// Saved folders are cycled through with the 'f' key
+   for folder in app.saved_folders.iter() {
+      top_menu.push(Spans::from(Span::styled(folder.name.clone(), Style::default().add_modifier(Modifier::ITALIC))));
+   }
    let filebox_title = format!("Filebox: {} / {}", app.mail_table.items.len(), chain.mail_map.len());
    let tabs = Tabs::new(top_menu)
       .select(app.active_folder_item.to_owned().into())
@@ -69,7 +73,12 @@ pub fn render_view(
    let selected_style = Style::default().add_modifier(Modifier::REVERSED);
    let normal_style = Style::default().bg(Color::Magenta);
 
-   let header_cells = ["", "From", "Subject", "Message", "Date"]
+   let header_titles: &[&str] = if app.mail_table.show_account {
+      &["", "Account", "From", "Subject", "Message", "Date"]
+   } else {
+      &["", "From", "Subject", "Message", "Date"]
+   };
+   let header_cells = header_titles
       .iter()
       .map(|h| Cell::from(*h).style(Style::default().fg(Color::White).add_modifier(Modifier::BOLD)));
    let header = Row::new(header_cells)
@@ -77,7 +86,7 @@ pub fn render_view(
       .height(1)
       .bottom_margin(0);
 
-   let rows = app.mail_table.items.iter().map(|item| {
+   let rows = app.mail_table.items.iter().enumerate().map(|(index, item)| {
       let height = item
          .iter()
          .map(|content| content.chars().filter(|c| *c == '\n').count())
@@ -85,16 +94,25 @@ pub fn render_view(
          .unwrap_or(0)
          + 1;
       let cells = item.iter().map(|c| Cell::from(c.as_str()));
-      Row::new(cells).height(height as u16).bottom_margin(0)
+      let style = if app.mail_table.unread_rows.contains(&index) {
+         Style::default().add_modifier(Modifier::BOLD)
+      } else {
+         Style::default()
+      };
+      Row::new(cells).style(style).height(height as u16).bottom_margin(0)
    });
-   let msg_width = area.width.saturating_sub(4 + 20 + 28 + 16 + 5);
-   let mail_table_widths = [
-      Constraint::Length(4),
+   let account_width = if app.mail_table.show_account { 13 } else { 0 };
+   let msg_width = area.width.saturating_sub(4 + account_width + 20 + 28 + 16 + 5);
+   let mut mail_table_widths = vec![Constraint::Length(4)];
+   if app.mail_table.show_account {
+      mail_table_widths.push(Constraint::Length(account_width - 1));
+   }
+   mail_table_widths.append(&mut vec![
       Constraint::Length(20),
       Constraint::Length(28),
       Constraint::Length(msg_width),
       Constraint::Length(16),
-   ];
+   ]);
    let table = Table::new(rows)
       .header(header)
       .block(Block::default().borders(Borders::NONE).title(""))
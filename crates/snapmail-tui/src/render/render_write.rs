@@ -16,7 +16,7 @@ use crate::{
 
 ///
 pub fn render_write(
-   _chain: &SnapmailChain,
+   chain: &SnapmailChain,
    main_rect: &mut Frame<CrosstermBackend<io::Stdout>>,
    area: Rect,
    app: &mut App,
@@ -37,7 +37,7 @@ pub fn render_write(
                WriteBlock::Subject => Style::default().fg(Color::Yellow),
                _ => Style::default(),
             })
-            .title("Subject")
+            .title(format!("Subject - From: {} ({})", chain.my_handle, app.account))
             .border_type(BorderType::Plain),
       );
 
@@ -114,6 +114,7 @@ pub fn render_write(
          //Constraint::Min(10),
          Constraint::Length(5),
          Constraint::Length(20),
+         Constraint::Length(12),
       ]);
 
    /// - Layout
@@ -1,7 +1,6 @@
 use crossterm::{
    event::{self, Event as CEvent, KeyCode},
 };
-use std::path::Path;
 use std::sync::mpsc;
 use std::io;
 use std::time::{Duration, Instant};
@@ -13,13 +12,16 @@ use tui::{
 };
 use snapmail_common::{
    globals::*,
-   conductor::*,
+   logging::LogBuffer,
+   settings::{AckPolicy, Theme, SessionSettings},
+   config::NetworkChange,
 };
+use url2::Url2;
 use crate::{
    menu::*,
    app::InputMode, app::InputVariable,
    app::AppCommand, app::App,
-   snapmail_chain::*,
+   accounts::Accounts,
    listen_signal::listen_signal,
    render::draw,
 };
@@ -34,16 +36,23 @@ enum Event<I> {
 ///
 pub async fn run(
    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
-   sid: String,
+   names: Vec<String>,
+   log_buffer: LogBuffer,
 ) -> Result<(), Box<dyn std::error::Error>> {
    /// - Startup holochain
-   let (conductor, dna_hash) = start_conductor_or_abort(sid.clone()).await;
-   let mut chain = SnapmailChain::from_latest(conductor.clone()).await;
+   let (signal_tx, signal_rx) = mpsc::channel();
+   let mut accounts = Accounts::start(names.clone(), signal_tx.clone()).await?;
+   let name = names[0].clone();
    terminal.clear()?;
 
    /// - Setup UI
-   let mut app = App::new(sid, &chain);
-   app.feedback(&format!("Welcome to Snapmail! - {:?}", dna_hash));
+   let all_accounts = accounts.len() > 1;
+   let mut app = App::new(name, all_accounts, &accounts, log_buffer);
+   let mut chain = accounts.view_chain(&app.account, app.all_accounts);
+   let dna_str = accounts.get(&name).maybe_cell.as_ref()
+      .map(|cell| cell.dna_hash().to_string())
+      .unwrap_or_default();
+   app.feedback(&format!("Welcome to Snapmail! - {}", dna_str));
 
    /// Setup input loop
    let (input_tx, input_rx) = mpsc::channel();
@@ -68,27 +77,40 @@ pub async fn run(
       }
    });
 
-   /// Setup Signal receive loop
-   let (signal_tx, signal_rx) = mpsc::channel();
-   let conductor_c = conductor.clone();
-   tokio::spawn(async move {
-      let _res = listen_signal(conductor_c,  signal_tx).await;
-   });
+   /// Setup Signal receive loop for each account
+   for account in accounts.list.iter() {
+      let Some(cell_c) = account.maybe_cell.clone() else { continue };
+      let signal_tx_c = signal_tx.clone();
+      let sid = account.sid.clone();
+      let prefix = accounts.signal_prefix(&account.name);
+      tokio::spawn(async move {
+         let _res = listen_signal(sid, cell_c, prefix, signal_tx_c).await;
+      });
+   }
+
+   /// Setup presence sweeps for each account
+   for account in accounts.list.iter() {
+      let interval = SessionSettings::load(&account.sid).unwrap_or_default().timeouts.presence_sweep();
+      let _ = account.presence.spawn(interval);
+   }
 
    /// Render loop
    loop {
       app.frame_count += 1;
-      app.peer_count = dump_state(conductor.clone());
+      app.peer_count = accounts.get(&app.account).api.peer_count().unwrap_or(0);
+      app.contacts_table.update_presence(&accounts.get(&app.account).presence);
+      app.update_peers();
 
       /// Render
       terminal.draw(|main_rect| {
          draw(main_rect, &chain, &mut app);
       })?;
       /// Process Command
-      let can_update_chain = app.process_command(conductor.clone(), &chain);
+      let can_update_chain = app.process_command(&accounts, &chain);
       if can_update_chain {
-         chain = SnapmailChain::from_latest(conductor.clone()).await;
-         app.update_data(&chain);
+         accounts.refresh().await;
+         chain = accounts.view_chain(&app.account, app.all_accounts);
+         app.update_data(&chain, &accounts);
       }
 
       /// Check if input received
@@ -107,57 +129,93 @@ pub async fn run(
 
       match input_mode {
          InputMode::Navigation => {
-            match key_code  {
+            /// Any other key cancels emptying the trash
+            if key_code != KeyCode::Null && app.resolve_key(key_code) != KeyCode::Char('x') {
+               app.confirm_empty_trash = false;
+            }
+            match app.resolve_key(key_code) {
                /// Top Menu
                KeyCode::Esc |
                KeyCode::Char('q') => return Ok(()),
                KeyCode::Char('v') => app.active_menu_item = TopMenuItem::View,
                KeyCode::Char('w') => app.active_menu_item = TopMenuItem::Write,
                KeyCode::Char('e') => app.active_menu_item = TopMenuItem::Settings,
+               KeyCode::Char('l') => app.active_menu_item = TopMenuItem::Logs,
+               KeyCode::Char('o') => {
+                  app.active_menu_item = TopMenuItem::Peers;
+                  app.refresh_peers(&accounts);
+               },
+               /// Identities
+               KeyCode::Char('n') => {
+                  if accounts.len() > 1 {
+                     let next_name = accounts.next_name(&app.account);
+                     app.switch_identity(next_name, &accounts);
+                     chain = accounts.view_chain(&app.account, app.all_accounts);
+                     app.update_data(&chain, &accounts);
+                     if app.active_menu_item == TopMenuItem::Peers {
+                        app.refresh_peers(&accounts);
+                     }
+                     app.feedback(&format!("Current identity: {}", app.account));
+                  }
+               },
+               KeyCode::Char('c') => {
+                  if accounts.len() > 1 {
+                     app.all_accounts = !app.all_accounts;
+                     chain = accounts.view_chain(&app.account, app.all_accounts);
+                     app.update_data(&chain, &accounts);
+                     let view = if app.all_accounts { "All accounts".to_string() } else { app.account.clone() };
+                     app.feedback(&format!("Viewing: {}", view));
+                  }
+               },
                /// Attachments
+               KeyCode::Char('0') => {
+                  if app.active_menu_item == TopMenuItem::View {
+                     app.download_all(&accounts, &chain)
+                  }
+               },
                KeyCode::Char('1') => {
                   if app.active_menu_item == TopMenuItem::View {
-                     app.try_download(conductor.clone(), 0)
+                     app.try_download(&accounts, &chain, 0)
                   }
                },
                KeyCode::Char('2') => {
                   if app.active_menu_item == TopMenuItem::View {
-                     app.try_download(conductor.clone(), 1)
+                     app.try_download(&accounts, &chain, 1)
                   }
                },
                KeyCode::Char('3') => {
                   if app.active_menu_item == TopMenuItem::View {
-                     app.try_download(conductor.clone(), 2)
+                     app.try_download(&accounts, &chain, 2)
                   }
                },
                KeyCode::Char('4') => {
                   if app.active_menu_item == TopMenuItem::View {
-                     app.try_download(conductor.clone(), 3)
+                     app.try_download(&accounts, &chain, 3)
                   }
                },
                KeyCode::Char('5') => {
                   if app.active_menu_item == TopMenuItem::View {
-                     app.try_download(conductor.clone(), 4)
+                     app.try_download(&accounts, &chain, 4)
                   }
                },
                KeyCode::Char('6') => {
                   if app.active_menu_item == TopMenuItem::View {
-                     app.try_download(conductor.clone(), 5)
+                     app.try_download(&accounts, &chain, 5)
                   }
                },
                KeyCode::Char('7') => {
                   if app.active_menu_item == TopMenuItem::View {
-                     app.try_download(conductor.clone(), 6)
+                     app.try_download(&accounts, &chain, 6)
                   }
                },
                KeyCode::Char('8') => {
                   if app.active_menu_item == TopMenuItem::View {
-                     app.try_download(conductor.clone(), 7)
+                     app.try_download(&accounts, &chain, 7)
                   }
                },
                KeyCode::Char('9') => {
                   if app.active_menu_item == TopMenuItem::View {
-                     app.try_download(conductor.clone(), 8)
+                     app.try_download(&accounts, &chain, 8)
                   }
                },
                /// View Menu
@@ -170,11 +228,33 @@ pub async fn run(
                KeyCode::Char('t') => {
                   app.update_active_folder(&chain, FolderItem::Trash)
                },
+               KeyCode::Char('j') => {
+                  app.update_active_folder(&chain, FolderItem::Junk)
+               },
                KeyCode::Char('a') => {
                   app.update_active_folder(&chain, FolderItem::All)
                },
+               KeyCode::Char('f') => {
+                  if app.active_menu_item == TopMenuItem::View {
+                     app.next_saved_folder(&chain);
+                  }
+               },
+               /// Trash
+               KeyCode::Char('x') => {
+                  if app.active_menu_item == TopMenuItem::View && app.active_folder_item == FolderItem::Trash {
+                     if app.confirm_empty_trash {
+                        app.command = AppCommand::EmptyTrash;
+                     } else {
+                        app.feedback_ext("Press 'x' again to delete all trashed mails for good", Color::Yellow, Color::Black);
+                     }
+                     app.confirm_empty_trash = !app.confirm_empty_trash;
+                  }
+               },
                /// Write Screen
                KeyCode::Insert => {
+                  if app.active_menu_item == TopMenuItem::View && app.active_folder_item == FolderItem::Trash {
+                     app.command = AppCommand::RestoreMail;
+                  }
                   if app.active_menu_item == TopMenuItem::Write {
                      app.feedback_ext("Sending mail...", Color::White, Color::Blue);
                      app.command = AppCommand::SendMail;
@@ -188,10 +268,20 @@ pub async fn run(
                },
                /// Settings Screen
                KeyCode::Char('b') => {
+                  if app.active_menu_item == TopMenuItem::View {
+                     app.toggle_block_selected_sender(&chain);
+                  }
                   if app.active_menu_item == TopMenuItem::Settings {
                      app.input_variable = InputVariable::BoostrapUrl;
                      app.input_mode = InputMode::Editing;
-                     app.input = String::new();
+                     app.input = app.bootstrap_url.clone();
+                  }
+               },
+               KeyCode::Char('p') => {
+                  if app.active_menu_item == TopMenuItem::Settings {
+                     app.input_variable = InputVariable::ProxyUrl;
+                     app.input_mode = InputMode::Editing;
+                     app.input = app.proxy_url.clone();
                   }
                },
                KeyCode::Char('h') => {
@@ -202,6 +292,9 @@ pub async fn run(
                   }
                },
                KeyCode::Char('u') => {
+                  if app.active_menu_item == TopMenuItem::View {
+                     app.unread_selected_mail();
+                  }
                   if app.active_menu_item == TopMenuItem::Settings {
                      app.input_variable = InputVariable::Uid;
                      app.input_mode = InputMode::Editing;
@@ -215,17 +308,61 @@ pub async fn run(
                      app.input = app.download_folder.clone().into_os_string().into_string().unwrap();
                   }
                },
+               KeyCode::Char('g') => {
+                  if app.active_menu_item == TopMenuItem::Settings {
+                     app.input_variable = InputVariable::Signature;
+                     app.input_mode = InputMode::Editing;
+                     app.input = app.settings.signature.clone();
+                  }
+               },
+               KeyCode::Char('r') => {
+                  if app.active_menu_item == TopMenuItem::Settings {
+                     app.input_variable = InputVariable::DefaultRecipients;
+                     app.input_mode = InputMode::Editing;
+                     app.input = app.settings.default_recipients.join(", ");
+                  }
+               },
+               KeyCode::Char('k') => {
+                  if app.active_menu_item == TopMenuItem::View {
+                     app.acknowledge_selected_mail();
+                  }
+                  if app.active_menu_item == TopMenuItem::Settings {
+                     app.settings.ack_policy = match app.settings.ack_policy {
+                        AckPolicy::Always => AckPolicy::Manual,
+                        AckPolicy::Manual => AckPolicy::PerContact,
+                        AckPolicy::PerContact => AckPolicy::Always,
+                     };
+                     app.save_settings();
+                     app.feedback(&format!("Ack policy: {:?}", app.settings.ack_policy));
+                  }
+               },
+               KeyCode::Char('m') => {
+                  if app.active_menu_item == TopMenuItem::Settings {
+                     app.settings.theme = match app.settings.theme {
+                        Theme::Dark => Theme::Light,
+                        Theme::Light => Theme::Dark,
+                     };
+                     app.save_settings();
+                     app.feedback(&format!("Theme: {:?}", app.settings.theme));
+                  }
+               },
                /// View Screen
                KeyCode::Down => {
                   if app.active_menu_item == TopMenuItem::View {
                      app.next_mail(&chain);
 
                   }
+                  if app.active_menu_item == TopMenuItem::Logs {
+                     app.logs_scroll = app.logs_scroll.saturating_sub(1);
+                  }
                },
                KeyCode::Up => {
                   if app.active_menu_item == TopMenuItem::View {
                      app.previous_mail(&chain);
                   }
+                  if app.active_menu_item == TopMenuItem::Logs {
+                     app.logs_scroll = app.logs_scroll.saturating_add(1);
+                  }
                },
                KeyCode::Delete => {
                   if app.active_menu_item == TopMenuItem::View {
@@ -236,6 +373,7 @@ pub async fn run(
                KeyCode::Enter => {
                   if app.active_menu_item == TopMenuItem::View {
                      app.input_mode = InputMode::Scrolling;
+                     app.open_selected_mail(&chain);
                   }
                   if app.active_menu_item == TopMenuItem::Write {
                      app.feedback_ext("Sending mail...", Color::White, Color::Blue);
@@ -289,17 +427,49 @@ pub async fn run(
                         },
                         InputVariable::Uid => {
                            app.uid = app.input.clone();
-                           let config_path = Path::new(&*CONFIG_PATH).join(app.sid.clone());
-                           let app_filepath = config_path.join(APP_CONFIG_FILENAME);
+                           let session_path = config_path().join(app.sid.clone());
+                           let app_filepath = session_path.join(APP_CONFIG_FILENAME);
                            std::fs::write(app_filepath, app.uid.as_bytes()).unwrap();
                            /// Must restart conductor
                            return Ok(());
                         },
                         InputVariable::DownloadFolder => {
                            app.download_folder = PathBuf::from(app.input.clone());
-                           let config_path = Path::new(&*CONFIG_PATH).join(app.sid.clone());
-                           let app_filepath = config_path.join(APP_DL_CONFIG_FILENAME);
-                           std::fs::write(app_filepath, app.input.as_bytes()).unwrap();
+                           app.settings.download_folder = Some(app.download_folder.clone());
+                           app.save_settings();
+                        },
+                        InputVariable::BoostrapUrl => {
+                           match Url2::try_parse(app.input.trim()) {
+                              Ok(url) => app.change_network(NetworkChange {
+                                 maybe_bootstrap: Some(url),
+                                 ..Default::default()
+                              }),
+                              Err(e) => app.feedback_ext(&format!("Invalid URL: {}", e), Color::Black, Color::Red),
+                           }
+                        },
+                        InputVariable::ProxyUrl => {
+                           let change = if app.input.trim().is_empty() {
+                              Ok(NetworkChange { remove_proxy: true, ..Default::default() })
+                           } else {
+                              Url2::try_parse(app.input.trim())
+                                 .map(|url| NetworkChange { maybe_proxy: Some(url), ..Default::default() })
+                           };
+                           match change {
+                              Ok(change) => app.change_network(change),
+                              Err(e) => app.feedback_ext(&format!("Invalid URL: {}", e), Color::Black, Color::Red),
+                           }
+                        },
+                        InputVariable::Signature => {
+                           app.settings.signature = app.input.clone();
+                           app.save_settings();
+                        },
+                        InputVariable::DefaultRecipients => {
+                           app.settings.default_recipients = app.input
+                              .split(',')
+                              .map(|name| name.trim().to_string())
+                              .filter(|name| !name.is_empty())
+                              .collect();
+                           app.save_settings();
                         },
                         _ => {},
                      }
@@ -1,35 +1,55 @@
-use holochain::conductor::ConductorHandle;
 use holochain_types::dna::*;
+use snapmail_common::{
+   api::SnapmailApi,
+   trash::Trash,
+   labels::MailLabels,
+   read_flags::ReadFlags,
+   blocklist::*,
+   settings::SessionSettings,
+};
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 use snapmail::mail::entries::*;
 use snapmail::mail::*;
-use snapmail::handle::*;
+use crate::accounts::parse_account_name;
 
 ///
+#[derive(Clone, Debug)]
 pub struct SnapmailChain {
    pub my_handle: String,
    pub handle_map: HashMap<AgentPubKey, String>,
    pub mail_map: HashMap<ActionHash, MailItem>,
+   /// Account each mail belongs to
+   pub mail_sid_map: HashMap<ActionHash, String>,
+   /// Mails in the trash of their session
+   pub trashed: HashSet<ActionHash>,
+   /// Local labels of each labelled mail
+   pub labels: HashMap<ActionHash, BTreeSet<String>>,
+   /// Mails flagged as read, and all sent mails
+   pub read: HashSet<ActionHash>,
+   /// Class of each received mail: normal, from a blocked sender, or spam
+   pub mail_classes: HashMap<ActionHash, MailClass>,
 }
 
 impl SnapmailChain {
-   /// Pull latest data from the DHT and local source chain
-   pub async fn from_latest(conductor: ConductorHandle) -> SnapmailChain {
+   /// Pull latest data from the DHT and local source chain of an account.
+   /// Also returns the mails newly pulled from the inbox, for the inbox rules and auto-download to run on.
+   pub async fn from_latest(account: &str, api: &dyn SnapmailApi) -> (SnapmailChain, Vec<ActionHash>) {
       /// Get my handle
       /// Cell ID and agent pubkey
       // let cell_ids = conductor.list_cell_ids().await.expect("list_cell_ids() should work");
       // assert!(!cell_ids.is_empty());
       // let agent_pubkey = cell_ids[0].agent_pubkey().to_owned();
       //let my_handle = snapmail_get_handle(conductor.clone(), agent_pubkey).unwrap();
-      let my_handle = snapmail_get_my_handle(conductor.clone(), ()).unwrap();
+      let my_handle = api.get_my_handle().unwrap();
       /// Query DHT
-      let handle_list = snapmail_get_all_handles(conductor.clone(), ()).unwrap_or(Vec::new());
-      let _new_ack_list = snapmail_check_ack_inbox(conductor.clone(), ());
-      let _new_mail_list = snapmail_check_mail_inbox(conductor.clone(), ());
+      let handle_list = api.get_all_handles().unwrap_or(Vec::new());
+      let _new_ack_list = api.check_ack_inbox();
+      let new_mail_list = api.check_mail_inbox().unwrap_or_default();
+      let (sid, _) = parse_account_name(account);
 
-      let all_mail_list = snapmail_get_all_mails(conductor.clone(), ()).unwrap_or(Vec::new());
+      let all_mail_list = api.get_all_mails().unwrap_or(Vec::new());
       /// Change list to HashMap
       let mut handle_map = HashMap::new();
       for item in handle_list {
@@ -41,14 +61,69 @@ impl SnapmailChain {
       //                           .to_string();
       /// Change list to HashMap
       let mut mail_map = HashMap::new();
+      let mut mail_sid_map = HashMap::new();
+      let trash = Trash::load(&sid).unwrap_or_default();
+      let trashed = all_mail_list.iter()
+         .filter(|item| trash.contains(&item.address))
+         .map(|item| item.address.clone())
+         .collect();
+      let mail_labels = MailLabels::load(&sid).unwrap_or_default();
+      let labels = all_mail_list.iter()
+         .filter_map(|item| mail_labels.labels_of(&item.address).map(|labels| (item.address.clone(), labels.clone())))
+         .collect();
+      let read_flags = ReadFlags::load(&sid).unwrap_or_default();
+      let read = all_mail_list.iter()
+         .filter(|item| read_flags.is_read(item))
+         .map(|item| item.address.clone())
+         .collect();
+      let blocklist = Blocklist::load(&sid).unwrap_or_default();
+      let spam_filter = SessionSettings::load(&sid).unwrap_or_default().spam_filter;
+      let mail_classes = classify_mails(&all_mail_list, &handle_map, &blocklist, &spam_filter);
       for item in all_mail_list {
+         mail_sid_map.insert(item.address.clone(), account.to_string());
          mail_map.insert(item.address.clone(), item.clone());
       }
       /// Done
-      SnapmailChain {
+      let chain = SnapmailChain {
          my_handle,
          handle_map,
          mail_map,
+         mail_sid_map,
+         trashed,
+         labels,
+         read,
+         mail_classes,
+      };
+      (chain, new_mail_list)
+   }
+
+   /// Merge the chains of several accounts into one view.
+   /// `my_handle` is the handle of the first chain.
+   pub fn merge(chains: &[&SnapmailChain]) -> SnapmailChain {
+      let mut merged = SnapmailChain {
+         my_handle: chains.first().map(|chain| chain.my_handle.clone()).unwrap_or_default(),
+         handle_map: HashMap::new(),
+         mail_map: HashMap::new(),
+         mail_sid_map: HashMap::new(),
+         trashed: HashSet::new(),
+         labels: HashMap::new(),
+         read: HashSet::new(),
+         mail_classes: HashMap::new(),
+      };
+      for chain in chains {
+         merged.handle_map.extend(chain.handle_map.clone());
+         merged.mail_map.extend(chain.mail_map.clone());
+         merged.mail_sid_map.extend(chain.mail_sid_map.clone());
+         merged.trashed.extend(chain.trashed.clone());
+         merged.labels.extend(chain.labels.clone());
+         merged.read.extend(chain.read.clone());
+         merged.mail_classes.extend(chain.mail_classes.clone());
       }
+      merged
+   }
+
+   /// Class of a mail. Sent mails are always normal.
+   pub fn mail_class(&self, hh: &ActionHash) -> MailClass {
+      self.mail_classes.get(hh).copied().unwrap_or(MailClass::Normal)
    }
 }
\ No newline at end of file
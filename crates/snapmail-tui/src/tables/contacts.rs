@@ -3,6 +3,7 @@ use tui::{
 };
 use std::collections::HashMap;
 use holochain_types::dna::*;
+use snapmail_common::presence::PresenceTracker;
 
 pub struct ContactsTable {
    pub state: TableState,
@@ -22,6 +23,7 @@ impl ContactsTable {
          //row.push(format!("{}", mail.address));
          row.push(status);
          row.push(handle.to_string());
+         row.push(String::new());
          row
 
       }).collect();
@@ -81,6 +83,22 @@ impl ContactsTable {
       self.items[index][0] = new_state.to_string();
    }
 
+   /// Mark contacts with these handles as " to " recipients
+   pub fn select_recipients(&mut self, handles: &[String]) {
+      for row in self.items.iter_mut() {
+         if handles.contains(&row[1]) {
+            row[0] = " to ".to_string();
+         }
+      }
+   }
+
+   /// Set the presence column from the tracker's latest sweep
+   pub fn update_presence(&mut self, tracker: &PresenceTracker) {
+      for (index, agent) in self.agent_index_map.iter() {
+         self.items[*index][2] = tracker.get(agent).label();
+      }
+   }
+
    pub fn toggle_selected(&mut self) {
       if let Some(index) = self.state.selected() {
          self.toggle_state(index);
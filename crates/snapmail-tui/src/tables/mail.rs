@@ -3,7 +3,7 @@ use tui::{
 };
 use chrono::{DateTime, TimeZone, Local};
 use snapmail::mail::entries::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use holochain_types::dna::*;
 use crate::snapmail_chain::*;
 
@@ -11,20 +11,34 @@ pub struct MailTable {
    pub state: TableState,
    pub items: Vec<Vec<String>>,
    pub mail_index_map: HashMap<usize, ActionHash>,
+   /// Rows have an "Account" column (session of each mail)
+   pub show_account: bool,
+   /// Rows of mails not flagged as read
+   pub unread_rows: HashSet<usize>,
 }
 
 impl MailTable {
-   pub fn new(mails: Vec<MailItem>, handle_map: &HashMap<AgentPubKey, String>, width: usize) -> MailTable {
+   pub fn new(
+      mails: Vec<MailItem>,
+      handle_map: &HashMap<AgentPubKey, String>,
+      width: usize,
+      maybe_sid_map: Option<&HashMap<ActionHash, String>>,
+      read: &HashSet<ActionHash>,
+   ) -> MailTable {
       /// Sort mails
       let mut sorted_mails = mails.clone();
       sorted_mails.sort_by(|a, b| {a.date.cmp(&b.date)});
       /// Convert each mail item to table item
       let mut i = 0;
       let mut mail_index_map = HashMap::new();
+      let mut unread_rows = HashSet::new();
       let items: Vec<Vec<String>> = sorted_mails
          .iter()
          .map(|mail| {
          mail_index_map.insert(i, mail.address.clone());
+         if !read.contains(&mail.address) {
+            unread_rows.insert(i);
+         }
          i+= 1;
          let status = get_status_string(mail);
          /// From
@@ -56,6 +70,10 @@ impl MailTable {
          let mut row: Vec<String> = Vec::new();
          //row.push(format!("{}", mail.address));
          row.push(status);
+         if let Some(sid_map) = maybe_sid_map {
+            let sid = sid_map.get(&mail.address).cloned().unwrap_or_default();
+            row.push(if sid.chars().count() > 12 { sid.chars().take(9).collect::<String>() + "..." } else { sid });
+         }
          row.push(username);
          row.push(subject);
          row.push(message);
@@ -67,6 +85,8 @@ impl MailTable {
          state: TableState::default(),
          items,
          mail_index_map,
+         show_account: maybe_sid_map.is_some(),
+         unread_rows,
       }
    }
 
@@ -133,8 +153,15 @@ impl MailTable {
          bcc_line +=  &format!(", {}", chain.handle_map.get(&to).unwrap());
       }
       /// Subject & From
-      let mut text = format!("Subject: {}\n", item.mail.subject);
+      let mut text = String::new();
+      if self.show_account {
+         text += &format!("Account: {}\n", chain.mail_sid_map.get(hh).cloned().unwrap_or_default());
+      }
+      text += &format!("Subject: {}\n", item.mail.subject);
       text += &format!("   From: {} - {}\n", author, date_str);
+      if let Some(labels) = chain.labels.get(hh) {
+         text += &format!(" Labels: {}\n", labels.iter().cloned().collect::<Vec<String>>().join(", "));
+      }
 
       /// Add recepîents if there are some
       if to_line.len() > 9 {
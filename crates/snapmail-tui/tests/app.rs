@@ -0,0 +1,119 @@
+//! App commands on the in-memory API. No conductor needed.
+
+#![allow(unused_doc_comments)]
+
+use crossterm::event::KeyCode;
+use snapmail::mail::*;
+use snapmail_common::{
+   api::SnapmailApi,
+   logging::LogBuffer,
+   mock::*,
+   read_flags::ReadFlags,
+   settings::*,
+   test_support::sessions_root,
+};
+use snapmail_tui::{
+   accounts::Accounts,
+   app::*,
+   menu::TopMenuItem,
+};
+use std::sync::{mpsc, Arc};
+
+
+#[tokio::test(flavor = "multi_thread")]
+async fn send_then_acknowledge_from_the_app() {
+   let (_guard, _root) = sessions_root(&["alex", "billy"]);
+   let network = MockNetwork::new();
+   let alex: Arc<dyn SnapmailApi> = Arc::new(network.add_agent("alex"));
+   let billy: Arc<dyn SnapmailApi> = Arc::new(network.add_agent("billy"));
+   let settings = SessionSettings { default_recipients: vec!["billy".to_string()], ..Default::default() };
+   settings.save("alex").unwrap();
+   let (signal_tx, _signal_rx) = mpsc::channel();
+
+   /// Alex writes to its default recipient
+   let mut alex_accounts = Accounts::from_apis(vec![("alex".to_string(), alex.clone())], signal_tx.clone()).await.unwrap();
+   let chain = alex_accounts.view_chain("alex", false);
+   let mut app = App::new("alex".to_string(), false, &alex_accounts, LogBuffer::new());
+   app.write_subject = "hello".to_string();
+   app.write_content = "Hi billy".to_string();
+   app.command = AppCommand::SendMail;
+   assert!(app.process_command(&alex_accounts, &chain));
+   assert_eq!(app.command, AppCommand::None);
+   assert!(app.write_subject.is_empty());
+   alex_accounts.refresh().await;
+   let chain = alex_accounts.view_chain("alex", false);
+   let sent = chain.mail_map.values().find(|item| item.mail.subject == "hello").unwrap();
+   assert_eq!(sent.state, MailState::Out(OutMailState::AllReceived));
+
+   /// Billy acknowledges it
+   let billy_accounts = Accounts::from_apis(vec![("billy".to_string(), billy.clone())], signal_tx).await.unwrap();
+   let chain = billy_accounts.view_chain("billy", false);
+   let inmail = chain.mail_map.values().find(|item| item.mail.subject == "hello").unwrap().clone();
+   assert_eq!(inmail.mail.payload, "Hi billy");
+   let mut app = App::new("billy".to_string(), false, &billy_accounts, LogBuffer::new());
+   app.command = AppCommand::AcknowledgeMail(inmail.address.clone());
+   assert!(app.process_command(&billy_accounts, &chain));
+   let state = billy.get_all_mails().unwrap().into_iter().find(|item| item.address == inmail.address).unwrap().state;
+   assert_eq!(state, MailState::In(InMailState::AckDelivered));
+
+   /// A failed ack is reported, not taken as done
+   app.command = AppCommand::AcknowledgeMail(inmail.address.clone());
+   assert!(!app.process_command(&billy_accounts, &chain));
+   assert!(app.feedbacks.last().unwrap().0.starts_with("Failed to acknowledge mail"));
+
+   /// Read flags are local to billy's session
+   app.command = AppCommand::MarkRead(inmail.address.clone());
+   assert!(app.process_command(&billy_accounts, &chain));
+   assert!(ReadFlags::load("billy").unwrap().is_read(&inmail));
+   app.command = AppCommand::MarkUnread(inmail.address.clone());
+   assert!(app.process_command(&billy_accounts, &chain));
+   assert!(!ReadFlags::load("billy").unwrap().is_read(&inmail));
+}
+
+
+#[tokio::test(flavor = "multi_thread")]
+async fn send_without_recipient_is_aborted() {
+   let (_guard, _root) = sessions_root(&["alex"]);
+   let network = MockNetwork::new();
+   let alex: Arc<dyn SnapmailApi> = Arc::new(network.add_agent("alex"));
+   let _billy = network.add_agent("billy");
+   let (signal_tx, _signal_rx) = mpsc::channel();
+
+   let accounts = Accounts::from_apis(vec![("alex".to_string(), alex.clone())], signal_tx).await.unwrap();
+   let chain = accounts.view_chain("alex", false);
+   let mut app = App::new("alex".to_string(), false, &accounts, LogBuffer::new());
+   app.write_subject = "nobody".to_string();
+   app.command = AppCommand::SendMail;
+   assert!(!app.process_command(&accounts, &chain));
+   assert_eq!(app.input_mode, InputMode::Navigation);
+   assert!(alex.get_all_mails().unwrap().is_empty());
+   /// Draft is kept
+   assert_eq!(app.write_subject, "nobody");
+}
+
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rebound_keys_replace_default_ones() {
+   let (_guard, _root) = sessions_root(&["alex"]);
+   let network = MockNetwork::new();
+   let alex: Arc<dyn SnapmailApi> = Arc::new(network.add_agent("alex"));
+   let mut settings = SessionSettings::default();
+   settings.keybindings.insert("write".to_string(), 'y');
+   settings.keybindings.insert("unread".to_string(), 'z');
+   settings.save("alex").unwrap();
+   let (signal_tx, _signal_rx) = mpsc::channel();
+   let accounts = Accounts::from_apis(vec![("alex".to_string(), alex)], signal_tx).await.unwrap();
+   let mut app = App::new("alex".to_string(), false, &accounts, LogBuffer::new());
+
+   app.active_menu_item = TopMenuItem::View;
+   assert_eq!(app.resolve_key(KeyCode::Char('y')), KeyCode::Char('w'));
+   assert_eq!(app.resolve_key(KeyCode::Char('w')), KeyCode::Null);
+   assert_eq!(app.resolve_key(KeyCode::Char('z')), KeyCode::Char('u'));
+   assert_eq!(app.resolve_key(KeyCode::Char('u')), KeyCode::Null);
+   assert_eq!(app.resolve_key(KeyCode::Char('q')), KeyCode::Char('q'));
+   assert_eq!(app.resolve_key(KeyCode::Enter), KeyCode::Enter);
+   /// View screen keys are free on other screens
+   app.active_menu_item = TopMenuItem::Settings;
+   assert_eq!(app.resolve_key(KeyCode::Char('u')), KeyCode::Char('u'));
+   assert_eq!(app.resolve_key(KeyCode::Char('z')), KeyCode::Char('z'));
+}
@@ -1,4 +1,4 @@
-REM Build and Rustify Snapmail WASM
+REM Build and Rustify Snapmail DNA
 cargo build --release -p rustify_wasm
 
-.\target\release\rustify-wasm.exe build\snapmail-rsm\target\wasm32-unknown-unknown\release\snapmail.wasm
+.\target\release\rustify-wasm.exe build\snapmail-rsm\snapmail.dna